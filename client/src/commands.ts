@@ -15,6 +15,109 @@ export function generateGraphDot(e: Extension): Command {
   }
 }
 
+export function reloadShaderNames(e: Extension): Command {
+  return async () => {
+    await e.lspClient.sendRequest(lsp.ExecuteCommandRequest.type.method, {
+      command: 'reloadShaderNames',
+      arguments: [],
+    })
+  }
+}
+
+export function clearDiagnostics(e: Extension): Command {
+  return async () => {
+    await e.lspClient.sendRequest(lsp.ExecuteCommandRequest.type.method, {
+      command: 'clearDiagnostics',
+      arguments: [],
+    })
+  }
+}
+
+// invoked through the "Add include guard" code action, which carries the edit to apply
+export function addIncludeGuard(e: Extension): Command {
+  return async (edit: lsp.WorkspaceEdit) => {
+    await vscode.workspace.applyEdit(e.lspClient.protocol2CodeConverter.asWorkspaceEdit(edit))
+  }
+}
+
+export function checkLineMap(e: Extension): Command {
+  return async () => {
+    if (vscode.window.activeTextEditor.document.languageId != 'glsl') return
+
+    const issues = await e.lspClient.sendRequest<{ line: number, directive: string, message: string }[]>(lsp.ExecuteCommandRequest.type.method, {
+      command: 'checkLineMap',
+      arguments: [vscode.window.activeTextEditor.document.uri.path],
+    })
+
+    if (issues.length == 0) {
+      vscode.window.showInformationMessage('All #line directives are consistent')
+      return
+    }
+    issues.forEach(issue => log.warn(`line ${issue.line + 1}: ${issue.directive}: ${issue.message}`))
+    vscode.window.showWarningMessage(`Found ${issues.length} inconsistent #line directive(s), see the output log for details`)
+  }
+}
+
+export function invalidate(e: Extension): Command {
+  return async () => {
+    if (vscode.window.activeTextEditor.document.languageId != 'glsl') return
+
+    await e.lspClient.sendRequest(lsp.ExecuteCommandRequest.type.method, {
+      command: 'invalidate',
+      arguments: [vscode.window.activeTextEditor.document.uri.path],
+    })
+  }
+}
+
+export function validateAll(e: Extension): Command {
+  return async () => {
+    const token = `validateAll-${Date.now()}`
+
+    await vscode.window.withProgress({
+      location: vscode.ProgressLocation.Notification,
+      title: 'Validating shaders',
+    }, async progress => {
+      let done = 0
+      const listener = e.lspClient.onProgress(lsp.WorkDoneProgress.type, token, value => {
+        if (value.kind == 'end' || value.percentage === undefined) return
+        progress.report({ message: value.message, increment: value.percentage - done })
+        done = value.percentage
+      })
+
+      try {
+        await e.lspClient.sendRequest(lsp.ExecuteCommandRequest.type.method, {
+          command: 'validateAll',
+          arguments: [],
+          workDoneToken: token,
+        })
+      } finally {
+        listener.dispose()
+      }
+    })
+  }
+}
+
+export function rawCompileLog(e: Extension): Command {
+  return async () => {
+    if (vscode.window.activeTextEditor.document.languageId != 'glsl') return
+
+    const compileLog = await e.lspClient.sendRequest<string>(lsp.ExecuteCommandRequest.type.method, {
+      command: 'rawCompileLog',
+      arguments: [vscode.window.activeTextEditor.document.uri.path],
+    })
+
+    if (compileLog.length == 0) {
+      vscode.window.showInformationMessage('The driver emitted no compile log')
+      return
+    }
+    const doc = await vscode.workspace.openTextDocument({ content: compileLog })
+    await vscode.window.showTextDocument(doc, {
+      viewColumn: vscode.ViewColumn.Two,
+      preview: true
+    })
+  }
+}
+
 export function restartExtension(e: Extension): Command {
   return async () => {
     vscode.window.showInformationMessage('Reloading Minecraft GLSL language server...')
@@ -29,7 +132,7 @@ export function virtualMergedDocument(e: Extension): Command {
     try {
       content = await e.lspClient.sendRequest<string>(lsp.ExecuteCommandRequest.type.method, {
         command: 'virtualMerge',
-        arguments: [path]
+        arguments: [path, vscode.workspace.getConfiguration('mcglsl').get<boolean>('indentFlattenedIncludes')]
       })
     } catch (e) { }
 
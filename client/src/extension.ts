@@ -49,6 +49,13 @@ export class Extension {
     this.registerCommand('restart', commands.restartExtension)
     this.registerCommand('virtualMerge', commands.virtualMergedDocument)
     this.registerCommand('parseTree', commands.parseTree)
+    this.registerCommand('reloadShaderNames', commands.reloadShaderNames)
+    this.registerCommand('checkLineMap', commands.checkLineMap)
+    this.registerCommand('clearDiagnostics', commands.clearDiagnostics)
+    this.registerCommand('addIncludeGuard', commands.addIncludeGuard)
+    this.registerCommand('rawCompileLog', commands.rawCompileLog)
+    this.registerCommand('invalidate', commands.invalidate)
+    this.registerCommand('validateAll', commands.validateAll)
 
     log.info('starting language server...')
 
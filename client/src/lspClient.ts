@@ -15,7 +15,11 @@ export class LanguageClient extends lsp.LanguageClient {
       outputChannel: lspOutputChannel,
       synchronize: {
         configurationSection: 'mcglsl',
-        fileEvents: workspace.createFileSystemWatcher(filewatcherGlob)
+        fileEvents: [
+          workspace.createFileSystemWatcher(filewatcherGlob),
+          // its options are defined in every shader, so all of them are validated again when it changes
+          workspace.createFileSystemWatcher('**/shaders/shaders.properties')
+        ]
       },
     })
     this.extension = ext
@@ -4,7 +4,10 @@ use slog_scope::GlobalLoggerGuard;
 use slog_term::{FullFormat, PlainSyncDecorator};
 use std::{cell::RefCell, sync::Arc};
 
-use std::io::Stderr;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Stderr};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
 use lazy_static::lazy_static;
 use slog::*;
@@ -20,9 +23,36 @@ pub fn slog_with_trace_id<F: FnOnce()>(f: F) {
 }
 
 pub fn set_logger_with_level(level: Level) -> GlobalLoggerGuard {
-    let drain = Arc::new(logger_base(level).fuse());
+    // only opening a log file can fail
+    set_logger(level, None).unwrap()
+}
+
+/// Sets the global logger to log to stderr and, if `log_file` is given, also to that file.
+/// The file is truncated when it is first opened, so that it only ever contains the logs of one session.
+/// Setting the logger again with the same file keeps writing to it, e.g. when only the level changed.
+pub fn set_logger(level: Level, log_file: Option<&Path>) -> io::Result<GlobalLoggerGuard> {
+    let drain: Arc<dyn SendSyncRefUnwindSafeDrain<Ok = (), Err = Never>> = match log_file {
+        Some(path) => {
+            let file = open_log_file(path)?;
+            Arc::new(Duplicate::new(logger_base(level), file_logger_base(level, file)).fuse())
+        }
+        None => Arc::new(logger_base(level).fuse()),
+    };
     DRAIN_SWITCH.ctrl().set(drain.clone());
-    slog_scope::set_global_logger(Logger::root(drain, o!()))
+    Ok(slog_scope::set_global_logger(Logger::root(drain, o!())))
+}
+
+// reuses the handle of the currently open log file, only opening (and truncating) a file that isn't open yet
+fn open_log_file(path: &Path) -> io::Result<File> {
+    let mut log_file = LOG_FILE.lock().unwrap();
+    if let Some((open_path, file)) = log_file.as_ref() {
+        if open_path == path {
+            return file.try_clone();
+        }
+    }
+    let file = OpenOptions::new().create(true).write(true).truncate(true).open(path)?;
+    *log_file = Some((path.to_path_buf(), file.try_clone()?));
+    Ok(file)
 }
 
 fn logger_base(level: Level) -> LevelFilter<Fuse<FullFormat<PlainSyncDecorator<Stderr>>>> {
@@ -31,6 +61,12 @@ fn logger_base(level: Level) -> LevelFilter<Fuse<FullFormat<PlainSyncDecorator<S
     drain.filter_level(level)
 }
 
+fn file_logger_base(level: Level, file: File) -> LevelFilter<Fuse<FullFormat<PlainSyncDecorator<File>>>> {
+    let plain = slog_term::PlainSyncDecorator::new(file);
+    let drain = slog_term::FullFormat::new(plain).build().fuse();
+    drain.filter_level(level)
+}
+
 thread_local! {
     static CURRENT_RNG: RefCell<rngs::ThreadRng> = RefCell::new(rngs::ThreadRng::default());
 }
@@ -40,4 +76,38 @@ lazy_static! {
         let logger = logger_base(Level::Info).fuse();
         AtomicSwitch::new(logger)
     };
+    static ref LOG_FILE: Mutex<Option<(PathBuf, File)>> = Mutex::new(None);
+}
+
+#[cfg(test)]
+mod logging_test {
+    use std::fs;
+
+    use slog::Level;
+
+    use crate::set_logger;
+
+    #[test]
+    fn test_log_file() {
+        let path = std::env::temp_dir().join(format!("mcshader-logging-test-{}.log", std::process::id()));
+        fs::write(&path, "left over from a previous session\n").unwrap();
+
+        let guard = set_logger(Level::Info, Some(&path)).unwrap();
+        slog_scope::info!("written to the log file"; "key" => "value");
+        slog_scope::debug!("filtered out by the level");
+        drop(guard);
+
+        let guard = set_logger(Level::Debug, Some(&path)).unwrap();
+        slog_scope::debug!("written after the level changed");
+        drop(guard);
+
+        let content = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(!content.contains("left over"));
+        assert!(content.contains("written to the log file"));
+        assert!(content.contains("key: value"));
+        assert!(!content.contains("filtered out"));
+        assert!(content.contains("written after the level changed"));
+    }
 }
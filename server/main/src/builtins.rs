@@ -0,0 +1,64 @@
+use std::collections::HashSet;
+
+use lazy_static::lazy_static;
+
+lazy_static! {
+    static ref BUILTIN_FUNCTIONS: HashSet<&'static str> = FUNCTIONS.iter().copied().collect();
+}
+
+// the built-in functions of GLSL 1.10 through 4.60, including those removed from the core profile
+// that the compatibility profile still has, such as `texture2D`
+#[rustfmt::skip]
+const FUNCTIONS: &[&str] = &[
+    // angle and trigonometry
+    "radians", "degrees", "sin", "cos", "tan", "asin", "acos", "atan", "sinh", "cosh", "tanh", "asinh", "acosh", "atanh",
+    // exponential
+    "pow", "exp", "log", "exp2", "log2", "sqrt", "inversesqrt",
+    // common
+    "abs", "sign", "floor", "trunc", "round", "roundEven", "ceil", "fract", "mod", "modf", "min", "max", "clamp", "mix",
+    "step", "smoothstep", "isnan", "isinf", "floatBitsToInt", "floatBitsToUint", "intBitsToFloat", "uintBitsToFloat",
+    "fma", "frexp", "ldexp",
+    // packing
+    "packUnorm2x16", "packSnorm2x16", "packUnorm4x8", "packSnorm4x8", "unpackUnorm2x16", "unpackSnorm2x16",
+    "unpackUnorm4x8", "unpackSnorm4x8", "packHalf2x16", "unpackHalf2x16", "packDouble2x32", "unpackDouble2x32",
+    // geometric
+    "length", "distance", "dot", "cross", "normalize", "ftransform", "faceforward", "reflect", "refract",
+    // matrix
+    "matrixCompMult", "outerProduct", "transpose", "determinant", "inverse",
+    // vector relational
+    "lessThan", "lessThanEqual", "greaterThan", "greaterThanEqual", "equal", "notEqual", "any", "all", "not",
+    // integer
+    "uaddCarry", "usubBorrow", "umulExtended", "imulExtended", "bitfieldExtract", "bitfieldInsert", "bitfieldReverse",
+    "bitCount", "findLSB", "findMSB",
+    // texture
+    "textureSize", "textureQueryLod", "textureQueryLevels", "textureSamples", "texture", "textureProj", "textureLod",
+    "textureOffset", "texelFetch", "texelFetchOffset", "textureProjOffset", "textureLodOffset", "textureProjLod",
+    "textureProjLodOffset", "textureGrad", "textureGradOffset", "textureProjGrad", "textureProjGradOffset",
+    "textureGather", "textureGatherOffset", "textureGatherOffsets",
+    // legacy texture
+    "texture1D", "texture1DProj", "texture1DLod", "texture1DProjLod", "texture2D", "texture2DProj", "texture2DLod",
+    "texture2DProjLod", "texture3D", "texture3DProj", "texture3DLod", "texture3DProjLod", "textureCube",
+    "textureCubeLod", "shadow1D", "shadow2D", "shadow1DProj", "shadow2DProj", "shadow1DLod", "shadow2DLod",
+    "shadow1DProjLod", "shadow2DProjLod", "texture2DGradARB", "texture2DLodEXT",
+    // atomic counter and memory
+    "atomicCounterIncrement", "atomicCounterDecrement", "atomicCounter", "atomicAdd", "atomicMin", "atomicMax",
+    "atomicAnd", "atomicOr", "atomicXor", "atomicExchange", "atomicCompSwap",
+    // image
+    "imageSize", "imageSamples", "imageLoad", "imageStore", "imageAtomicAdd", "imageAtomicMin", "imageAtomicMax",
+    "imageAtomicAnd", "imageAtomicOr", "imageAtomicXor", "imageAtomicExchange", "imageAtomicCompSwap",
+    // geometry shader
+    "EmitStreamVertex", "EndStreamPrimitive", "EmitVertex", "EndPrimitive",
+    // fragment processing
+    "dFdx", "dFdy", "dFdxFine", "dFdyFine", "dFdxCoarse", "dFdyCoarse", "fwidth", "fwidthFine", "fwidthCoarse",
+    "interpolateAtCentroid", "interpolateAtSample", "interpolateAtOffset",
+    // noise
+    "noise1", "noise2", "noise3", "noise4",
+    // shader invocation control and memory barriers
+    "barrier", "memoryBarrier", "memoryBarrierAtomicCounter", "memoryBarrierBuffer", "memoryBarrierShared",
+    "memoryBarrierImage", "groupMemoryBarrier",
+];
+
+/// Returns whether `name` is a GLSL built-in function.
+pub fn is_builtin_function(name: &str) -> bool {
+    BUILTIN_FUNCTIONS.contains(name)
+}
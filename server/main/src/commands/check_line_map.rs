@@ -0,0 +1,202 @@
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use anyhow::Result;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+use slog_scope::{info, warn};
+
+use crate::configuration::Configuration;
+use crate::graph::CachedStableGraph;
+use crate::url_norm::FromJson;
+
+use super::merged_includes::VirtualMergedDocument;
+use super::Invokeable;
+
+lazy_static! {
+    static ref RE_LINE_DIRECTIVE: Regex = Regex::new(r#"^\s*#line\s+(\d+)\s+(\d+)(?:\s*//\s*(.*))?$"#).unwrap();
+}
+
+/// A `#line` directive in a merged shader that doesn't agree with the files that were merged.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct LineMapIssue {
+    // the 0-indexed line of the directive in the merged source
+    pub line: usize,
+    pub directive: String,
+    pub message: String,
+}
+
+/// Merges a top-level shader and checks that the `#line` directives inserted while merging are
+/// consistent, returning a list of the inconsistent directives. Intended as a debugging aid for
+/// the merge logic rather than for shader authors.
+pub struct CheckLineMapCommand {
+    pub graph: Rc<RefCell<CachedStableGraph>>,
+    pub config: Rc<RefCell<Configuration>>,
+}
+
+impl Invokeable for CheckLineMapCommand {
+    fn run_command(&self, root: &Path, arguments: &[Value]) -> Result<Value> {
+        let path = PathBuf::from_json(arguments.get(0).unwrap())?;
+
+        let merger = VirtualMergedDocument {
+            graph: self.graph.clone(),
+            config: self.config.clone(),
+        };
+        let (view, sources) = merger.merge_file_with_sources(root, &path)?;
+
+        let issues = check_line_directives(&view, &sources);
+        if issues.is_empty() {
+            info!("line directives are consistent"; "path" => path.to_str().unwrap());
+        } else {
+            warn!("found inconsistent line directives"; "path" => path.to_str().unwrap(), "issues" => format!("{:?}", issues));
+        }
+
+        Ok(serde_json::to_value(issues)?)
+    }
+}
+
+/// Checks every `#line N source` directive in `merged` against the files it was merged from, where
+/// `sources` holds the path of each source number. Each directive must either open an included
+/// file at its first line, or resume the including file after the line of the include, and name the
+/// path belonging to its source number.
+pub fn check_line_directives(merged: &str, sources: &[PathBuf]) -> Vec<LineMapIssue> {
+    let mut issues = Vec::new();
+
+    // the stack of files currently being included, with the 0-indexed line that the next merged line maps to.
+    // The top-level file is always source number 0
+    let mut contexts: Vec<(usize, usize)> = vec![(0, 0)];
+
+    for (merged_line, line) in merged.lines().enumerate() {
+        let cap = match RE_LINE_DIRECTIVE.captures(line) {
+            Some(cap) => cap,
+            None => {
+                contexts.last_mut().unwrap().1 += 1;
+                continue;
+            }
+        };
+
+        let mut issue = |message: String| {
+            issues.push(LineMapIssue {
+                line: merged_line,
+                directive: line.to_string(),
+                message,
+            })
+        };
+
+        let line_num = cap.get(1).unwrap().as_str().parse::<usize>().unwrap_or(0);
+        let source_num = cap.get(2).unwrap().as_str().parse::<usize>().unwrap_or(usize::MAX);
+
+        let path = match sources.get(source_num) {
+            Some(path) => path,
+            None => {
+                issue(format!("source number {} doesn't belong to any merged file", source_num));
+                continue;
+            }
+        };
+
+        // paths are written with escaped backslashes
+        if let Some(comment) = cap.get(3) {
+            let comment_path = comment.as_str().trim().replace("\\\\", "\\");
+            if Path::new(&comment_path) != path {
+                issue(format!(
+                    "source number {} belongs to {:?}, not {:?}",
+                    source_num, path, comment_path
+                ));
+            }
+        }
+
+        if line_num == 0 {
+            issue("line numbers start at 1".into());
+            continue;
+        }
+
+        let resumes_parent = contexts.len() >= 2 && contexts[contexts.len() - 2].0 == source_num;
+        if resumes_parent {
+            contexts.pop();
+            let parent = contexts.last_mut().unwrap();
+            if line_num - 1 < parent.1 {
+                issue(format!(
+                    "resumes {:?} at line {}, before line {} which was already merged",
+                    path,
+                    line_num,
+                    parent.1 + 1
+                ));
+            }
+            parent.1 = line_num - 1;
+        } else if line_num == 1 {
+            contexts.push((source_num, 0));
+        } else {
+            issue(format!(
+                "jumps to line {} of {:?}, which is neither the start of an include nor the including file",
+                line_num, path
+            ));
+        }
+    }
+
+    if contexts.len() > 1 {
+        issues.push(LineMapIssue {
+            line: merged.lines().count(),
+            directive: String::new(),
+            message: format!("{} included file(s) never return to their including file", contexts.len() - 1),
+        });
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod check_line_map_test {
+    use std::path::PathBuf;
+
+    use serde_json::Value;
+
+    use crate::commands::check_line_map::{check_line_directives, CheckLineMapCommand};
+    use crate::commands::Invokeable;
+    use crate::test::{copy_to_and_set_root, new_temp_server};
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_check_line_map_consistent() {
+        let mut server = new_temp_server(None);
+
+        let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/09", &mut server);
+        server.build_initial_graph();
+
+        let command = CheckLineMapCommand {
+            graph: server.graph.clone(),
+            config: server.config.clone(),
+        };
+
+        let final_path = tmp_path.join("shaders").join("final.fsh");
+        let issues = command
+            .run_command(&tmp_path, &[Value::String(final_path.to_str().unwrap().to_string())])
+            .unwrap();
+        assert_eq!(issues, Value::Array(vec![]));
+
+        server.endpoint.request_shutdown();
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_check_line_map_inconsistent() {
+        let sources = vec![PathBuf::from("/shaders/final.fsh"), PathBuf::from("/shaders/a.glsl")];
+
+        let merged = "#version 120\n\
+            #line 1 1 // /shaders/a.glsl\n\
+            float a() { return 1.0; }\n\
+            #line 1 0 // /shaders/final.fsh\n\
+            #line 4 2 // /shaders/b.glsl\n\
+            #line 2 1 // /shaders/final.fsh\n";
+
+        let issues = check_line_directives(merged, &sources);
+        let lines: Vec<usize> = issues.iter().map(|issue| issue.line).collect();
+        assert_eq!(lines, vec![3, 4, 5, 5]);
+        assert!(issues[0].message.contains("before line 2"));
+        assert!(issues[1].message.contains("source number 2"));
+        assert!(issues[2].message.contains("not \"/shaders/final.fsh\""));
+        assert!(issues[3].message.contains("jumps to line 2"));
+    }
+}
@@ -0,0 +1,27 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use rust_lsp::lsp_types::{Diagnostic, ExecuteCommandParams, Url};
+use serde_json::Value;
+use slog_scope::info;
+
+use crate::MinecraftShaderLanguageServer;
+
+use super::Invokeable;
+
+/// Publishes empty diagnostics for every file that currently has diagnostics, returning the URLs of those files.
+/// Takes no arguments.
+pub struct ClearDiagnosticsCommand;
+
+impl Invokeable for ClearDiagnosticsCommand {
+    fn run_server_command(&self, server: &MinecraftShaderLanguageServer, _: &ExecuteCommandParams) -> Result<Value> {
+        let cleared: HashMap<Url, Vec<Diagnostic>> = server.diagnostics.borrow().keys().map(|uri| (uri.clone(), vec![])).collect();
+
+        let mut uris: Vec<String> = cleared.keys().map(|uri| uri.to_string()).collect();
+        uris.sort();
+        info!("clearing diagnostics"; "files" => format!("{:?}", uris));
+
+        server.publish_diagnostic(cleared, None);
+        Ok(serde_json::to_value(uris)?)
+    }
+}
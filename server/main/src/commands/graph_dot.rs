@@ -1,10 +1,12 @@
 use std::cell::RefCell;
+use std::collections::HashSet;
 use std::fs::OpenOptions;
 use std::io::prelude::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 use petgraph::dot::Config;
+use petgraph::stable_graph::NodeIndex;
 use serde_json::Value;
 
 use petgraph::dot;
@@ -13,32 +15,75 @@ use anyhow::{format_err, Result};
 use slog_scope::info;
 
 use crate::graph::CachedStableGraph;
+use crate::url_norm::FromJson;
 
 use super::Invokeable;
 
+/// Writes the include graph to `graph.dot` in the workspace root and returns it. The first argument
+/// is the workspace root, an optional second argument is the path of a shader to which the graph is
+/// narrowed down to the files it (transitively) includes.
 pub struct GraphDotCommand {
     pub graph: Rc<RefCell<CachedStableGraph>>,
 }
 
+impl GraphDotCommand {
+    // collects the given node and every node reachable from it through includes
+    fn reachable_from(&self, node: NodeIndex) -> HashSet<NodeIndex> {
+        let graph = self.graph.borrow();
+
+        let mut reachable = HashSet::new();
+        let mut stack = vec![node];
+        while let Some(node) = stack.pop() {
+            if reachable.insert(node) {
+                stack.extend(graph.child_node_indexes(node));
+            }
+        }
+        reachable
+    }
+
+    fn generate_dot(&self, focus: Option<NodeIndex>) -> String {
+        let graph = self.graph.borrow();
+
+        let content = match focus {
+            Some(focus) => {
+                let reachable = self.reachable_from(focus);
+                let subgraph = graph.graph.filter_map(
+                    |idx, name| reachable.contains(&idx).then(|| name.clone()),
+                    |_, position| Some(*position),
+                );
+                dot::Dot::with_config(&subgraph, &[Config::GraphContentOnly]).to_string()
+            }
+            None => dot::Dot::with_config(&graph.graph, &[Config::GraphContentOnly]).to_string(),
+        };
+
+        format!("digraph {{\n\tgraph [splines=ortho]\n\tnode [shape=box]\n{}\n}}", content)
+    }
+}
+
 impl Invokeable for GraphDotCommand {
-    fn run_command(&self, root: &Path, _: &[Value]) -> Result<Value> {
+    fn run_command(&self, root: &Path, arguments: &[Value]) -> Result<Value> {
+        let focus = match arguments.get(1) {
+            Some(path) => {
+                let path = PathBuf::from_json(path)?;
+                match self.graph.borrow_mut().find_node(&path) {
+                    Some(node) => Some(node),
+                    None => return Err(format_err!("shader {:?} is not in the include graph", path)),
+                }
+            }
+            None => None,
+        };
+
         let filepath = root.join("graph.dot");
 
-        info!("generating dot file"; "path" => filepath.as_os_str().to_str());
+        info!("generating dot file"; "path" => filepath.as_os_str().to_str(), "focused" => focus.is_some());
 
         let mut file = OpenOptions::new().truncate(true).write(true).create(true).open(filepath).unwrap();
 
-        let mut write_data_closure = || -> Result<(), std::io::Error> {
-            let graph = self.graph.as_ref();
+        let dot = self.generate_dot(focus);
 
+        let mut write_data_closure = || -> Result<(), std::io::Error> {
             file.seek(std::io::SeekFrom::Start(0))?;
-            file.write_all("digraph {\n\tgraph [splines=ortho]\n\tnode [shape=box]\n".as_bytes())?;
-            file.write_all(
-                dot::Dot::with_config(&graph.borrow().graph, &[Config::GraphContentOnly])
-                    .to_string()
-                    .as_bytes(),
-            )?;
-            file.write_all("\n}".as_bytes())?;
+            file.write_all(dot.as_bytes())?;
             file.flush()?;
             file.seek(std::io::SeekFrom::Start(0))?;
             Ok(())
@@ -46,7 +91,53 @@ impl Invokeable for GraphDotCommand {
 
         match write_data_closure() {
             Err(err) => Err(format_err!("error generating graphviz data: {}", err)),
-            _ => Ok(Value::Null),
+            _ => Ok(Value::String(dot)),
         }
     }
 }
+
+#[cfg(test)]
+mod graph_dot_test {
+    use serde_json::Value;
+
+    use crate::commands::graph_dot::GraphDotCommand;
+    use crate::commands::Invokeable;
+    use crate::test::{copy_to_and_set_root, new_temp_server};
+
+    fn node_count(dot: &Value) -> usize {
+        dot.as_str()
+            .unwrap()
+            .lines()
+            .filter(|line| line.contains("[ label") && !line.contains("->"))
+            .count()
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_graph_dot_focus() {
+        let mut server = new_temp_server(None);
+
+        let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/11", &mut server);
+        server.build_initial_graph();
+
+        let command = GraphDotCommand {
+            graph: server.graph.clone(),
+        };
+
+        let root = Value::String(tmp_path.to_str().unwrap().to_string());
+        let full = command.run_command(&tmp_path, &[root.clone()]).unwrap();
+        assert_eq!(node_count(&full), 5);
+
+        let shader = Value::String(tmp_path.join("shaders").join("composite.fsh").to_str().unwrap().to_string());
+        let focused = command.run_command(&tmp_path, &[root, shader]).unwrap();
+        assert_eq!(node_count(&focused), 3);
+        assert!(!focused.as_str().unwrap().contains("world0"));
+
+        assert_eq!(
+            std::fs::read_to_string(tmp_path.join("graph.dot")).unwrap(),
+            focused.as_str().unwrap()
+        );
+
+        server.endpoint.request_shutdown();
+    }
+}
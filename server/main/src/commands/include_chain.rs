@@ -0,0 +1,124 @@
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use anyhow::{format_err, Result};
+use petgraph::stable_graph::NodeIndex;
+use serde::Serialize;
+use serde_json::Value;
+use slog_scope::info;
+
+use crate::graph::CachedStableGraph;
+use crate::url_norm::FromJson;
+
+use super::Invokeable;
+
+/// A file along an include chain, and the 0-indexed line on which it includes the next file
+/// in the chain. The last file in a chain, the target, has no include line.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IncludeHop {
+    pub path: PathBuf,
+    pub include_line: Option<usize>,
+}
+
+/// Returns how a shader reaches one of its (transitive) includes, as the list of files from the
+/// shader to the include. The first argument is the path of the shader, the second the path of the
+/// include. If the include is reached in multiple ways, the shortest chain is returned.
+pub struct IncludeChainCommand {
+    pub graph: Rc<RefCell<CachedStableGraph>>,
+}
+
+impl IncludeChainCommand {
+    fn find_chain(&self, from: NodeIndex, to: NodeIndex) -> Option<Vec<IncludeHop>> {
+        let graph = self.graph.borrow();
+
+        let mut chain: Vec<IncludeHop> = graph
+            .include_chain(from, to)?
+            .into_iter()
+            .map(|(node, position)| IncludeHop {
+                path: graph.get_node(node),
+                include_line: Some(position.line),
+            })
+            .collect();
+        chain.push(IncludeHop {
+            path: graph.get_node(to),
+            include_line: None,
+        });
+
+        Some(chain)
+    }
+}
+
+impl Invokeable for IncludeChainCommand {
+    fn run_command(&self, _: &Path, arguments: &[Value]) -> Result<Value> {
+        let shader = PathBuf::from_json(arguments.get(0).unwrap())?;
+        let target = match arguments.get(1) {
+            Some(target) => PathBuf::from_json(target)?,
+            None => return Err(format_err!("expected the path of an include as second argument")),
+        };
+
+        let (shader_node, target_node) = {
+            let mut graph = self.graph.borrow_mut();
+            let shader_node = match graph.find_node(&shader) {
+                Some(node) => node,
+                None => return Err(format_err!("shader {:?} is not in the include graph", shader)),
+            };
+            let target_node = match graph.find_node(&target) {
+                Some(node) => node,
+                None => return Err(format_err!("include {:?} is not in the include graph", target)),
+            };
+            (shader_node, target_node)
+        };
+
+        info!("finding include chain"; "shader" => shader.to_str().unwrap(), "target" => target.to_str().unwrap());
+
+        match self.find_chain(shader_node, target_node) {
+            Some(chain) => Ok(serde_json::to_value(chain)?),
+            None => Err(format_err!("{:?} is not included by {:?}", target, shader)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod include_chain_test {
+    use serde_json::{json, Value};
+
+    use crate::commands::include_chain::IncludeChainCommand;
+    use crate::commands::Invokeable;
+    use crate::test::{copy_to_and_set_root, new_temp_server};
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_include_chain() {
+        let mut server = new_temp_server(None);
+
+        let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/09", &mut server);
+        server.build_initial_graph();
+
+        let command = IncludeChainCommand {
+            graph: server.graph.clone(),
+        };
+
+        let path = |file: &str| tmp_path.join("shaders").join(file).to_str().unwrap().to_string();
+
+        let chain = command
+            .run_command(&tmp_path, &[Value::String(path("final.fsh")), Value::String(path("c.glsl"))])
+            .unwrap();
+        assert_eq!(
+            chain,
+            json!([
+                { "path": path("final.fsh"), "includeLine": 2 },
+                { "path": path("a.glsl"), "includeLine": 0 },
+                { "path": path("b.glsl"), "includeLine": 0 },
+                { "path": path("c.glsl"), "includeLine": null },
+            ])
+        );
+
+        assert!(command
+            .run_command(&tmp_path, &[Value::String(path("c.glsl")), Value::String(path("final.fsh"))])
+            .is_err());
+
+        server.endpoint.request_shutdown();
+    }
+}
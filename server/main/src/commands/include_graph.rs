@@ -0,0 +1,104 @@
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+use anyhow::Result;
+use serde_json::Value;
+use slog_scope::info;
+
+use crate::graph::CachedStableGraph;
+use crate::lsp_ext::{IncludeGraphEdge, IncludeGraphResult};
+
+use super::Invokeable;
+
+/// Returns every file in the include graph and every include between them. Takes no arguments.
+pub struct IncludeGraphCommand {
+    pub graph: Rc<RefCell<CachedStableGraph>>,
+}
+
+impl IncludeGraphCommand {
+    fn include_graph(&self) -> IncludeGraphResult {
+        let graph = self.graph.borrow();
+
+        let mut nodes: Vec<_> = graph.graph.node_indices().map(|node| graph.get_node(node)).collect();
+        nodes.sort();
+
+        let mut edges: Vec<_> = graph
+            .graph
+            .node_indices()
+            .flat_map(|parent| {
+                let graph = &graph;
+                graph
+                    .get_all_child_positions(parent)
+                    .map(move |(child, position)| IncludeGraphEdge {
+                        parent: graph.get_node(parent),
+                        child: graph.get_node(child),
+                        line: position.line,
+                    })
+            })
+            .collect();
+        edges.sort_by(|a, b| (&a.parent, a.line).cmp(&(&b.parent, b.line)));
+
+        IncludeGraphResult { nodes, edges }
+    }
+}
+
+impl Invokeable for IncludeGraphCommand {
+    fn run_command(&self, _: &Path, _: &[Value]) -> Result<Value> {
+        let include_graph = self.include_graph();
+
+        info!("returning include graph"; "nodes" => include_graph.nodes.len(), "edges" => include_graph.edges.len());
+
+        Ok(serde_json::to_value(include_graph)?)
+    }
+}
+
+#[cfg(test)]
+mod include_graph_test {
+    use pretty_assertions::assert_eq;
+    use serde_json::from_value;
+
+    use crate::commands::include_graph::IncludeGraphCommand;
+    use crate::commands::Invokeable;
+    use crate::lsp_ext::{IncludeGraphEdge, IncludeGraphResult};
+    use crate::test::{copy_to_and_set_root, new_temp_server};
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_include_graph() {
+        let mut server = new_temp_server(None);
+
+        let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/09", &mut server);
+        server.build_initial_graph();
+
+        let command = IncludeGraphCommand {
+            graph: server.graph.clone(),
+        };
+
+        let result: IncludeGraphResult = from_value(command.run_command(&tmp_path, &[]).unwrap()).unwrap();
+
+        let path = |file: &str| tmp_path.join("shaders").join(file);
+        let edge = |parent: &str, child: &str, line: usize| IncludeGraphEdge {
+            parent: path(parent),
+            child: path(child),
+            line,
+        };
+
+        assert_eq!(
+            result,
+            IncludeGraphResult {
+                nodes: vec![path("a.glsl"), path("b.glsl"), path("c.glsl"), path("final.fsh")],
+                edges: vec![
+                    edge("a.glsl", "b.glsl", 0),
+                    edge("a.glsl", "b.glsl", 1),
+                    edge("b.glsl", "c.glsl", 0),
+                    edge("b.glsl", "c.glsl", 1),
+                    edge("final.fsh", "a.glsl", 2),
+                    edge("final.fsh", "a.glsl", 3),
+                ],
+            }
+        );
+
+        server.endpoint.request_shutdown();
+    }
+}
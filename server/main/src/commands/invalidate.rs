@@ -0,0 +1,43 @@
+use anyhow::{format_err, Result};
+use rust_lsp::lsp_types::ExecuteCommandParams;
+use serde_json::Value;
+use slog_scope::info;
+
+use std::path::PathBuf;
+
+use crate::url_norm::FromJson;
+use crate::MinecraftShaderLanguageServer;
+
+use super::Invokeable;
+
+/// Drops what is cached of a file, for when it changed without the client knowing, then reads it again to update
+/// its includes and lints it along with the shaders including it, publishing their diagnostics. Takes the path of
+/// the file.
+pub struct InvalidateCommand;
+
+impl Invokeable for InvalidateCommand {
+    fn run_server_command(&self, server: &MinecraftShaderLanguageServer, params: &ExecuteCommandParams) -> Result<Value> {
+        let path = match params.arguments.get(0) {
+            Some(path) => PathBuf::from_json(path)?,
+            None => return Err(format_err!("expected the path of the file to invalidate")),
+        };
+        if !server.in_workspace(&path) {
+            return Err(format_err!("{:?} is not part of the workspace", path));
+        }
+        let path = server.resolve_symlinks(&path);
+        info!("invalidating file"; "path" => path.to_str().unwrap());
+
+        server.file_cache.borrow_mut().invalidate(&path);
+        if server.graph.borrow_mut().find_node(&path).is_none() {
+            server.add_file_and_includes_to_graph(&path);
+        } else {
+            server.update_includes(&path);
+        }
+
+        if !server.is_orphan_library(&path) {
+            let diagnostics = server.lint(&path)?;
+            server.publish_diagnostic(diagnostics, None);
+        }
+        Ok(Value::Null)
+    }
+}
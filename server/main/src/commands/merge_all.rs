@@ -0,0 +1,51 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{format_err, Result};
+use rust_lsp::lsp_types::ExecuteCommandParams;
+use serde_json::Value;
+use slog_scope::error;
+
+use crate::source_mapper::SourceMapper;
+use crate::MinecraftShaderLanguageServer;
+
+use super::Invokeable;
+
+/// Merges every top-level shader in the workspace with its includes, keyed by the shader's path. Shaders that can't
+/// be merged, e.g. as their includes form a cycle or can't be read, are logged and left out. Takes no arguments.
+pub struct MergeAllCommand;
+
+impl MergeAllCommand {
+    pub fn merge_all(&self, server: &MinecraftShaderLanguageServer) -> HashMap<PathBuf, String> {
+        let mut merged = HashMap::new();
+        for path in server.toplevel_shaders() {
+            match self.merge_shader(server, &path) {
+                Ok(view) => {
+                    merged.insert(path, view);
+                }
+                Err(e) => error!("error merging shader"; "error" => format!("{:?}", e), "path" => path.to_str().unwrap()),
+            }
+        }
+        merged
+    }
+
+    // merges the top-level shader at `path` with all of its includes
+    fn merge_shader(&self, server: &MinecraftShaderLanguageServer, path: &Path) -> Result<String> {
+        let root = server
+            .graph
+            .borrow_mut()
+            .find_node(path)
+            .ok_or_else(|| format_err!("node not found {:?}", path))?;
+        let tree = server.get_dfs_for_node(root)?;
+        let sources = server.load_sources(&tree)?;
+
+        let mut source_mapper = SourceMapper::new(sources.len());
+        Ok(server.merge_tree(&tree, &sources, &mut source_mapper)?)
+    }
+}
+
+impl Invokeable for MergeAllCommand {
+    fn run_server_command(&self, server: &MinecraftShaderLanguageServer, _: &ExecuteCommandParams) -> Result<Value> {
+        Ok(serde_json::to_value(self.merge_all(server))?)
+    }
+}
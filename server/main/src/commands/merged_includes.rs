@@ -13,15 +13,17 @@ use anyhow::{format_err, Result};
 
 use std::fs;
 
+use crate::configuration::Configuration;
 use crate::dfs;
 use crate::merge_views::FilialTuple;
 use crate::source_mapper::SourceMapper;
-use crate::{graph::CachedStableGraph, merge_views, url_norm::FromJson};
+use crate::{graph::CachedStableGraph, merge_views, text_encoding, url_norm::FromJson};
 
 use super::Invokeable;
 
 pub struct VirtualMergedDocument {
     pub graph: Rc<RefCell<CachedStableGraph>>,
+    pub config: Rc<RefCell<Configuration>>,
 }
 
 impl VirtualMergedDocument {
@@ -57,7 +59,7 @@ impl VirtualMergedDocument {
                 continue;
             }
 
-            let source = match fs::read_to_string(&path) {
+            let source = match fs::read(&path).and_then(|contents| text_encoding::decode(&contents, &self.config.borrow().encoding)) {
                 Ok(s) => s,
                 Err(e) => return Err(format_err!("error reading {:?}: {}", path, e)),
             };
@@ -69,11 +71,21 @@ impl VirtualMergedDocument {
     }
 }
 
-impl Invokeable for VirtualMergedDocument {
-    fn run_command(&self, root: &Path, arguments: &[Value]) -> Result<Value> {
-        let path = PathBuf::from_json(arguments.get(0).unwrap())?;
+impl VirtualMergedDocument {
+    /// Merges the top-level file at `path` with all of its includes.
+    pub fn merge_file(&self, root: &Path, path: &Path) -> Result<String> {
+        self.merge_file_with_sources(root, path).map(|(view, _)| view)
+    }
+
+    /// Merges the top-level file at `path` with all of its includes, also returning the path
+    /// of each file in the merged source, indexed by the source number used in its `#line` directives.
+    pub fn merge_file_with_sources(&self, root: &Path, path: &Path) -> Result<(String, Vec<PathBuf>)> {
+        self.merge(root, path, false)
+    }
 
-        let file_ancestors = match self.get_file_toplevel_ancestors(&path) {
+    // merges the top-level file at `path`, indenting included content to its `#include` if `indent_includes` is set
+    fn merge(&self, root: &Path, path: &Path, indent_includes: bool) -> Result<(String, Vec<PathBuf>)> {
+        let file_ancestors = match self.get_file_toplevel_ancestors(path) {
             Ok(opt) => match opt {
                 Some(ancestors) => ancestors,
                 None => vec![],
@@ -89,7 +101,7 @@ impl Invokeable for VirtualMergedDocument {
         // if we are a top-level file (this has to be one of the set defined by Optifine, right?)
         if file_ancestors.is_empty() {
             // gather the list of all descendants
-            let root = self.graph.borrow_mut().find_node(&path).unwrap();
+            let root = self.graph.borrow_mut().find_node(path).unwrap();
             let tree = match self.get_dfs_for_node(root) {
                 Ok(tree) => tree,
                 Err(e) => return Err(e.into()),
@@ -103,8 +115,11 @@ impl Invokeable for VirtualMergedDocument {
 
             let mut source_mapper = SourceMapper::new(all_sources.len());
             let graph = self.graph.borrow();
-            let view = merge_views::MergeViewBuilder::new(&tree, &all_sources, &graph, &mut source_mapper).build();
-            return Ok(serde_json::value::Value::String(view));
+            let view = merge_views::MergeViewBuilder::new(&tree, &all_sources, &graph, &mut source_mapper)
+                .indent_includes(indent_includes)
+                .build();
+            let sources = source_mapper.nodes().iter().map(|node| graph.get_node(*node)).collect();
+            return Ok((view, sources));
         }
         return Err(format_err!(
             "{:?} is not a top-level file aka has ancestors",
@@ -112,3 +127,88 @@ impl Invokeable for VirtualMergedDocument {
         ));
     }
 }
+
+impl Invokeable for VirtualMergedDocument {
+    fn run_command(&self, root: &Path, arguments: &[Value]) -> Result<Value> {
+        let path = PathBuf::from_json(arguments.get(0).unwrap())?;
+        // the merge is shown to the user, who may ask for included content to be indented to its `#include`
+        let indent_includes = arguments.get(1).and_then(Value::as_bool).unwrap_or(false);
+
+        let (view, _) = self.merge(root, &path, indent_includes)?;
+        Ok(serde_json::value::Value::String(view))
+    }
+}
+
+#[cfg(test)]
+mod merged_includes_test {
+    use pretty_assertions::assert_eq;
+    use serde_json::Value;
+
+    use crate::commands::merged_includes::VirtualMergedDocument;
+    use crate::commands::Invokeable;
+    use crate::test::{copy_to_and_set_root, new_temp_server};
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_indent_includes() {
+        let mut server = new_temp_server(None);
+
+        let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/29", &mut server);
+        server.build_initial_graph();
+
+        let command = VirtualMergedDocument {
+            graph: server.graph.clone(),
+            config: server.config.clone(),
+        };
+
+        let path = |file: &str| tmp_path.join("shaders").join(file).to_str().unwrap().replace('\\', "\\\\");
+        let (final_path, body_path, inner_path) = (path("final.fsh"), path("lib/body.glsl"), path("lib/inner.glsl"));
+        let merge = |indent: Option<bool>| {
+            let mut arguments = vec![Value::String(
+                tmp_path.join("shaders").join("final.fsh").to_str().unwrap().to_string(),
+            )];
+            arguments.extend(indent.map(Value::Bool));
+            command.run_command(&tmp_path, &arguments).unwrap()
+        };
+
+        // included content is passed on verbatim unless asked for, as it is when validating
+        let verbatim = format!(
+            "#version 120\n\nvoid main() {{\n\
+            #line 1 1 // {body}\n\
+            vec3 color = vec3(1.0);\n\
+            if (color.r > 0.5) {{\n\
+            #line 1 2 // {inner}\n\
+            color *= 0.5;\n\
+            #line 4 1 // {body}\n\
+            }}\n\
+            gl_FragColor = vec4(color, 1.0);\n\
+            #line 5 0 // {root}\n\
+            }}\n",
+            body = body_path,
+            inner = inner_path,
+            root = final_path
+        );
+        assert_eq!(merge(None), Value::String(verbatim.clone()));
+        assert_eq!(merge(Some(false)), Value::String(verbatim));
+
+        let indented = format!(
+            "#version 120\n\nvoid main() {{\n\
+            \t#line 1 1 // {body}\n\
+            \tvec3 color = vec3(1.0);\n\
+            \tif (color.r > 0.5) {{\n\
+            \t    #line 1 2 // {inner}\n\
+            \t    color *= 0.5;\n\
+            \t#line 4 1 // {body}\n\
+            \t}}\n\
+            \tgl_FragColor = vec4(color, 1.0);\n\
+            #line 5 0 // {root}\n\
+            }}\n",
+            body = body_path,
+            inner = inner_path,
+            root = final_path
+        );
+        assert_eq!(merge(Some(true)), Value::String(indented));
+
+        server.endpoint.request_shutdown();
+    }
+}
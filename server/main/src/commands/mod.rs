@@ -1,13 +1,28 @@
 use std::{collections::HashMap, path::Path};
 
+use rust_lsp::lsp_types::ExecuteCommandParams;
 use serde_json::Value;
 
 use anyhow::{format_err, Result};
 use slog_scope::info;
 
+use crate::MinecraftShaderLanguageServer;
+
+pub mod check_line_map;
+pub mod clear_diagnostics;
 pub mod graph_dot;
+pub mod include_chain;
+pub mod include_graph;
+pub mod invalidate;
+pub mod merge_all;
 pub mod merged_includes;
 pub mod parse_tree;
+pub mod raw_compile_log;
+pub mod reload_shader_names;
+pub mod resolve_include;
+pub mod shader_interface;
+pub mod symbol_index;
+pub mod validate_all;
 
 pub struct CustomCommandProvider {
     commands: HashMap<String, Box<dyn Invokeable>>,
@@ -20,17 +35,29 @@ impl CustomCommandProvider {
         }
     }
 
-    pub fn execute(&self, command: &str, args: &[Value], root_path: &Path) -> Result<Value> {
+    pub fn execute(&self, server: &MinecraftShaderLanguageServer, params: &ExecuteCommandParams) -> Result<Value> {
+        let command = params.command.as_str();
+        let args = &params.arguments;
         if self.commands.contains_key(command) {
             info!("running command";
                 "command" => command,
                 "args" => format!("[{}]", args.iter().map(|v| serde_json::to_string(v).unwrap()).collect::<Vec<String>>().join(", ")));
-            return self.commands.get(command).unwrap().run_command(root_path, args);
+            return self.commands.get(command).unwrap().run_server_command(server, params);
         }
         Err(format_err!("command doesn't exist"))
     }
 }
 
 pub trait Invokeable {
-    fn run_command(&self, root: &Path, arguments: &[Value]) -> Result<Value>;
+    /// Runs the command against the workspace at `root`. Commands that need more of the server than that, e.g. to
+    /// publish diagnostics, implement `run_server_command` instead.
+    fn run_command(&self, _root: &Path, _arguments: &[Value]) -> Result<Value> {
+        Err(format_err!("command needs the language server to run"))
+    }
+
+    /// Runs the command on behalf of `server`, which by default runs `run_command` with the workspace root and the
+    /// command's arguments.
+    fn run_server_command(&self, server: &MinecraftShaderLanguageServer, params: &ExecuteCommandParams) -> Result<Value> {
+        self.run_command(&server.root, &params.arguments)
+    }
 }
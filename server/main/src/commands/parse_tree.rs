@@ -10,12 +10,15 @@ use serde_json::Value;
 use slog_scope::warn;
 use tree_sitter::{Parser, TreeCursor};
 
+use crate::configuration::Configuration;
+use crate::text_encoding;
 use crate::url_norm::FromJson;
 
 use super::Invokeable;
 
 pub struct TreeSitterSExpr {
     pub tree_sitter: Rc<RefCell<Parser>>,
+    pub config: Rc<RefCell<Configuration>>,
 }
 
 impl Invokeable for TreeSitterSExpr {
@@ -24,7 +27,7 @@ impl Invokeable for TreeSitterSExpr {
 
         warn!("parsing"; "path" => path.to_str().unwrap().to_string());
 
-        let source = fs::read_to_string(path)?;
+        let source = text_encoding::decode(&fs::read(path)?, &self.config.borrow().encoding)?;
 
         let tree = match self.tree_sitter.borrow_mut().parse(source, None) {
             Some(tree) => tree,
@@ -0,0 +1,105 @@
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use anyhow::{format_err, Result};
+use serde_json::Value;
+use slog_scope::info;
+
+use crate::configuration::Configuration;
+use crate::graph::CachedStableGraph;
+use crate::merge_views;
+use crate::opengl::{self, ShaderValidator};
+use crate::shader_options::ShaderOptions;
+use crate::url_norm::FromJson;
+use crate::TreeType;
+
+use super::merged_includes::VirtualMergedDocument;
+use super::Invokeable;
+
+/// Merges and validates a top-level shader, returning the compile log exactly as the driver emitted
+/// it, or an empty string if it emitted none. Useful when a diagnostic seems to be misparsed.
+pub struct RawCompileLogCommand {
+    pub graph: Rc<RefCell<CachedStableGraph>>,
+    pub config: Rc<RefCell<Configuration>>,
+    pub opengl_context: Rc<dyn ShaderValidator>,
+}
+
+impl Invokeable for RawCompileLogCommand {
+    fn run_command(&self, root: &Path, arguments: &[Value]) -> Result<Value> {
+        let path = PathBuf::from_json(arguments.get(0).unwrap())?;
+
+        let tree_type = match TreeType::from_path(&path) {
+            Some(tree_type) => tree_type,
+            None => return Err(format_err!("{:?} is not a shader stage file", path)),
+        };
+        if !self.opengl_context.supported_stages().contains(&opengl::shader_type(&tree_type)) {
+            return Err(format_err!("the OpenGL context doesn't support {:?} shaders", tree_type));
+        }
+
+        let merger = VirtualMergedDocument {
+            graph: self.graph.clone(),
+            config: self.config.clone(),
+        };
+        // validated just like when linting, with the stage's define and the pack's default options
+        let view = ShaderOptions::load(root)
+            .for_stage(tree_type)
+            .inject(&merger.merge_file(root, &path)?);
+        let view = if self.config.borrow().lower_line_directives {
+            merge_views::lower_line_directives(&view)
+        } else {
+            view
+        };
+
+        info!("validating shader for its raw compile log"; "path" => path.to_str().unwrap(), "stage" => format!("{:?}", tree_type));
+
+        Ok(Value::String(self.opengl_context.validate(tree_type, &view).unwrap_or_default()))
+    }
+}
+
+#[cfg(test)]
+mod raw_compile_log_test {
+    use std::rc::Rc;
+
+    use serde_json::Value;
+
+    use crate::commands::raw_compile_log::RawCompileLogCommand;
+    use crate::commands::Invokeable;
+    use crate::opengl::{self, MockShaderValidator};
+    use crate::test::{copy_to_and_set_root, new_temp_server};
+
+    const LOG: &str = "0(7) : error C1008: undefined variable \"c\"\n0(7) : warning C7533: global variable gl_FragColor is deprecated\n";
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_raw_compile_log() {
+        for log in [Some(LOG), None] {
+            let mut server = new_temp_server(None);
+
+            let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/09", &mut server);
+            server.build_initial_graph();
+
+            let mut mockgl = MockShaderValidator::new();
+            mockgl.expect_supported_stages().returning(opengl::all_stages);
+            mockgl
+                .expect_validate()
+                .withf(|_, source| source.starts_with("#version 120\n"))
+                .times(1)
+                .returning(move |_, _| log.map(String::from));
+
+            let command = RawCompileLogCommand {
+                graph: server.graph.clone(),
+                config: server.config.clone(),
+                opengl_context: Rc::new(mockgl),
+            };
+
+            let final_path = tmp_path.join("shaders").join("final.fsh");
+            let raw_log = command
+                .run_command(&tmp_path, &[Value::String(final_path.to_str().unwrap().to_string())])
+                .unwrap();
+            assert_eq!(raw_log, Value::String(log.unwrap_or_default().to_string()));
+
+            server.endpoint.request_shutdown();
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use rust_lsp::lsp_types::ExecuteCommandParams;
+use serde_json::Value;
+use slog_scope::{error, info};
+
+use crate::toplevel::TopLevelFiles;
+use crate::MinecraftShaderLanguageServer;
+
+use super::Invokeable;
+
+/// Rebuilds the top-level shader names from the configured extra names and maximum index. The workspace is rescanned
+/// for files added since the graph was built, and the programs that are top-level only under the new names are
+/// linted. Returns the paths of those programs. Takes no arguments.
+pub struct ReloadShaderNamesCommand;
+
+impl Invokeable for ReloadShaderNamesCommand {
+    fn run_server_command(&self, server: &MinecraftShaderLanguageServer, _: &ExecuteCommandParams) -> Result<Value> {
+        let previous: HashSet<PathBuf> = server.toplevel_shaders().into_iter().collect();
+
+        let config = server.config.borrow();
+        info!("rebuilding top-level shader names";
+            "extra_names" => format!("{:?}", config.extra_shader_names),
+            "max_index" => config.max_shader_index);
+        *server.toplevel_files.borrow_mut() = TopLevelFiles::new(&config.extra_shader_names, config.max_shader_index);
+        drop(config);
+
+        for path in server.shader_files() {
+            server.add_file_and_includes_to_graph(&path);
+        }
+
+        let added: Vec<PathBuf> = server
+            .toplevel_shaders()
+            .into_iter()
+            .filter(|path| !previous.contains(path))
+            .collect();
+        for path in &added {
+            match server.lint(path) {
+                Ok(diagnostics) => server.publish_diagnostic(diagnostics, None),
+                Err(e) => error!("error linting"; "error" => format!("{:?}", e), "path" => path.to_str().unwrap()),
+            }
+        }
+
+        Ok(serde_json::to_value(added)?)
+    }
+}
@@ -0,0 +1,96 @@
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use anyhow::{format_err, Result};
+use serde::Serialize;
+use serde_json::Value;
+use slog_scope::info;
+
+use crate::graph::CachedStableGraph;
+use crate::url_norm::FromJson;
+
+use super::Invokeable;
+
+/// The file an `#include` resolves to, and whether that file exists.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvedInclude {
+    pub path: PathBuf,
+    pub exists: bool,
+}
+
+/// Returns the file included on a line of a file, or null if the line isn't an `#include`. The first
+/// argument is the path of the file, the second the 0-indexed line.
+pub struct ResolveIncludeCommand {
+    pub graph: Rc<RefCell<CachedStableGraph>>,
+}
+
+impl Invokeable for ResolveIncludeCommand {
+    fn run_command(&self, _: &Path, arguments: &[Value]) -> Result<Value> {
+        let path = PathBuf::from_json(arguments.get(0).unwrap())?;
+        let line = match arguments.get(1).and_then(Value::as_u64) {
+            Some(line) => line as usize,
+            None => return Err(format_err!("expected a line number as second argument")),
+        };
+
+        let mut graph = self.graph.borrow_mut();
+        let node = match graph.find_node(&path) {
+            Some(node) => node,
+            None => return Err(format_err!("{:?} is not in the include graph", path)),
+        };
+
+        info!("resolving include"; "path" => path.to_str().unwrap(), "line" => line);
+
+        let include = graph
+            .get_all_child_positions(node)
+            .find(|(_, position)| position.line == line)
+            .map(|(child, _)| graph.get_node(child));
+
+        match include {
+            Some(include) => Ok(serde_json::to_value(ResolvedInclude {
+                exists: include.is_file(),
+                path: include,
+            })?),
+            None => Ok(Value::Null),
+        }
+    }
+}
+
+#[cfg(test)]
+mod resolve_include_test {
+    use serde_json::{json, Value};
+
+    use crate::commands::resolve_include::ResolveIncludeCommand;
+    use crate::commands::Invokeable;
+    use crate::test::{copy_to_and_set_root, new_temp_server};
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_resolve_include() {
+        let mut server = new_temp_server(None);
+
+        let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/04", &mut server);
+        server.build_initial_graph();
+
+        let command = ResolveIncludeCommand {
+            graph: server.graph.clone(),
+        };
+
+        let path = |file: &str| tmp_path.join("shaders").join(file).to_str().unwrap().to_string();
+
+        let resolve = |line: u64| {
+            command
+                .run_command(&tmp_path, &[Value::String(path("final.fsh")), json!(line)])
+                .unwrap()
+        };
+
+        assert_eq!(resolve(0), Value::Null);
+        assert_eq!(resolve(2), json!({ "path": path("utils/utilities.glsl"), "exists": true }));
+        // the misspelled include doesn't exist
+        assert_eq!(resolve(3), json!({ "path": path("utils/matricies.glsl"), "exists": false }));
+        assert_eq!(resolve(4), Value::Null);
+
+        server.endpoint.request_shutdown();
+    }
+}
@@ -0,0 +1,68 @@
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use anyhow::{format_err, Result};
+use serde_json::{Map, Value};
+use slog_scope::info;
+use tree_sitter::Parser;
+
+use crate::configuration::Configuration;
+use crate::graph::CachedStableGraph;
+use crate::shader_interface::ShaderInterface;
+use crate::url_norm::FromJson;
+
+use super::merged_includes::VirtualMergedDocument;
+use super::Invokeable;
+
+// the stages a program may consist of, by file extension
+const STAGES: &[(&str, &str)] = &[("vsh", "vertex"), ("gsh", "geometry"), ("fsh", "fragment"), ("csh", "compute")];
+
+/// Returns the uniforms, inputs and outputs declared by each stage of a shader program,
+/// including those declared in included files. The program is given as a path to any of
+/// its stages, or to the program name without an extension.
+pub struct ShaderInterfaceCommand {
+    pub graph: Rc<RefCell<CachedStableGraph>>,
+    pub tree_sitter: Rc<RefCell<Parser>>,
+    pub config: Rc<RefCell<Configuration>>,
+}
+
+impl Invokeable for ShaderInterfaceCommand {
+    fn run_command(&self, root: &Path, arguments: &[Value]) -> Result<Value> {
+        let program = PathBuf::from_json(arguments.get(0).unwrap())?;
+        let is_stage = program
+            .extension()
+            .map_or(false, |ext| STAGES.iter().any(|(stage_ext, _)| ext == *stage_ext));
+        let program = if is_stage { program.with_extension("") } else { program };
+
+        let merger = VirtualMergedDocument {
+            graph: self.graph.clone(),
+            config: self.config.clone(),
+        };
+
+        let mut stages = Map::new();
+        for (ext, stage) in STAGES {
+            let path = program.with_extension(ext);
+            if self.graph.borrow_mut().find_node(&path).is_none() {
+                continue;
+            }
+
+            let source = merger.merge_file(root, &path)?;
+            let tree = match self.tree_sitter.borrow_mut().parse(&source, None) {
+                Some(tree) => tree,
+                None => return Err(format_err!("tree-sitter parsing resulted in no parse tree")),
+            };
+
+            let interface = ShaderInterface::from_tree(&tree, &source, *stage == "fragment");
+            stages.insert(stage.to_string(), serde_json::to_value(interface)?);
+        }
+
+        if stages.is_empty() {
+            return Err(format_err!("no shader stages found for program {:?}", program));
+        }
+
+        info!("collected shader interface"; "program" => program.to_str().unwrap(), "stages" => stages.len());
+
+        Ok(Value::Object(stages))
+    }
+}
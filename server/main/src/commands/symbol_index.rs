@@ -0,0 +1,153 @@
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use anyhow::Result;
+use rust_lsp::lsp_types::{Range, SymbolKind};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use slog_scope::{debug, info};
+use tree_sitter::Parser;
+
+use crate::configuration::Configuration;
+use crate::graph::CachedStableGraph;
+use crate::navigation::ParserContext;
+
+use super::Invokeable;
+
+/// A symbol defined at the top level of a file, and where.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexedSymbol {
+    pub name: String,
+    pub path: PathBuf,
+    pub range: Range,
+    // the qualifiers and type of uniforms e.g. `uniform sampler2D`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// Every symbol of the pack worth indexing, by kind. Each list is sorted by file, then position.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SymbolIndex {
+    pub functions: Vec<IndexedSymbol>,
+    pub structs: Vec<IndexedSymbol>,
+    pub uniforms: Vec<IndexedSymbol>,
+    pub macros: Vec<IndexedSymbol>,
+}
+
+/// Returns the functions, structs, uniforms and macros defined across every file in the include graph, for
+/// building documentation or search on top of. Other globals and anything local are left out. Takes no arguments.
+pub struct SymbolIndexCommand {
+    pub graph: Rc<RefCell<CachedStableGraph>>,
+    pub tree_sitter: Rc<RefCell<Parser>>,
+    pub config: Rc<RefCell<Configuration>>,
+}
+
+impl SymbolIndexCommand {
+    fn symbol_index(&self) -> SymbolIndex {
+        let mut paths: Vec<PathBuf> = {
+            let graph = self.graph.borrow();
+            graph.graph.node_indices().map(|node| graph.get_node(node)).collect()
+        };
+        paths.sort();
+
+        let mut index = SymbolIndex::default();
+        for path in paths {
+            let parser = &mut self.tree_sitter.borrow_mut();
+            let symbols = match ParserContext::new(parser, &path, &self.config.borrow().encoding).and_then(|ctx| ctx.list_symbols(&path)) {
+                Ok(symbols) => symbols.unwrap_or_default(),
+                Err(e) => {
+                    // e.g. an include that doesn't exist (yet)
+                    debug!("skipping file in symbol index"; "path" => path.to_str().unwrap(), "error" => e.to_string());
+                    continue;
+                }
+            };
+
+            for symbol in symbols {
+                let is_uniform = symbol
+                    .detail
+                    .as_ref()
+                    .map_or(false, |detail| detail.split_whitespace().any(|q| q == "uniform"));
+                let (list, detail) = match symbol.kind {
+                    SymbolKind::FUNCTION => (&mut index.functions, None),
+                    SymbolKind::STRUCT => (&mut index.structs, None),
+                    SymbolKind::STRING => (&mut index.macros, None),
+                    SymbolKind::VARIABLE | SymbolKind::OBJECT if is_uniform => (&mut index.uniforms, symbol.detail),
+                    _ => continue,
+                };
+                list.push(IndexedSymbol {
+                    name: symbol.name,
+                    path: path.clone(),
+                    range: symbol.selection_range,
+                    detail,
+                });
+            }
+        }
+        index
+    }
+}
+
+impl Invokeable for SymbolIndexCommand {
+    fn run_command(&self, _: &Path, _: &[Value]) -> Result<Value> {
+        let index = self.symbol_index();
+
+        info!("returning symbol index";
+            "functions" => index.functions.len(),
+            "structs" => index.structs.len(),
+            "uniforms" => index.uniforms.len(),
+            "macros" => index.macros.len(),
+        );
+
+        Ok(serde_json::to_value(index)?)
+    }
+}
+
+#[cfg(test)]
+mod symbol_index_test {
+    use pretty_assertions::assert_eq;
+    use rust_lsp::lsp_types::{Position, Range};
+    use serde_json::from_value;
+
+    use crate::commands::symbol_index::{IndexedSymbol, SymbolIndex, SymbolIndexCommand};
+    use crate::commands::Invokeable;
+    use crate::test::{copy_to_and_set_root, new_temp_server};
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_symbol_index() {
+        let mut server = new_temp_server(None);
+        server.tree_sitter.borrow_mut().set_language(tree_sitter_glsl::language()).unwrap();
+
+        let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/45", &mut server);
+        server.build_initial_graph();
+
+        let command = SymbolIndexCommand {
+            graph: server.graph.clone(),
+            tree_sitter: server.tree_sitter.clone(),
+            config: server.config.clone(),
+        };
+
+        let index: SymbolIndex = from_value(command.run_command(&tmp_path, &[]).unwrap()).unwrap();
+
+        let names = |symbols: &[IndexedSymbol]| symbols.iter().map(|s| s.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&index.functions), vec!["main", "shade"]);
+        assert_eq!(names(&index.structs), vec!["Light"]);
+        assert_eq!(names(&index.uniforms), vec!["colortex0", "frameTimeCounter"]);
+        assert_eq!(names(&index.macros), vec!["INTENSITY", "SHADOWS"]);
+
+        let common_path = tmp_path.join("shaders").join("lib").join("common.glsl");
+        assert_eq!(
+            index.uniforms[1],
+            IndexedSymbol {
+                name: "frameTimeCounter".into(),
+                path: common_path.clone(),
+                range: Range::new(Position::new(3, 14), Position::new(3, 30)),
+                detail: Some("uniform float".into()),
+            }
+        );
+        assert_eq!(index.functions[1].path, common_path);
+        assert_eq!(index.functions[0].path, tmp_path.join("shaders").join("final.fsh"));
+
+        server.endpoint.request_shutdown();
+    }
+}
@@ -0,0 +1,62 @@
+use anyhow::Result;
+use rust_lsp::lsp_types::{ExecuteCommandParams, WorkDoneProgress, WorkDoneProgressBegin, WorkDoneProgressEnd, WorkDoneProgressReport};
+use serde_json::Value;
+use slog_scope::{error, info};
+
+use crate::MinecraftShaderLanguageServer;
+
+use super::Invokeable;
+
+/// Lints every top-level shader in the workspace and publishes their diagnostics, returning the number of shaders
+/// linted. Progress is reported against the work done token the client passed along with the command, if any.
+/// Takes no arguments.
+pub struct ValidateAllCommand;
+
+impl ValidateAllCommand {
+    /// Validates all shaders, passing progress to `progress` as it goes: a begin, a report with the name of each
+    /// shader and the percentage done before it is linted, and an end once all are done, even if some failed to lint.
+    pub fn validate_all(&self, server: &MinecraftShaderLanguageServer, mut progress: impl FnMut(WorkDoneProgress)) -> Result<Value> {
+        let shaders = server.toplevel_shaders();
+
+        info!("validating all top-level shaders"; "count" => shaders.len());
+
+        progress(WorkDoneProgress::Begin(WorkDoneProgressBegin {
+            title: "Validating shaders".into(),
+            cancellable: Some(false),
+            message: Some(format!("{} shader(s)", shaders.len())),
+            percentage: Some(0),
+        }));
+
+        let shaders_root = server.root.join("shaders");
+        for (i, path) in shaders.iter().enumerate() {
+            progress(WorkDoneProgress::Report(WorkDoneProgressReport {
+                cancellable: Some(false),
+                message: Some(format!(
+                    "{} ({}/{})",
+                    path.strip_prefix(&shaders_root).unwrap_or(path).display(),
+                    i + 1,
+                    shaders.len()
+                )),
+                percentage: Some((i * 100 / shaders.len()) as u32),
+            }));
+
+            match server.lint(path) {
+                Ok(diagnostics) => server.publish_diagnostic(diagnostics, None),
+                Err(e) => error!("error linting"; "error" => format!("{:?}", e), "path" => path.to_str().unwrap()),
+            }
+        }
+
+        progress(WorkDoneProgress::End(WorkDoneProgressEnd {
+            message: Some(format!("Validated {} shader(s)", shaders.len())),
+        }));
+
+        Ok(serde_json::to_value(shaders.len())?)
+    }
+}
+
+impl Invokeable for ValidateAllCommand {
+    fn run_server_command(&self, server: &MinecraftShaderLanguageServer, params: &ExecuteCommandParams) -> Result<Value> {
+        let token = &params.work_done_progress_params.work_done_token;
+        self.validate_all(server, |value| server.send_progress(token, value))
+    }
+}
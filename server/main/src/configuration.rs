@@ -1,12 +1,214 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
+use rust_lsp::lsp_types::DiagnosticSeverity;
+use serde::Deserialize;
+use serde_json::Value;
 use slog::Level;
-use slog_scope::error;
+use slog_scope::{error, info};
 
+use crate::toplevel;
+
+// the file at the root of a workspace whose settings override the client's for that workspace
+pub const WORKSPACE_SETTINGS_FILE: &str = ".mcshaderrc";
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Configuration {
+    #[serde(alias = "logLevel")]
+    pub log_level: String,
+    // when set, logs are also written to this file, truncating it. Relative paths are resolved against
+    // the workspace root
+    #[serde(alias = "logFile")]
+    pub log_file: Option<PathBuf>,
+    // additional program names to be recognized as top-level files, alongside their indexed variants
+    #[serde(alias = "extraShaderNames")]
+    pub extra_shader_names: Vec<String>,
+    // the highest index suffix recognized for indexed programs e.g. composite99
+    #[serde(alias = "maxShaderIndex")]
+    pub max_shader_index: usize,
+    // directories, relative to the shaders folder, whose .glsl files are treated as libraries
+    // even before any shader includes them
+    #[serde(alias = "libraryDirs")]
+    pub library_dirs: Vec<String>,
+    // merged shaders larger than this are not validated, to guard against runaway include trees
+    #[serde(alias = "maxMergedBytes")]
+    pub max_merged_bytes: usize,
+    // when set, a JSON report of all current diagnostics is written here after every lint.
+    // Relative paths are resolved against the workspace root
+    #[serde(alias = "diagnosticsOutput")]
+    pub diagnostics_output: Option<PathBuf>,
+    // whether symlinked shader files and folders are tracked under the path they link to
+    #[serde(alias = "followSymlinks")]
+    pub follow_symlinks: bool,
+    // whether runs of blank lines are collapsed in merged shaders before validating them
+    #[serde(alias = "compactMerge")]
+    pub compact_merge: bool,
+    // which file diagnostics raised in included files are reported on
+    #[serde(alias = "diagnosticTarget")]
+    pub diagnostic_target: DiagnosticTarget,
+    // the most diagnostics reported for a single file, the rest being summarized by one more. 0 disables the limit
+    #[serde(alias = "maxDiagnosticsPerFile")]
+    pub max_diagnostics_per_file: usize,
+    // which shaders are validated when a file is opened, changed or saved
+    #[serde(alias = "lintScope")]
+    pub lint_scope: LintScope,
+    // whether includes that have their own `#version` and define `main` are validated on their own, as the
+    // stage of the shaders including them, rather than through those shaders
+    #[serde(alias = "validateStandaloneIncludes")]
+    pub validate_standalone_includes: bool,
+    // the encoding shader files are saved in, as a label such as `windows-1252`
+    pub encoding: String,
+    // the severity of the server's own diagnostics, by the name of the check raising them e.g. `missing-include`.
+    // Checks not listed keep the severity they are raised with
+    #[serde(alias = "internalSeverity")]
+    pub internal_severity: HashMap<String, Severity>,
+    // glob patterns, relative to the shaders folder, of files whose validator diagnostics are dropped e.g. vendored
+    // libraries
+    #[serde(alias = "ignoreDiagnosticsFrom")]
+    pub ignore_diagnostics_from: Vec<String>,
+    // a regex matching a line of the driver's compile log, replacing the one picked for the vendor. Must have
+    // `linenum` and `output` named groups, and may have `filepath` (the source number), `column` and `severity`
+    #[serde(alias = "diagnosticsRegex")]
+    pub diagnostics_regex: Option<String>,
+    // whether divisions of integer literals initializing floating point variables, e.g. `float x = 1 / 2;`, are hinted at
+    #[serde(alias = "integerDivisionHints")]
+    pub integer_division_hints: bool,
+    // prefixes of include paths, e.g. `@core`, mapped to the directories they stand for, relative to the shaders folder
+    #[serde(alias = "includeAliases")]
+    pub include_aliases: HashMap<String, String>,
+    // whether status notifications, and popups for successfully executed commands, are left out, for clients that
+    // don't show them or scripted use
+    pub quiet: bool,
+    // lines longer than this, in bytes, are not scanned for includes, guarding against minified or generated
+    // shaders. 0 disables the limit
+    #[serde(alias = "maxIncludeLineLength")]
+    pub max_include_line_length: usize,
+    // whether every `#line` directive is lowered by one before validating, for drivers that number the lines
+    // following a directive one higher than it says, e.g. some of Intel's
+    #[serde(alias = "lowerLineDirectives")]
+    pub lower_line_directives: bool,
+    // where a suffix of include paths, e.g. a version marker like `?v=2`, starts. The suffix is ignored when
+    // resolving the include. Empty disables it
+    #[serde(alias = "includeSuffixSeparator")]
+    pub include_suffix_separator: String,
+    // whether the OpenGL vendor cached from a previous run is dropped and detected again, e.g. after changing GPUs
+    #[serde(alias = "redetectVendor")]
+    pub redetect_vendor: bool,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticTarget {
+    // the included file the diagnostic originates from
+    Origin,
+    // the top-level file, on the line that (transitively) includes the originating file
+    Root,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+impl From<Severity> for DiagnosticSeverity {
+    fn from(severity: Severity) -> DiagnosticSeverity {
+        match severity {
+            Severity::Error => DiagnosticSeverity::ERROR,
+            Severity::Warning => DiagnosticSeverity::WARNING,
+            Severity::Information => DiagnosticSeverity::INFORMATION,
+            Severity::Hint => DiagnosticSeverity::HINT,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LintScope {
+    // only the file itself. Includes get their own checks, but the programs including them are not validated
+    File,
+    // every top-level program that (transitively) includes the file
+    Dependents,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Configuration {
+            log_level: "info".into(),
+            log_file: None,
+            extra_shader_names: vec![],
+            max_shader_index: toplevel::DEFAULT_MAX_SHADER_INDEX,
+            library_dirs: vec!["lib".into()],
+            max_merged_bytes: 16 * 1024 * 1024,
+            diagnostics_output: None,
+            follow_symlinks: false,
+            compact_merge: false,
+            diagnostic_target: DiagnosticTarget::Origin,
+            max_diagnostics_per_file: 100,
+            lint_scope: LintScope::Dependents,
+            validate_standalone_includes: false,
+            encoding: "utf-8".into(),
+            internal_severity: HashMap::new(),
+            ignore_diagnostics_from: vec![],
+            diagnostics_regex: None,
+            integer_division_hints: true,
+            include_aliases: HashMap::new(),
+            quiet: false,
+            max_include_line_length: 64 * 1024,
+            lower_line_directives: false,
+            include_suffix_separator: "?".into(),
+            redetect_vendor: false,
+        }
+    }
+}
 
 pub fn handle_log_level_change<F: FnOnce(Level)>(log_level: String, callback: F) {
     match Level::from_str(log_level.as_str()) {
         Ok(level) => callback(level),
         Err(_) => error!("got unexpected log level from config"; "level" => log_level),
     };
-}
\ No newline at end of file
+}
+
+/// Reads the workspace's own settings from the JSON object in `.mcshaderrc` at its root, if there is one.
+/// A file that can't be read or parsed is logged and ignored.
+pub fn workspace_settings(root: &Path) -> Option<serde_json::Map<String, Value>> {
+    let path = root.join(WORKSPACE_SETTINGS_FILE);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            error!("failed to read workspace settings"; "path" => path.to_str().unwrap(), "error" => e.to_string());
+            return None;
+        }
+    };
+    match serde_json::from_str(&content) {
+        Ok(Value::Object(settings)) => {
+            info!("found workspace settings"; "path" => path.to_str().unwrap(), "settings" => &content);
+            Some(settings)
+        }
+        Ok(_) => {
+            error!("workspace settings are not a JSON object"; "path" => path.to_str().unwrap());
+            None
+        }
+        Err(e) => {
+            error!("failed to parse workspace settings"; "path" => path.to_str().unwrap(), "error" => e.to_string());
+            None
+        }
+    }
+}
+
+/// Applies `overrides` over the client's `settings`, each overriding setting replacing the client's entirely.
+pub fn merge_settings(settings: &Value, overrides: serde_json::Map<String, Value>) -> Value {
+    let mut merged = match settings {
+        Value::Object(settings) => settings.clone(),
+        _ => serde_json::Map::new(),
+    };
+    merged.extend(overrides);
+    Value::Object(merged)
+}
@@ -1,8 +1,15 @@
-use std::{collections::HashMap, cell::OnceCell, path::Path};
+use std::{
+    cell::OnceCell,
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
 
+use anyhow::Result;
+use glob::Pattern;
+use path_slash::PathExt;
 use regex::Regex;
 use rust_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
-use slog_scope::debug;
+use slog_scope::{debug, error, warn};
 use url::Url;
 
 use crate::{
@@ -12,10 +19,26 @@ use crate::{
     source_mapper::{SourceMapper, SourceNum},
 };
 
+/// Compiles a user supplied regex for matching lines of a compile log, checking that it has the `linenum` and
+/// `output` named groups the parser relies on.
+pub fn line_regex(pattern: &str) -> Result<Regex> {
+    let regex = Regex::new(pattern)?;
+    let names: HashSet<&str> = regex.capture_names().flatten().collect();
+    for required in ["linenum", "output"] {
+        if !names.contains(required) {
+            return Err(anyhow::format_err!("the regex has no `{}` named group", required));
+        }
+    }
+    Ok(regex)
+}
+
 pub struct DiagnosticsParser<'a, T: opengl::ShaderValidator + ?Sized> {
     line_offset: OnceCell<u32>,
     line_regex: OnceCell<Regex>,
     vendor_querier: &'a T,
+    // the `shaders` directory that ignored patterns are relative to
+    shaders_root: PathBuf,
+    ignored: Vec<Pattern>,
 }
 
 impl<'a, T: opengl::ShaderValidator + ?Sized> DiagnosticsParser<'a, T> {
@@ -24,16 +47,52 @@ impl<'a, T: opengl::ShaderValidator + ?Sized> DiagnosticsParser<'a, T> {
             line_offset: OnceCell::new(),
             line_regex: OnceCell::new(),
             vendor_querier,
+            shaders_root: PathBuf::new(),
+            ignored: Vec::new(),
         }
     }
 
+    /// Matches compile log lines with `regex` instead of the one picked for the vendor.
+    pub fn with_line_regex(self, regex: Regex) -> Self {
+        let _ = self.line_regex.set(regex);
+        self
+    }
+
+    /// Drops diagnostics located in files matching any of the glob `patterns`, given relative to the `shaders`
+    /// directory under `root` e.g. `lib/vendor/**`. Invalid patterns are logged and skipped.
+    pub fn ignoring(mut self, root: &Path, patterns: &[String]) -> Self {
+        self.shaders_root = root.join("shaders");
+        self.ignored = patterns
+            .iter()
+            .filter_map(|pattern| match Pattern::new(pattern) {
+                Ok(pattern) => Some(pattern),
+                Err(e) => {
+                    error!("invalid pattern in ignoreDiagnosticsFrom"; "pattern" => pattern, "error" => format!("{}", e));
+                    None
+                }
+            })
+            .collect();
+        self
+    }
+
+    fn is_ignored(&self, path: &Path) -> bool {
+        let relative = match path.strip_prefix(&self.shaders_root) {
+            Ok(relative) => relative.to_slash_lossy(),
+            Err(_) => return false,
+        };
+        self.ignored.iter().any(|pattern| pattern.matches(&relative))
+    }
+
     fn get_line_regex(&self) -> &Regex {
         self.line_regex.get_or_init(|| match self.vendor_querier.vendor().as_str() {
-            "NVIDIA Corporation" => {
-                Regex::new(r#"^(?P<filepath>\d+)\((?P<linenum>\d+)\) : (?P<severity>error|warning) [A-C]\d+: (?P<output>.+)"#).unwrap()
-            }
-            _ => Regex::new(r#"^(?P<severity>ERROR|WARNING): (?P<filepath>[^?<>*|"\n]+):(?P<linenum>\d+): (?:'.*' :|[a-z]+\(#\d+\)) +(?P<output>.+)$"#)
-                .unwrap(),
+            "NVIDIA Corporation" => Regex::new(
+                r#"^(?P<filepath>\d+)\((?P<linenum>\d+)(?:[:,](?P<column>\d+))?\) : (?P<severity>error|warning) [A-C]\d+: (?P<output>.+)"#,
+            )
+            .unwrap(),
+            _ => Regex::new(
+                r#"^(?P<severity>ERROR|WARNING): (?P<filepath>[^?<>*|"\n]+):(?P<linenum>\d+): (?:'.*' :|[a-z]+\(#\d+\)) +(?P<output>.+)$"#,
+            )
+            .unwrap(),
         })
     }
 
@@ -52,20 +111,29 @@ impl<'a, T: opengl::ShaderValidator + ?Sized> DiagnosticsParser<'a, T> {
 
         debug!("diagnostics regex selected"; "regex" => self.get_line_regex() .as_str());
 
-        for line in output_lines {
-            let diagnostic_capture = match self.get_line_regex().captures(line) {
+        let mut located = false;
+        for output_line in output_lines {
+            let diagnostic_capture = match self.get_line_regex().captures(output_line) {
                 Some(d) => d,
                 None => continue,
             };
+            located = true;
 
-            debug!("found match for output line"; "line" => line, "capture" => format!("{:?}", diagnostic_capture));
+            debug!("found match for output line"; "line" => output_line, "capture" => format!("{:?}", diagnostic_capture));
 
             let msg = diagnostic_capture.name("output").unwrap().as_str();
 
             let line = match diagnostic_capture.name("linenum") {
                 Some(c) => c.as_str().parse::<u32>().unwrap_or(0),
                 None => 0,
-            } - self.get_line_offset();
+            }
+            .saturating_sub(self.get_line_offset());
+
+            // columns are reported 1-indexed, same as lines. Without a column we default to the whole line
+            let (start_column, end_column) = match diagnostic_capture.name("column").and_then(|c| c.as_str().parse::<u32>().ok()) {
+                Some(column) => (column.saturating_sub(1), column.saturating_sub(1) + 1),
+                None => (0, 1000),
+            };
 
             // TODO: line matching maybe
             /* let line_text = source_lines[line as usize];
@@ -82,19 +150,37 @@ impl<'a, T: opengl::ShaderValidator + ?Sized> DiagnosticsParser<'a, T> {
 
             let origin = match diagnostic_capture.name("filepath") {
                 Some(o) => {
-                    let source_num: SourceNum = o.as_str().parse::<usize>().unwrap().into();
-                    let graph_node = source_mapper.get_node(source_num);
-                    graph.get_node(graph_node).to_str().unwrap().to_string()
+                    let source_num = o.as_str().parse::<usize>().ok().map(SourceNum::from);
+                    match source_num.and_then(|source_num| source_mapper.find_node(source_num)) {
+                        Some(graph_node) => Some(graph.get_node(graph_node).to_str().unwrap().to_string()),
+                        None => {
+                            warn!("compile log references a source that wasn't merged, reporting it on the root";
+                                "source" => o.as_str(), "root" => uri.to_str().unwrap(), "line" => output_line);
+                            None
+                        }
+                    }
                 }
-                None => uri.to_str().unwrap().to_string(),
+                None => Some(uri.to_str().unwrap().to_string()),
             };
 
+            // without a known source, the line number can't be mapped either, so the whole log line is reported
+            // on the root's first line
+            let (origin, line, start_column, end_column, msg) = match origin {
+                Some(origin) => (origin, line, start_column, end_column, msg),
+                None => (uri.to_str().unwrap().to_string(), 0, 0, 1000, output_line),
+            };
+
+            if self.is_ignored(Path::new(&origin)) {
+                debug!("ignoring diagnostic in ignored file"; "path" => &origin, "message" => msg);
+                continue;
+            }
+
             let diagnostic = Diagnostic {
                 range: Range::new(
                     /* Position::new(line, leading_whitespace as u64),
                     Position::new(line, line_text.len() as u64) */
-                    Position::new(line, 0),
-                    Position::new(line, 1000),
+                    Position::new(line, start_column),
+                    Position::new(line, end_column),
                 ),
                 code: None,
                 severity: Some(severity),
@@ -114,6 +200,24 @@ impl<'a, T: opengl::ShaderValidator + ?Sized> DiagnosticsParser<'a, T> {
                 }
             };
         }
+
+        // the validator only returns a log when validation failed, so if none of it could be placed (e.g. a linker
+        // error, which has no line number) we attach the whole log to the program's root rather than dropping it
+        if !located && !output.trim().is_empty() {
+            debug!("no located diagnostics in output, reporting it on the root"; "root" => uri.to_str().unwrap());
+            let diagnostic = Diagnostic {
+                range: Range::new(Position::new(0, 0), Position::new(0, 1000)),
+                code: None,
+                severity: Some(DiagnosticSeverity::ERROR),
+                source: Some(consts::SOURCE.into()),
+                message: output.trim().into(),
+                related_information: None,
+                tags: None,
+                code_description: Option::None,
+                data: Option::None,
+            };
+            diagnostics.insert(Url::from_file_path(uri).unwrap(), vec![diagnostic]);
+        }
         diagnostics
     }
 }
@@ -122,11 +226,15 @@ impl<'a, T: opengl::ShaderValidator + ?Sized> DiagnosticsParser<'a, T> {
 mod diagnostics_test {
     use std::path::PathBuf;
 
+    use rust_lsp::lsp_types::{DiagnosticSeverity, Position, Range};
     use slog::slog_o;
     use url::Url;
 
     use crate::{
-        diagnostics_parser::DiagnosticsParser, opengl::MockShaderValidator, source_mapper::SourceMapper, test::new_temp_server,
+        diagnostics_parser::DiagnosticsParser,
+        opengl::{self, MockShaderValidator},
+        source_mapper::SourceMapper,
+        test::{copy_to_and_set_root, new_temp_server},
     };
 
     #[test]
@@ -159,6 +267,42 @@ mod diagnostics_test {
         });
     }
 
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_nvidia_diagnostics_ranges() {
+        slog_scope::scope(&slog_scope::logger().new(slog_o!("driver" => "nvidia")), || {
+            let mut mockgl = MockShaderValidator::new();
+            mockgl.expect_vendor().returning(|| "NVIDIA Corporation".into());
+            let server = new_temp_server(Some(Box::new(mockgl)));
+
+            let output = "0(9) : error C0000: syntax error, unexpected '}', expecting ',' or ';' at token \"}\"
+0(1) : warning C7555: 'varying' is deprecated, use 'in/out' instead
+0(14:5) : error C1008: undefined variable \"banana\"";
+
+            #[cfg(target_family = "unix")]
+            let path: PathBuf = "/home/noah/.minecraft/shaderpacks/test/shaders/final.fsh".into();
+            #[cfg(target_family = "windows")]
+            let path: PathBuf = "c:\\home\\noah\\.minecraft\\shaderpacks\\test\\shaders\\final.fsh".into();
+
+            let mut source_mapper = SourceMapper::new(0);
+            source_mapper.get_num(server.graph.borrow_mut().add_node(&path));
+
+            let parser = DiagnosticsParser::new(server.opengl_context.as_ref());
+
+            let results =
+                parser.parse_diagnostics_output(output.to_string(), path.parent().unwrap(), &source_mapper, &server.graph.borrow());
+
+            let diagnostics = results.get(&Url::from_file_path(&path).unwrap()).unwrap();
+            assert_eq!(diagnostics.len(), 3);
+
+            assert_eq!(diagnostics[0].range, Range::new(Position::new(8, 0), Position::new(8, 1000)));
+            assert_eq!(diagnostics[1].range, Range::new(Position::new(0, 0), Position::new(0, 1000)));
+            assert_eq!(diagnostics[1].severity, Some(DiagnosticSeverity::WARNING));
+            assert_eq!(diagnostics[2].range, Range::new(Position::new(13, 4), Position::new(13, 5)));
+            server.endpoint.request_shutdown();
+        });
+    }
+
     #[test]
     #[logging_macro::log_scope]
     fn test_amd_diagnostics() {
@@ -191,4 +335,154 @@ ERROR: 0:15: 'varying' : syntax error: syntax error
             server.endpoint.request_shutdown();
         });
     }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_unlocated_errors() {
+        let mut mockgl = MockShaderValidator::new();
+        mockgl.expect_vendor().returning(|| "NVIDIA Corporation".into());
+        let server = new_temp_server(Some(Box::new(mockgl)));
+
+        let output = "Link info\n---------\nerror: \"main\" function not found\n";
+
+        let path: PathBuf = ["/", "shaders", "final.fsh"].iter().collect();
+
+        let mut source_mapper = SourceMapper::new(0);
+        source_mapper.get_num(server.graph.borrow_mut().add_node(&path));
+
+        let parser = DiagnosticsParser::new(server.opengl_context.as_ref());
+
+        let results = parser.parse_diagnostics_output(output.to_string(), &path, &source_mapper, &server.graph.borrow());
+
+        assert_eq!(results.len(), 1);
+        let diagnostics = results.get(&Url::from_file_path(&path).unwrap()).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range, Range::new(Position::new(0, 0), Position::new(0, 1000)));
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(diagnostics[0].message, output.trim());
+
+        server.endpoint.request_shutdown();
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_unknown_source_number() {
+        let mut mockgl = MockShaderValidator::new();
+        mockgl.expect_vendor().returning(|| "NVIDIA Corporation".into());
+        let server = new_temp_server(Some(Box::new(mockgl)));
+
+        // only source numbers 0 and 1 were merged
+        let output = "1(3) : error C1008: undefined variable \"banana\"\n7(12) : error C0000: syntax error, unexpected '}'\n";
+
+        let path: PathBuf = ["/", "shaders", "final.fsh"].iter().collect();
+        let common_path: PathBuf = ["/", "shaders", "common.glsl"].iter().collect();
+
+        let mut source_mapper = SourceMapper::new(0);
+        source_mapper.get_num(server.graph.borrow_mut().add_node(&path));
+        source_mapper.get_num(server.graph.borrow_mut().add_node(&common_path));
+
+        let parser = DiagnosticsParser::new(server.opengl_context.as_ref());
+
+        let results = parser.parse_diagnostics_output(output.to_string(), &path, &source_mapper, &server.graph.borrow());
+
+        assert_eq!(results.len(), 2);
+        let common_diagnostics = results.get(&Url::from_file_path(&common_path).unwrap()).unwrap();
+        assert_eq!(common_diagnostics.len(), 1);
+        assert_eq!(common_diagnostics[0].range, Range::new(Position::new(2, 0), Position::new(2, 1000)));
+
+        // the unknown source is reported on the root, with the raw log line
+        let root_diagnostics = results.get(&Url::from_file_path(&path).unwrap()).unwrap();
+        assert_eq!(root_diagnostics.len(), 1);
+        assert_eq!(root_diagnostics[0].range, Range::new(Position::new(0, 0), Position::new(0, 1000)));
+        assert_eq!(root_diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(root_diagnostics[0].message, "7(12) : error C0000: syntax error, unexpected '}'");
+
+        server.endpoint.request_shutdown();
+    }
+
+    // reports an error on every line using an undefined variable, numbering lines the way Intel drivers do:
+    // the lines following `#line N` are numbered from N + 1
+    fn intel_compile_log(source: &str) -> String {
+        let mut current = (0, 1);
+        let mut errors = vec![];
+        for line in source.lines() {
+            if let Some(directive) = line.strip_prefix("#line ") {
+                let mut parts = directive.split_whitespace();
+                let line_num = parts.next().unwrap().parse::<usize>().unwrap();
+                current = (parts.next().unwrap().parse::<usize>().unwrap(), line_num + 1);
+                continue;
+            }
+            if line.contains("undefined_") {
+                errors.push(format!("ERROR: {}:{}: 'undefined' : undeclared identifier", current.0, current.1));
+            }
+            current.1 += 1;
+        }
+        errors.join("\n")
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_lower_line_directives() {
+        for lower in [false, true] {
+            let mut mockgl = MockShaderValidator::new();
+            mockgl.expect_vendor().returning(|| "Intel".into());
+            mockgl.expect_supported_stages().returning(opengl::all_stages);
+            mockgl.expect_validate().returning(|_, source| Some(intel_compile_log(source)));
+            let mut server = new_temp_server(Some(Box::new(mockgl)));
+
+            let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/51", &mut server);
+            server.apply_configuration(&serde_json::json!({ "lowerLineDirectives": lower }));
+            server.build_initial_graph();
+
+            let final_path = tmp_path.join("shaders").join("final.fsh");
+            let include_path = tmp_path.join("shaders").join("lib").join("a.glsl");
+
+            let diagnostics = server.lint(&final_path).unwrap();
+            let error_line = |path: &PathBuf| -> Vec<u32> {
+                diagnostics[&Url::from_file_path(path).unwrap()]
+                    .iter()
+                    .filter(|d| d.message.contains("undeclared identifier"))
+                    .map(|d| d.range.start.line)
+                    .collect()
+            };
+
+            // unless the directives are lowered, Intel numbers every line after them one too high
+            let expected = if lower { (vec![5], vec![1]) } else { (vec![6], vec![2]) };
+            assert_eq!((error_line(&final_path), error_line(&include_path)), expected);
+
+            server.endpoint.request_shutdown();
+        }
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_ignored_diagnostics() {
+        let mut mockgl = MockShaderValidator::new();
+        mockgl.expect_vendor().returning(|| "NVIDIA Corporation".into());
+        let server = new_temp_server(Some(Box::new(mockgl)));
+
+        let root: PathBuf = ["/", "pack"].iter().collect();
+        let final_path = root.join("shaders").join("final.fsh");
+        let noise_path = root.join("shaders").join("lib").join("vendor").join("noise.glsl");
+        let util_path = root.join("shaders").join("lib").join("util.glsl");
+
+        let mut source_mapper = SourceMapper::new(0);
+        source_mapper.get_num(server.graph.borrow_mut().add_node(&final_path));
+        source_mapper.get_num(server.graph.borrow_mut().add_node(&noise_path));
+        source_mapper.get_num(server.graph.borrow_mut().add_node(&util_path));
+
+        let output = "0(5) : error C1008: undefined variable \"albedo\"\n\
+            1(3) : warning C7022: unrecognized profile specifier \"precision\"\n\
+            2(4) : error C1008: undefined variable \"noise\"";
+
+        let parser = DiagnosticsParser::new(server.opengl_context.as_ref()).ignoring(&root, &["lib/vendor/**".into()]);
+        let results = parser.parse_diagnostics_output(output.to_string(), &final_path, &source_mapper, &server.graph.borrow());
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[&Url::from_file_path(&final_path).unwrap()].len(), 1);
+        assert_eq!(results[&Url::from_file_path(&util_path).unwrap()].len(), 1);
+        assert!(!results.contains_key(&Url::from_file_path(&noise_path).unwrap()));
+
+        server.endpoint.request_shutdown();
+    }
 }
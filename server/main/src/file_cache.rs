@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::SystemTime;
+
+use crate::normalize_path;
+
+/// Holds the contents of the files read from disk, so that a file scanned for includes isn't read again
+/// to be merged. A cached file is only reused while its modification time and size are unchanged.
+#[derive(Default)]
+pub struct FileCache {
+    files: HashMap<PathBuf, CachedFile>,
+    // the number of times a file was actually read from disk
+    reads: usize,
+}
+
+struct CachedFile {
+    modified: SystemTime,
+    len: u64,
+    contents: Rc<[u8]>,
+}
+
+impl FileCache {
+    pub fn read(&mut self, path: &Path) -> io::Result<Rc<[u8]>> {
+        let path = &normalize_path(path);
+        let metadata = fs::metadata(path)?;
+        let modified = metadata.modified()?;
+
+        if let Some(cached) = self.files.get(path) {
+            if cached.modified == modified && cached.len == metadata.len() {
+                return Ok(cached.contents.clone());
+            }
+        }
+
+        let contents: Rc<[u8]> = fs::read(path)?.into();
+        self.reads += 1;
+        self.files.insert(
+            path.to_path_buf(),
+            CachedFile {
+                modified,
+                len: metadata.len(),
+                contents: contents.clone(),
+            },
+        );
+        Ok(contents)
+    }
+
+    /// Drops the cached contents of a file, for when it is known to have changed.
+    pub fn invalidate(&mut self, path: &Path) {
+        self.files.remove(&normalize_path(path));
+    }
+
+    pub fn reads(&self) -> usize {
+        self.reads
+    }
+}
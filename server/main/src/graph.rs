@@ -5,12 +5,12 @@ use petgraph::visit::EdgeRef;
 use petgraph::Direction;
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     path::{Path, PathBuf},
     str::FromStr,
 };
 
-use super::IncludePosition;
+use super::{normalize_path, IncludePosition};
 
 /// Wraps a `StableDiGraph` with caching behaviour for node search by maintaining
 /// an index for node value to node index and a reverse index.
@@ -40,20 +40,38 @@ impl CachedStableGraph {
     /// is cached (which should always be the case), else **O(n)** where **n** is
     /// the number of node indices, as an exhaustive search must be done.
     pub fn find_node(&mut self, name: &Path) -> Option<NodeIndex> {
-        match self.cache.get(name) {
-            Some(n) => Some(*n),
+        let name = &normalize_path(name);
+        match self.cached_index(name) {
+            Some(n) => Some(n),
             None => {
                 // If the string is not in cache, O(n) search the graph (i know...) and then cache the NodeIndex
                 // for later
                 let n = self.graph.node_indices().find(|n| self.graph[*n] == name.to_str().unwrap());
                 if let Some(n) = n {
                     self.cache.insert(name.into(), n);
+                    self.reverse_index.insert(n, name.into());
                 }
                 n
             }
         }
     }
 
+    // Returns the cached `NodeIndex` for `name` if it still refers to a node with that value. Node indices
+    // are reused by the graph after removal, so a cache entry for a removed node may point to a vacant index
+    // or to an entirely different node, in which case the entry is evicted.
+    fn cached_index(&mut self, name: &Path) -> Option<NodeIndex> {
+        let idx = *self.cache.get(name)?;
+        if self.graph.node_weight(idx).map(|n| n.as_str()) == name.to_str() {
+            return Some(idx);
+        }
+
+        self.cache.remove(name);
+        if self.reverse_index.get(&idx).map(|p| p.as_path()) == Some(name) {
+            self.reverse_index.remove(&idx);
+        }
+        None
+    }
+
     // Returns the `PathBuf` for a given `NodeIndex`
     pub fn get_node(&self, node: NodeIndex) -> PathBuf {
         PathBuf::from_str(&self.graph[node]).unwrap()
@@ -90,8 +108,9 @@ impl CachedStableGraph {
     }
 
     pub fn add_node(&mut self, name: &Path) -> NodeIndex {
-        if let Some(idx) = self.cache.get(name) {
-            return *idx;
+        let name = &normalize_path(name);
+        if let Some(idx) = self.cached_index(name) {
+            return idx;
         }
         let idx = self.graph.add_node(name.to_str().unwrap().to_string());
         self.cache.insert(name.to_owned(), idx);
@@ -120,6 +139,38 @@ impl CachedStableGraph {
         self.get_root_ancestors(node, node, &mut visited)
     }
 
+    /// Returns a shortest chain of includes from `from` to `to`, as each including node along the way
+    /// with the position of its include of the next node. Includes are followed in the order they appear
+    /// in each file, so the result is deterministic. Returns `None` if `from` doesn't include `to`.
+    pub fn include_chain(&self, from: NodeIndex, to: NodeIndex) -> Option<Vec<(NodeIndex, IncludePosition)>> {
+        // for each visited node, the node including it and the position it is included at
+        let mut included_by: HashMap<NodeIndex, Option<(NodeIndex, IncludePosition)>> = HashMap::new();
+        included_by.insert(from, None);
+
+        let mut queue = VecDeque::from([from]);
+        while let Some(node) = queue.pop_front() {
+            if node == to {
+                break;
+            }
+            for (child, position) in self.get_all_child_positions(node) {
+                if !included_by.contains_key(&child) {
+                    included_by.insert(child, Some((node, position)));
+                    queue.push_back(child);
+                }
+            }
+        }
+
+        let mut chain = Vec::new();
+        let mut node = to;
+        while let Some(parent) = included_by.get(&node)? {
+            chain.push(*parent);
+            node = parent.0;
+        }
+        chain.reverse();
+
+        Some(chain)
+    }
+
     // TODO: impl Iterator
     fn parent_node_indexes(&self, node: NodeIndex) -> Vec<NodeIndex> {
         self.graph.neighbors_directed(node, Direction::Incoming).collect()
@@ -167,7 +218,7 @@ impl CachedStableGraph {
     }
 
     fn remove_node(&mut self, name: &Path) {
-        let idx = self.cache.remove(name);
+        let idx = self.cache.remove(&normalize_path(name));
         if let Some(idx) = idx {
             self.graph.remove_node(idx);
         }
@@ -225,6 +276,53 @@ mod graph_test {
         assert_eq!(neighbors.len(), 0);
     }
 
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_find_node_stale_cache() {
+        let mut graph = CachedStableGraph::new();
+
+        let sample = graph.add_node(&PathBuf::from("sample"));
+        let banana = graph.add_node(&PathBuf::from("banana"));
+
+        // removing the node from the graph directly leaves its cache entry behind
+        graph.graph.remove_node(sample);
+        assert!(graph.find_node(&PathBuf::from("sample")).is_none());
+
+        graph.graph.remove_node(banana);
+        // the vacant index gets reused while banana's stale cache entry still points at it
+        let apple = graph.add_node(&PathBuf::from("apple"));
+        assert_eq!(apple, banana);
+        assert!(graph.find_node(&PathBuf::from("banana")).is_none());
+        assert_eq!(graph.find_node(&PathBuf::from("apple")), Some(apple));
+
+        // re-adding a removed node must create a new node rather than reuse a stale index
+        let sample2 = graph.add_node(&PathBuf::from("sample"));
+        assert_ne!(sample2, apple);
+        assert_eq!(graph.get_node(sample2), PathBuf::from("sample"));
+        assert_eq!(graph.find_node(&PathBuf::from("sample")), Some(sample2));
+        assert_eq!(graph.find_node(&PathBuf::from("apple")), Some(apple));
+        assert_eq!(graph.graph.node_count(), 2);
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_equivalent_paths() {
+        let mut graph = CachedStableGraph::new();
+
+        let idx = graph.add_node(&PathBuf::from("/pack/shaders/lib/common.glsl"));
+
+        assert_eq!(graph.find_node(&PathBuf::from("/pack/shaders/./lib/common.glsl")), Some(idx));
+        assert_eq!(
+            graph.find_node(&PathBuf::from("/pack/shaders/world0/../lib/common.glsl")),
+            Some(idx)
+        );
+        assert_eq!(graph.add_node(&PathBuf::from("/pack/shaders/lib/../lib/./common.glsl")), idx);
+        assert_eq!(graph.graph.node_count(), 1);
+
+        graph.remove_node(&PathBuf::from("/pack/./shaders/lib/common.glsl"));
+        assert!(graph.find_node(&PathBuf::from("/pack/shaders/lib/common.glsl")).is_none());
+    }
+
     #[test]
     #[logging_macro::log_scope]
     fn test_double_import() {
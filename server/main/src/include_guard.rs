@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use lazy_static::lazy_static;
+use path_slash::PathExt;
+use regex::Regex;
+use rust_lsp::lsp_types::{Position, Range, TextEdit, WorkspaceEdit};
+use url::Url;
+
+use crate::linemap;
+
+lazy_static! {
+    static ref RE_IFNDEF: Regex = Regex::new(r#"^\s*#\s*ifndef\s+([A-Za-z_][A-Za-z0-9_]*)\s*(?://.*)?$"#).unwrap();
+    static ref RE_DEFINE: Regex = Regex::new(r#"^\s*#\s*define\s+([A-Za-z_][A-Za-z0-9_]*)\s*(?://.*)?$"#).unwrap();
+}
+
+/// Derives the guard macro for an include from its path relative to the `shaders` directory,
+/// e.g. `lib/common.glsl` becomes `LIB_COMMON_GLSL`.
+pub fn guard_name(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root.join("shaders")).unwrap_or(path);
+    let mut name: String = relative
+        .to_slash_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    name = name.trim_start_matches('_').to_string();
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+    name
+}
+
+/// Returns whether the source already starts with an include guard: an `#ifndef X` that is
+/// immediately followed by `#define X`, ignoring blank lines and comments before and between them.
+pub fn has_include_guard(source: &str) -> bool {
+    let mut lines = code_lines(source);
+
+    let guard = match lines.next().and_then(|line| RE_IFNDEF.captures(line)) {
+        Some(cap) => cap.get(1).unwrap().as_str().to_string(),
+        None => return false,
+    };
+
+    match lines.next().and_then(|line| RE_DEFINE.captures(line)) {
+        Some(cap) => cap.get(1).unwrap().as_str() == guard,
+        None => false,
+    }
+}
+
+// iterates the lines of the source that aren't blank or entirely made up of comments
+fn code_lines(source: &str) -> impl Iterator<Item = &str> {
+    let mut in_block_comment = false;
+    source.lines().filter(move |line| {
+        let mut rest = line.trim();
+        loop {
+            if in_block_comment {
+                match rest.find("*/") {
+                    Some(end) => {
+                        in_block_comment = false;
+                        rest = rest[end + 2..].trim_start();
+                    }
+                    None => return false,
+                }
+            } else if rest.starts_with("/*") {
+                in_block_comment = true;
+                rest = &rest[2..];
+            } else {
+                return !rest.is_empty() && !rest.starts_with("//");
+            }
+        }
+    })
+}
+
+/// Builds the edit that wraps an include in an include guard, or `None` if it already has one.
+pub fn include_guard_edit(root: &Path, path: &Path, uri: Url, source: &str) -> Option<WorkspaceEdit> {
+    if has_include_guard(source) {
+        return None;
+    }
+
+    let guard = guard_name(root, path);
+
+    let last_line = source.split('\n').last().unwrap_or_default();
+    let end = Position::new(
+        source.matches('\n').count() as u32,
+        linemap::utf16_column(last_line, last_line.len()) as u32,
+    );
+    let footer = if source.is_empty() || source.ends_with('\n') {
+        "#endif\n".to_string()
+    } else {
+        "\n#endif\n".to_string()
+    };
+
+    let start = Position::new(0, 0);
+    let edits = vec![
+        TextEdit::new(Range::new(start, start), format!("#ifndef {}\n#define {}\n", guard, guard)),
+        TextEdit::new(Range::new(end, end), footer),
+    ];
+
+    Some(WorkspaceEdit::new(HashMap::from([(uri, edits)])))
+}
+
+#[cfg(test)]
+mod include_guard_test {
+    use std::path::Path;
+
+    use pretty_assertions::assert_eq;
+    use url::Url;
+
+    use crate::include_guard::{guard_name, has_include_guard, include_guard_edit};
+
+    // applies the insertions of an edit to the source, last one first so that positions stay valid
+    fn apply(source: &str, edit: rust_lsp::lsp_types::WorkspaceEdit) -> String {
+        let mut edits = edit.changes.unwrap().into_values().next().unwrap();
+        edits.sort_by_key(|edit| edit.range.start);
+
+        let mut result = source.to_string();
+        for edit in edits.iter().rev() {
+            let offset: usize = result
+                .split_inclusive('\n')
+                .take(edit.range.start.line as usize)
+                .map(|line| line.len())
+                .sum::<usize>()
+                + edit.range.start.character as usize;
+            result.insert_str(offset, &edit.new_text);
+        }
+        result
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_include_guard_edit() {
+        let root = Path::new("/pack");
+        let path = root.join("shaders").join("lib").join("common-utils.glsl");
+        let uri = Url::from_file_path(&path).unwrap();
+
+        assert_eq!(guard_name(root, &path), "LIB_COMMON_UTILS_GLSL");
+
+        let source = "// utilities\nfloat luma(vec3 c) { return dot(c, vec3(0.3, 0.6, 0.1)); }\n";
+        let edit = include_guard_edit(root, &path, uri.clone(), source).unwrap();
+        let guarded = apply(source, edit);
+        assert_eq!(
+            guarded,
+            "#ifndef LIB_COMMON_UTILS_GLSL\n#define LIB_COMMON_UTILS_GLSL\n// utilities\nfloat luma(vec3 c) { return dot(c, vec3(0.3, 0.6, 0.1)); }\n#endif\n"
+        );
+        assert!(has_include_guard(&guarded));
+        assert!(include_guard_edit(root, &path, uri.clone(), &guarded).is_none());
+
+        let unterminated = "float a;";
+        let edit = include_guard_edit(root, &path, uri, unterminated).unwrap();
+        assert_eq!(
+            apply(unterminated, edit),
+            "#ifndef LIB_COMMON_UTILS_GLSL\n#define LIB_COMMON_UTILS_GLSL\nfloat a;\n#endif\n"
+        );
+
+        assert!(has_include_guard(
+            "/* header\n*/\n\n#ifndef GUARD\n// guard\n#define GUARD\n#endif\n"
+        ));
+        assert!(!has_include_guard("#ifndef FEATURE\nfloat a;\n#endif\n"));
+        assert!(!has_include_guard("#ifndef A\n#define B\n#endif\n"));
+    }
+}
@@ -0,0 +1,2640 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+use path_slash::{PathBufExt, PathExt};
+use regex::Regex;
+use rust_lsp::lsp_types::{Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, Location, NumberOrString, Position, Range};
+use tree_sitter::{Node, Tree};
+use url::Url;
+
+use crate::builtins;
+use crate::configuration::Severity;
+use crate::consts;
+use crate::graph::CachedStableGraph;
+use crate::linemap;
+use crate::shader_interface::{self, InterfaceBlock, InterfaceVariable, ShaderInterface};
+use crate::source_mapper::{self, SourceMapper};
+use crate::toplevel;
+use crate::RE_INCLUDE;
+
+lazy_static! {
+    static ref RE_INCLUDE_DIRECTIVE: Regex = Regex::new(r#"^\s*#\s*include\b\s*(.*?)\s*$"#).unwrap();
+    static ref RE_LITERAL_INCLUDE_PATH: Regex = Regex::new(r#"^(?:"[^"]*"|<[^>]*>)\s*(?://.*|/\*.*)?$"#).unwrap();
+    static ref RE_DRAW_BUFFERS: Regex = Regex::new(r#"/\*\s*DRAWBUFFERS\s*:\s*([0-9]+)\s*\*/"#).unwrap();
+    static ref RE_EXTENSION_DIRECTIVE: Regex =
+        Regex::new(r#"^\s*#\s*extension\s+([A-Za-z0-9_]+)\s*:\s*(require|enable|warn|disable)\b"#).unwrap();
+    static ref RE_ARRAY_TYPE_SIZE: Regex = Regex::new(r#"\[\s*([0-9]+)\s*\]\s*$"#).unwrap();
+    static ref RE_ARRAY_CONSTRUCTOR: Regex = Regex::new(r#"^\s*([A-Za-z_][A-Za-z0-9_]*)\s*\[\s*([0-9]*)\s*\]\s*\("#).unwrap();
+    static ref RE_RENDER_TARGETS: Regex = Regex::new(r#"/\*\s*RENDERTARGETS\s*:\s*([0-9]+(?:\s*,\s*[0-9]+)*)\s*\*/"#).unwrap();
+    static ref RE_VERSION_DIRECTIVE: Regex = Regex::new(r#"^\s*#\s*version\s+([0-9]+)(?:\s+([a-z]+))?"#).unwrap();
+    static ref RE_DEFINE_DIRECTIVE: Regex = Regex::new(r#"^\s*#\s*define\s+([A-Za-z_][A-Za-z0-9_]*)(.*)$"#).unwrap();
+    static ref RE_NOT_DEFINED_GUARD: Regex =
+        Regex::new(r#"^(?:ifndef\s+([A-Za-z_][A-Za-z0-9_]*)|if\s*!\s*defined\s*\(?\s*([A-Za-z_][A-Za-z0-9_]*)\s*\)?)\s*(?://.*)?$"#)
+            .unwrap();
+    static ref RE_UNDEF_DIRECTIVE: Regex = Regex::new(r#"^\s*#\s*undef\s+([A-Za-z_][A-Za-z0-9_]*)"#).unwrap();
+    static ref RE_BUFFER_NUMBER: Regex = Regex::new(r#"[0-9]+"#).unwrap();
+    static ref RE_FLOAT_TYPE: Regex = Regex::new(r#"^(?:float|double|d?vec[234]|d?mat[234](?:x[234])?)$"#).unwrap();
+    static ref RE_INTEGER_LITERAL: Regex = Regex::new(r#"^(?:0[xX][0-9a-fA-F]+|[0-9]+)[uU]?$"#).unwrap();
+}
+
+/// Builds a diagnostic raised by the language server itself rather than the driver. `code` names
+/// the check that raised it.
+pub fn new_diagnostic(code: &str, severity: DiagnosticSeverity, range: Range, message: impl Into<String>) -> Diagnostic {
+    Diagnostic {
+        range,
+        code: Some(NumberOrString::String(code.into())),
+        severity: Some(severity),
+        source: Some(consts::SOURCE.into()),
+        message: message.into(),
+        related_information: None,
+        tags: None,
+        code_description: Option::None,
+        data: Option::None,
+    }
+}
+
+/// Truncates the diagnostics of each file to the first `max_per_file`, which are usually the most relevant as
+/// later errors are often caused by earlier ones. A final diagnostic takes the place of the hidden ones, at the
+/// position of the first of them. A `max_per_file` of 0 disables the limit.
+pub fn limit_diagnostics(diagnostics: &mut HashMap<Url, Vec<Diagnostic>>, max_per_file: usize) {
+    if max_per_file == 0 {
+        return;
+    }
+
+    for file_diagnostics in diagnostics.values_mut() {
+        if file_diagnostics.len() <= max_per_file {
+            continue;
+        }
+
+        let hidden = file_diagnostics.len() - max_per_file;
+        let first_hidden = file_diagnostics[max_per_file].range;
+        file_diagnostics.truncate(max_per_file);
+        file_diagnostics.push(new_diagnostic(
+            "diagnostics-truncated",
+            DiagnosticSeverity::INFORMATION,
+            first_hidden,
+            format!("{} more diagnostic(s) hidden, see mcglsl.maxDiagnosticsPerFile", hidden),
+        ));
+    }
+}
+
+/// Overrides the severity of the server's own diagnostics with the one configured for the check that raised them.
+pub fn override_severities(diagnostics: &mut HashMap<Url, Vec<Diagnostic>>, severities: &HashMap<String, Severity>) {
+    if severities.is_empty() {
+        return;
+    }
+
+    for diagnostic in diagnostics.values_mut().flatten() {
+        if let Some(NumberOrString::String(code)) = &diagnostic.code {
+            if let Some(severity) = severities.get(code) {
+                diagnostic.severity = Some((*severity).into());
+            }
+        }
+    }
+}
+
+/// Runs all checks that only need the contents of a single file.
+pub fn file_diagnostics(source: &str, include_suffix_separator: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    diagnostics.extend(include_path_whitespace(source, include_suffix_separator));
+    diagnostics.extend(unresolvable_includes(source));
+    diagnostics.extend(unterminated_block_comment_diagnostic(source));
+    diagnostics.extend(duplicate_draw_buffers(source));
+    diagnostics
+}
+
+/// Returns the position of the opening `/*` of a block comment that is never closed, if the source has one.
+/// Such a comment swallows the rest of the file, including any includes in it.
+pub fn unterminated_block_comment(source: &str) -> Option<Position> {
+    let mut opening: Option<Position> = None;
+    for (line_num, line) in source.lines().enumerate() {
+        let mut offset = 0;
+        loop {
+            let rest = &line[offset..];
+            if opening.is_some() {
+                match rest.find("*/") {
+                    Some(end) => {
+                        opening = None;
+                        offset += end + 2;
+                    }
+                    None => break,
+                }
+            } else {
+                let line_comment = rest.find("//");
+                match rest.find("/*") {
+                    Some(start) if line_comment.map_or(true, |l| start < l) => {
+                        opening = Some(Position::new(line_num as u32, (offset + start) as u32));
+                        offset += start + 2;
+                    }
+                    _ => break,
+                }
+            }
+        }
+    }
+    opening
+}
+
+fn unterminated_block_comment_diagnostic(source: &str) -> Option<Diagnostic> {
+    let opening = unterminated_block_comment(source)?;
+    Some(new_diagnostic(
+        "unterminated-comment",
+        DiagnosticSeverity::ERROR,
+        Range::new(opening, Position::new(opening.line, opening.character + 2)),
+        "Block comment is never closed, so everything after it is commented out",
+    ))
+}
+
+/// Flags include paths with leading or trailing whitespace inside the quotes, which are
+/// trimmed when resolving the include but are most likely a typo. A suffix starting at
+/// `suffix_separator` is kept as is in the suggested path.
+pub fn include_path_whitespace(source: &str, suffix_separator: &str) -> Vec<Diagnostic> {
+    source
+        .lines()
+        .enumerate()
+        .filter_map(|(line_num, line)| {
+            let cap = RE_INCLUDE.captures(line)?.get(1).unwrap();
+            let quoted = cap.as_str();
+            let range = crate::include_path_range(quoted, suffix_separator);
+            let fixed = format!("{}{}", &quoted[range.clone()], quoted[range.end..].trim_start());
+            if fixed == quoted {
+                return None;
+            }
+
+            Some(new_diagnostic(
+                "include-path-whitespace",
+                DiagnosticSeverity::WARNING,
+                Range::new(
+                    Position::new(line_num as u32, linemap::utf16_column(line, cap.start()) as u32),
+                    Position::new(line_num as u32, linemap::utf16_column(line, cap.end()) as u32),
+                ),
+                format!(
+                    "Include path \"{}\" has surrounding whitespace, which is ignored. Consider changing it to \"{}\"",
+                    quoted, fixed
+                ),
+            ))
+        })
+        .collect()
+}
+
+/// Flags includes whose argument isn't a literal path, e.g. `#include STR(common)`. These can't be
+/// resolved without running the preprocessor, so the included file is missing from the include graph.
+pub fn unresolvable_includes(source: &str) -> Vec<Diagnostic> {
+    source
+        .lines()
+        .enumerate()
+        .filter_map(|(line_num, line)| {
+            let arg = RE_INCLUDE_DIRECTIVE.captures(line)?.get(1).unwrap();
+            if RE_LITERAL_INCLUDE_PATH.is_match(arg.as_str()) {
+                return None;
+            }
+
+            Some(new_diagnostic(
+                "unresolvable-include",
+                DiagnosticSeverity::INFORMATION,
+                Range::new(
+                    Position::new(line_num as u32, linemap::utf16_column(line, arg.start()) as u32),
+                    Position::new(line_num as u32, linemap::utf16_column(line, arg.end()) as u32),
+                ),
+                format!(
+                    "Include of `{}` can't be resolved statically, so the included file isn't tracked and won't be linted with this file",
+                    arg.as_str()
+                ),
+            ))
+        })
+        .collect()
+}
+
+/// Flags `#extension NAME : require` directives for extensions that the context validating the shaders
+/// doesn't advertise, as its driver will fail to compile them. `supported_extensions` is only called if
+/// the source requires any extension, as querying them isn't free. Nothing is flagged if the context
+/// doesn't list any extensions, as it then can't be told what is supported.
+pub fn unsupported_extensions<'a>(source: &str, supported_extensions: impl FnOnce() -> &'a HashSet<String>) -> Vec<Diagnostic> {
+    let required: Vec<(usize, regex::Match)> = source
+        .lines()
+        .enumerate()
+        .filter_map(|(line_num, line)| {
+            let cap = RE_EXTENSION_DIRECTIVE.captures(line)?;
+            (&cap[2] == "require").then(|| (line_num, cap.get(1).unwrap()))
+        })
+        .collect();
+    if required.is_empty() {
+        return vec![];
+    }
+
+    let supported = supported_extensions();
+    if supported.is_empty() {
+        return vec![];
+    }
+    required
+        .into_iter()
+        .filter(|(_, name)| !supported.contains(name.as_str()))
+        .map(|(line_num, name)| {
+            new_diagnostic(
+                "unsupported-extension",
+                DiagnosticSeverity::WARNING,
+                Range::new(
+                    Position::new(line_num as u32, name.start() as u32),
+                    Position::new(line_num as u32, name.end() as u32),
+                ),
+                format!(
+                    "{} is required, but isn't supported by the OpenGL context validating this shader, so validation will fail",
+                    name.as_str()
+                ),
+            )
+        })
+        .collect()
+}
+
+/// Flags `/`-rooted includes in files of a shader pack nested inside the workspace, e.g. in
+/// `<workspace>/packs/foo/shaders`. Those includes are resolved against the workspace's own
+/// `shaders_root` rather than the nested pack's `shaders` folder, so they most likely pull in the wrong file.
+pub fn foreign_workspace_includes(path: &Path, source: &str, shaders_root: &Path) -> Vec<Diagnostic> {
+    let owning_root = match path
+        .ancestors()
+        .skip(1)
+        .find(|dir| dir.file_name().map_or(false, |name| name == "shaders"))
+    {
+        Some(dir) => dir,
+        None => return vec![],
+    };
+    if owning_root == shaders_root {
+        return vec![];
+    }
+
+    source
+        .lines()
+        .enumerate()
+        .filter_map(|(line_num, line)| {
+            let cap = RE_INCLUDE.captures(line)?.get(1).unwrap();
+            let include = cap.as_str().trim();
+            if !include.starts_with('/') {
+                return None;
+            }
+
+            Some(new_diagnostic(
+                "foreign-workspace-include",
+                DiagnosticSeverity::WARNING,
+                Range::new(
+                    Position::new(line_num as u32, linemap::utf16_column(line, cap.start()) as u32),
+                    Position::new(line_num as u32, linemap::utf16_column(line, cap.end()) as u32),
+                ),
+                format!(
+                    "\"{}\" is resolved against {:?} rather than this shader pack's {:?}. Consider opening the shader pack as its own workspace",
+                    include, shaders_root, owning_root
+                ),
+            ))
+        })
+        .collect()
+}
+
+/// Flags relative includes whose path also names a file at the `shaders_root`. The include resolves relative to
+/// the including file, but a reader may well expect the file at the root, so an explicit `/`-rooted path is
+/// suggested instead.
+pub fn ambiguous_relative_includes(path: &Path, source: &str, shaders_root: &Path) -> Vec<Diagnostic> {
+    let parent = match path.parent() {
+        Some(parent) if parent != shaders_root => parent,
+        _ => return vec![],
+    };
+
+    source
+        .lines()
+        .enumerate()
+        .filter_map(|(line_num, line)| {
+            let cap = RE_INCLUDE.captures(line)?.get(1).unwrap();
+            let include = cap.as_str().trim();
+            if include.is_empty() || include.starts_with('/') {
+                return None;
+            }
+
+            let resolved = crate::normalize_path(&parent.join(PathBuf::from_slash(include)));
+            let at_root = crate::normalize_path(&shaders_root.join(PathBuf::from_slash(include)));
+            // a world folder's copy overrides the base pack's on purpose, and without one the base pack's is included
+            if resolved == at_root || !at_root.is_file() || toplevel::base_pack_path(&resolved, shaders_root).as_ref() == Some(&at_root) {
+                return None;
+            }
+            let rooted = format!("/{}", resolved.strip_prefix(shaders_root).ok()?.to_slash_lossy());
+
+            Some(new_diagnostic(
+                "ambiguous-include",
+                DiagnosticSeverity::INFORMATION,
+                Range::new(
+                    Position::new(line_num as u32, linemap::utf16_column(line, cap.start()) as u32),
+                    Position::new(line_num as u32, linemap::utf16_column(line, cap.end()) as u32),
+                ),
+                format!(
+                    "\"{}\" includes {} relative to this file, though /{} exists too. Consider including \"{}\" to make this explicit",
+                    include, rooted, include, rooted
+                ),
+            ))
+        })
+        .collect()
+}
+
+/// Flags a merged program that doesn't define `main` anywhere, on the first line of its top-level file. The
+/// driver's error for this doesn't point at any file. If the tree has syntax errors, a `main` may have been
+/// parsed as part of an error, so nothing is reported then.
+pub fn missing_main(tree: &Tree, merged: &str, source_mapper: &SourceMapper, graph: &CachedStableGraph) -> HashMap<Url, Vec<Diagnostic>> {
+    let mut diagnostics: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+    if defines_main(tree.root_node(), merged) || tree.root_node().has_error() {
+        return diagnostics;
+    }
+
+    let origins = source_mapper::merged_line_origins(merged);
+    let (merged_line, (source_num, line)) = match origins.iter().enumerate().find_map(|(i, origin)| origin.map(|o| (i, o))) {
+        Some(first) => first,
+        None => return diagnostics,
+    };
+    let path = graph.get_node(source_mapper.get_node(source_num));
+    let line_len = merged.lines().nth(merged_line).map_or(0, |l| l.len());
+
+    diagnostics
+        .entry(Url::from_file_path(&path).unwrap())
+        .or_default()
+        .push(new_diagnostic(
+            "missing-main",
+            DiagnosticSeverity::ERROR,
+            Range::new(Position::new(line as u32, 0), Position::new(line as u32, line_len as u32)),
+            "No `main` function is defined in this shader or any of the files it includes",
+        ));
+    diagnostics
+}
+
+/// Returns whether `main` is defined anywhere in the tree, including in preprocessor conditionals.
+pub fn defines_main(node: Node, source: &str) -> bool {
+    if node.kind() == "function_definition" {
+        let name = node
+            .child_by_field_name("declarator")
+            .and_then(|d| d.child_by_field_name("declarator"))
+            .map(|name| name.utf8_text(source.as_bytes()).unwrap());
+        return name == Some("main");
+    }
+
+    let mut cursor = node.walk();
+    let found = node.children(&mut cursor).any(|child| defines_main(child, source));
+    found
+}
+
+/// Flags every definition of `main` in a merged program that defines it more than once, usually
+/// because an include also defines it. The driver only reports a redefinition at the second
+/// definition, so each definition is flagged with the files that define `main`.
+///
+/// Definitions nested in preprocessor conditionals are ignored, as they are commonly used to
+/// select between alternative `main`s.
+pub fn duplicate_main(tree: &Tree, merged: &str, source_mapper: &SourceMapper, graph: &CachedStableGraph) -> HashMap<Url, Vec<Diagnostic>> {
+    let origins = source_mapper::merged_line_origins(merged);
+
+    let mut definitions: Vec<(PathBuf, Range)> = Vec::new();
+    let mut cursor = tree.root_node().walk();
+    for child in tree.root_node().children(&mut cursor) {
+        if child.kind() != "function_definition" {
+            continue;
+        }
+        let name = match child
+            .child_by_field_name("declarator")
+            .and_then(|d| d.child_by_field_name("declarator"))
+        {
+            Some(name) => name,
+            None => continue,
+        };
+        if name.utf8_text(merged.as_bytes()).unwrap() != "main" {
+            continue;
+        }
+
+        if let Some(location) = merged_location(&name, merged, &origins, source_mapper, graph) {
+            definitions.push(location);
+        }
+    }
+
+    let mut diagnostics: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+    if definitions.len() < 2 {
+        return diagnostics;
+    }
+
+    let mut files: Vec<String> = Vec::new();
+    for (path, _) in &definitions {
+        let name = path.file_name().unwrap().to_str().unwrap().to_string();
+        if !files.contains(&name) {
+            files.push(name);
+        }
+    }
+
+    for (path, range) in definitions {
+        diagnostics
+            .entry(Url::from_file_path(&path).unwrap())
+            .or_default()
+            .push(new_diagnostic(
+                "duplicate-main",
+                DiagnosticSeverity::ERROR,
+                range,
+                format!("`main` is defined more than once in this program, in: {}", files.join(", ")),
+            ));
+    }
+    diagnostics
+}
+
+/// Flags functions defined in a merged program that have the name of a GLSL built-in function, such as
+/// `mix` or `texture`. Depending on the version this either fails to compile or silently replaces the
+/// built-in for the whole program, neither of which is usually intended.
+pub fn builtin_function_redefinitions(
+    tree: &Tree, merged: &str, source_mapper: &SourceMapper, graph: &CachedStableGraph,
+) -> HashMap<Url, Vec<Diagnostic>> {
+    let origins = source_mapper::merged_line_origins(merged);
+
+    let mut diagnostics: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+    let mut cursor = tree.root_node().walk();
+    for child in tree.root_node().children(&mut cursor) {
+        if child.kind() != "function_definition" {
+            continue;
+        }
+        let name = match child
+            .child_by_field_name("declarator")
+            .and_then(|d| d.child_by_field_name("declarator"))
+        {
+            Some(name) => name,
+            None => continue,
+        };
+        let name_text = name.utf8_text(merged.as_bytes()).unwrap();
+        if !builtins::is_builtin_function(name_text) {
+            continue;
+        }
+
+        if let Some((path, range)) = merged_location(&name, merged, &origins, source_mapper, graph) {
+            diagnostics
+                .entry(Url::from_file_path(&path).unwrap())
+                .or_default()
+                .push(new_diagnostic(
+                    "builtin-redefinition",
+                    DiagnosticSeverity::WARNING,
+                    range,
+                    format!("`{}` redefines the built-in function of the same name", name_text),
+                ));
+        }
+    }
+    diagnostics
+}
+
+/// Checks that the brackets, braces and parentheses of a merged program are balanced, ignoring comments and
+/// preprocessor directives, and flags the first delimiter that isn't. Drivers usually only report a missing
+/// closing delimiter at the end of the program, far from its cause.
+///
+/// Branches of preprocessor conditionals commonly open the same delimiter in different ways, so each branch is
+/// checked from the state before the conditional, and the state after the first branch is kept.
+pub fn unbalanced_delimiters(merged: &str, source_mapper: &SourceMapper, graph: &CachedStableGraph) -> HashMap<Url, Vec<Diagnostic>> {
+    // the open delimiters, with their merged line and column
+    let mut open: Vec<(char, usize, usize)> = Vec::new();
+    // for each enclosing conditional, the open delimiters before it and after its first branch, if it ended
+    let mut conditionals: Vec<(Vec<(char, usize, usize)>, Option<Vec<(char, usize, usize)>>)> = Vec::new();
+
+    let mut problem: Option<(usize, usize, String)> = None;
+    let mut in_block_comment = false;
+    let mut in_directive = false;
+
+    'lines: for (line_num, line) in merged.lines().enumerate() {
+        if in_directive || (!in_block_comment && line.trim_start().starts_with('#')) {
+            in_directive = line.ends_with('\\');
+            let directive = line.trim_start().trim_start_matches('#').trim_start();
+            if directive.starts_with("if") {
+                conditionals.push((open.clone(), None));
+            } else if directive.starts_with("el") {
+                if let Some((before, first_branch)) = conditionals.last_mut() {
+                    first_branch.get_or_insert_with(|| open.clone());
+                    open = before.clone();
+                }
+            } else if directive.starts_with("endif") {
+                if let Some((_, Some(first_branch))) = conditionals.pop() {
+                    open = first_branch;
+                }
+            }
+            continue;
+        }
+
+        let mut column = 0;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            let current_column = column;
+            column += c.len_utf16();
+
+            if in_block_comment {
+                if c == '*' && chars.peek() == Some(&'/') {
+                    chars.next();
+                    column += 1;
+                    in_block_comment = false;
+                }
+                continue;
+            }
+
+            match c {
+                '/' if chars.peek() == Some(&'/') => continue 'lines,
+                '/' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    column += 1;
+                    in_block_comment = true;
+                }
+                '(' | '[' | '{' => open.push((c, line_num, current_column)),
+                ')' | ']' | '}' => {
+                    let expected = match open.pop() {
+                        Some((opening, _, _)) => closing_delimiter(opening),
+                        None => {
+                            problem = Some((line_num, current_column, format!("`{}` doesn't close anything", c)));
+                            break 'lines;
+                        }
+                    };
+                    if c != expected {
+                        problem = Some((line_num, current_column, format!("Expected `{}`, found `{}`", expected, c)));
+                        break 'lines;
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    let problem = problem.or_else(|| {
+        open.last()
+            .map(|(c, line, column)| (*line, *column, format!("`{}` is never closed", c)))
+    });
+
+    let mut diagnostics: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+    let (merged_line, column, message) = match problem {
+        Some(problem) => problem,
+        None => return diagnostics,
+    };
+
+    let origins = source_mapper::merged_line_origins(merged);
+    if let Some((source_num, line)) = origins.get(merged_line).copied().flatten() {
+        let path = graph.get_node(source_mapper.get_node(source_num));
+        diagnostics
+            .entry(Url::from_file_path(&path).unwrap())
+            .or_default()
+            .push(new_diagnostic(
+                "unbalanced-delimiter",
+                DiagnosticSeverity::ERROR,
+                Range::new(
+                    Position::new(line as u32, column as u32),
+                    Position::new(line as u32, column as u32 + 1),
+                ),
+                message,
+            ));
+    }
+    diagnostics
+}
+
+fn closing_delimiter(opening: char) -> char {
+    match opening {
+        '(' => ')',
+        '[' => ']',
+        _ => '}',
+    }
+}
+
+/// A `#define` found in a merged shader, located in the file it was merged from.
+struct MacroDefinition {
+    path: PathBuf,
+    range: Range,
+    // the expansion, with comments removed and whitespace collapsed
+    body: String,
+    // the branch of each enclosing preprocessor conditional, as the conditional's and the branch's index
+    branches: Vec<(usize, usize)>,
+}
+
+/// Flags macros that are defined again, with a different expansion, while still defined, e.g. by an include and
+/// then by the file including it. Redefinitions with the same expansion are allowed, as are definitions in
+/// different branches of a preprocessor conditional, as only one of those is compiled. Defaults guarded by
+/// `#ifndef NAME` or `#if !defined(NAME)` are only compiled when NAME isn't defined yet, so aren't compared
+/// against earlier definitions.
+pub fn macro_redefinitions(merged: &str, source_mapper: &SourceMapper, graph: &CachedStableGraph) -> HashMap<Url, Vec<Diagnostic>> {
+    let mut diagnostics: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+    let mut defined: HashMap<String, Vec<MacroDefinition>> = HashMap::new();
+
+    let mut branches: Vec<(usize, usize)> = Vec::new();
+    // the macro each enclosing conditional's current branch is compiled without, if any
+    let mut guards: Vec<Option<String>> = Vec::new();
+    let mut conditionals = 0;
+
+    let origins = source_mapper::merged_line_origins(merged);
+    let lines: Vec<&str> = merged.lines().collect();
+    let mut line_num = 0;
+    while line_num < lines.len() {
+        let mut line = lines[line_num].to_string();
+        // where each of the lines joined into `line` starts in it, and which merged line it is
+        let mut line_starts = vec![(0, line_num)];
+        line_num += 1;
+        // directives continue on the next line after a trailing backslash
+        while line.ends_with('\\') && line_num < lines.len() {
+            line.pop();
+            line.push(' ');
+            line_starts.push((line.len(), line_num));
+            line.push_str(lines[line_num]);
+            line_num += 1;
+        }
+
+        let directive = match line.trim_start().strip_prefix('#') {
+            Some(directive) => directive.trim_start(),
+            None => continue,
+        };
+        if directive.starts_with("if") {
+            branches.push((conditionals, 0));
+            guards.push(
+                RE_NOT_DEFINED_GUARD
+                    .captures(directive)
+                    .and_then(|cap| cap.get(1).or_else(|| cap.get(2)))
+                    .map(|name| name.as_str().to_string()),
+            );
+            conditionals += 1;
+            continue;
+        } else if directive.starts_with("el") {
+            if let Some((_, branch)) = branches.last_mut() {
+                *branch += 1;
+            }
+            if let Some(guard) = guards.last_mut() {
+                *guard = None;
+            }
+            continue;
+        } else if directive.starts_with("endif") {
+            branches.pop();
+            guards.pop();
+            continue;
+        }
+
+        if let Some(cap) = RE_UNDEF_DIRECTIVE.captures(&line) {
+            defined.remove(cap.get(1).unwrap().as_str());
+            continue;
+        }
+        let cap = match RE_DEFINE_DIRECTIVE.captures(&line) {
+            Some(cap) => cap,
+            None => continue,
+        };
+        let name = cap.get(1).unwrap();
+        // the name may be on a continuation line, so its position is taken from the line it is written on
+        let (line_start, name_line) = *line_starts.iter().rev().find(|(start, _)| *start <= name.start()).unwrap();
+        let (source_num, file_line) = match origins.get(name_line).copied().flatten() {
+            Some(origin) => origin,
+            // lines added by merging, such as `#line` directives, aren't written by anyone
+            None => continue,
+        };
+        let column = |offset: usize| linemap::utf16_column(lines[name_line], offset - line_start) as u32;
+
+        let body = cap[2].split("//").next().unwrap().split_whitespace().collect::<Vec<_>>().join(" ");
+        let definition = MacroDefinition {
+            path: graph.get_node(source_mapper.get_node(source_num)),
+            range: Range::new(
+                Position::new(file_line as u32, column(name.start())),
+                Position::new(file_line as u32, column(name.end())),
+            ),
+            body,
+            branches: branches.clone(),
+        };
+
+        let guarded = guards.iter().any(|guard| guard.as_deref() == Some(name.as_str()));
+        let definitions = defined.entry(name.as_str().to_string()).or_default();
+        let conflicting = definitions
+            .iter()
+            .filter(|_| !guarded)
+            .find(|previous| previous.body != definition.body && compiled_together(&previous.branches, &definition.branches));
+        if let Some(previous) = conflicting {
+            let mut diagnostic = new_diagnostic(
+                "macro-redefinition",
+                DiagnosticSeverity::WARNING,
+                definition.range,
+                format!(
+                    "`{}` is redefined as `{}`, but was already defined as `{}` on line {} of {}",
+                    name.as_str(),
+                    definition.body,
+                    previous.body,
+                    previous.range.start.line + 1,
+                    previous.path.file_name().unwrap().to_str().unwrap()
+                ),
+            );
+            diagnostic.related_information = Some(vec![DiagnosticRelatedInformation {
+                location: Location::new(Url::from_file_path(&previous.path).unwrap(), previous.range),
+                message: format!("`{}` is first defined here", name.as_str()),
+            }]);
+            diagnostics
+                .entry(Url::from_file_path(&definition.path).unwrap())
+                .or_default()
+                .push(diagnostic);
+        }
+        definitions.push(definition);
+    }
+    diagnostics
+}
+
+// whether code in the conditional branches `a` and `b` can both be compiled, i.e. they take the same branch of
+// every conditional enclosing both
+fn compiled_together(a: &[(usize, usize)], b: &[(usize, usize)]) -> bool {
+    for ((a_conditional, a_branch), (b_conditional, b_branch)) in a.iter().zip(b) {
+        if a_conditional != b_conditional {
+            return true;
+        }
+        if a_branch != b_branch {
+            return false;
+        }
+    }
+    true
+}
+
+/// Returns the buffers that a fragment shader's outputs are written to, as listed by the last
+/// `/* DRAWBUFFERS:NNN */` or `/* RENDERTARGETS: N,N */` directive in the merged source, if any.
+pub fn declared_draw_buffers(merged: &str) -> Option<Vec<u32>> {
+    let mut declared = None;
+    for line in merged.lines() {
+        if let Some(cap) = RE_DRAW_BUFFERS.captures_iter(line).last() {
+            declared = Some(cap[1].chars().map(|c| c.to_digit(10).unwrap()).collect());
+        }
+        if let Some(cap) = RE_RENDER_TARGETS.captures_iter(line).last() {
+            declared = Some(cap[1].split(',').filter_map(|n| n.trim().parse().ok()).collect());
+        }
+    }
+    declared
+}
+
+/// Flags buffers listed more than once by a single `/* DRAWBUFFERS:NNN */` or `/* RENDERTARGETS: N,N */`
+/// directive, e.g. the second `0` of `DRAWBUFFERS:001`. Two outputs can't be drawn to the same buffer, so the
+/// pass fails to set up its draw buffers.
+pub fn duplicate_draw_buffers(source: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for (line_num, line) in source.lines().enumerate() {
+        // each listed buffer, with its byte range in the line
+        let mut buffers: Vec<(&str, usize, usize)> = Vec::new();
+        for cap in RE_DRAW_BUFFERS.captures_iter(line) {
+            let list = cap.get(1).unwrap();
+            buffers.extend(list.as_str().char_indices().map(|(i, c)| {
+                let start = list.start() + i;
+                (&line[start..start + c.len_utf8()], start, start + c.len_utf8())
+            }));
+        }
+        for cap in RE_RENDER_TARGETS.captures_iter(line) {
+            let list = cap.get(1).unwrap();
+            buffers.extend(
+                RE_BUFFER_NUMBER
+                    .find_iter(list.as_str())
+                    .map(|n| (n.as_str(), list.start() + n.start(), list.start() + n.end())),
+            );
+        }
+
+        let mut listed = HashSet::new();
+        for (buffer, start, end) in buffers {
+            if listed.insert(buffer.parse::<u32>().unwrap()) {
+                continue;
+            }
+            diagnostics.push(new_diagnostic(
+                "duplicate-draw-buffer",
+                DiagnosticSeverity::ERROR,
+                Range::new(
+                    Position::new(line_num as u32, start as u32),
+                    Position::new(line_num as u32, end as u32),
+                ),
+                format!(
+                    "Buffer {} is already listed by this directive, but each output must be drawn to a different buffer",
+                    buffer
+                ),
+            ));
+        }
+    }
+    diagnostics
+}
+
+/// Flags writes to `gl_FragData[i]` and declarations of `layout(location = i) out` variables in a merged
+/// fragment shader where `i` is past the buffers listed by its `DRAWBUFFERS` or `RENDERTARGETS` directive.
+/// Output `i` is written to the `i`th listed buffer, so these writes silently go nowhere.
+pub fn undeclared_draw_buffers(
+    tree: &Tree, merged: &str, source_mapper: &SourceMapper, graph: &CachedStableGraph,
+) -> HashMap<Url, Vec<Diagnostic>> {
+    let mut diagnostics: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+    let declared = match declared_draw_buffers(merged) {
+        Some(declared) => declared,
+        None => return diagnostics,
+    };
+
+    // the output index written to, and the node writing it
+    let mut writes: Vec<(u32, Node)> = Vec::new();
+    collect_frag_data_writes(tree.root_node(), merged, &mut writes);
+
+    for declaration in shader_interface::global_declarations(tree.root_node()) {
+        let qualifiers = shader_interface::declaration_qualifiers(&declaration, merged);
+        let location = match shader_interface::layout_location(&qualifiers) {
+            Some(location) if qualifiers.iter().any(|q| q == "out") => location,
+            _ => continue,
+        };
+
+        let mut declarator_cursor = declaration.walk();
+        for declarator in declaration.children_by_field_name("declarator", &mut declarator_cursor) {
+            if let Some(ident) = shader_interface::declarator_identifier(declarator) {
+                writes.push((location, ident));
+            }
+        }
+    }
+
+    let origins = source_mapper::merged_line_origins(merged);
+    for (index, node) in writes {
+        if (index as usize) < declared.len() {
+            continue;
+        }
+        let (path, range) = match merged_location(&node, merged, &origins, source_mapper, graph) {
+            Some(location) => location,
+            None => continue,
+        };
+        diagnostics
+            .entry(Url::from_file_path(&path).unwrap())
+            .or_default()
+            .push(new_diagnostic(
+                "undeclared-draw-buffer",
+                DiagnosticSeverity::WARNING,
+                range,
+                format!(
+                    "`{}` writes to output {}, but the draw buffer directive only declares {} buffer(s): {}",
+                    node.utf8_text(merged.as_bytes()).unwrap(),
+                    index,
+                    declared.len(),
+                    declared.iter().map(|b| b.to_string()).collect::<Vec<_>>().join(", ")
+                ),
+            ));
+    }
+    diagnostics
+}
+
+/// Flags the uses of `gl_FragColor` in a merged fragment shader that also uses `gl_FragData`, which drivers
+/// reject as a shader may only write one of them. Versions that removed both, 1.40 and core profiles from
+/// 1.50 onwards, are left to the driver, which reports them as undeclared.
+pub fn mixed_fragment_outputs(
+    tree: &Tree, merged: &str, source_mapper: &SourceMapper, graph: &CachedStableGraph,
+) -> HashMap<Url, Vec<Diagnostic>> {
+    let mut diagnostics: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+
+    let (version, profile) = version_directive(merged).unwrap_or((110, None));
+    if version == 140 || (version >= 150 && profile.unwrap_or("core") == "core") {
+        return diagnostics;
+    }
+
+    let mut frag_color = Vec::new();
+    collect_identifiers(tree.root_node(), merged, "gl_FragColor", &mut frag_color);
+    let mut frag_data = Vec::new();
+    collect_identifiers(tree.root_node(), merged, "gl_FragData", &mut frag_data);
+    if frag_data.is_empty() {
+        return diagnostics;
+    }
+
+    let mut message =
+        "`gl_FragColor` can't be written in a shader that also writes `gl_FragData`, write `gl_FragData[0]` instead".to_string();
+    if version >= 130 {
+        message.push_str(". Both are deprecated since GLSL 1.30 in favour of `out` variables");
+    }
+
+    let origins = source_mapper::merged_line_origins(merged);
+    for node in frag_color {
+        let (path, range) = match merged_location(&node, merged, &origins, source_mapper, graph) {
+            Some(location) => location,
+            None => continue,
+        };
+        diagnostics
+            .entry(Url::from_file_path(&path).unwrap())
+            .or_default()
+            .push(new_diagnostic(
+                "mixed-fragment-outputs",
+                DiagnosticSeverity::ERROR,
+                range,
+                message.clone(),
+            ));
+    }
+    diagnostics
+}
+
+/// Flags the `varying` and `attribute` qualifiers of a merged shader's global declarations in versions that replaced
+/// them with `in` and `out`. They are deprecated since GLSL 1.30 and in compatibility profiles, and removed in 1.40
+/// and in core profiles from 1.50 onwards.
+pub fn legacy_storage_qualifiers(
+    tree: &Tree, merged: &str, source_mapper: &SourceMapper, graph: &CachedStableGraph,
+) -> HashMap<Url, Vec<Diagnostic>> {
+    let mut diagnostics: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+
+    let (version, profile) = version_directive(merged).unwrap_or((110, None));
+    if version < 130 {
+        return diagnostics;
+    }
+    let removed = version == 140 || (version >= 150 && profile.unwrap_or("core") == "core");
+
+    let origins = source_mapper::merged_line_origins(merged);
+    for declaration in shader_interface::global_declarations(tree.root_node()) {
+        let type_node = declaration.child_by_field_name("type");
+        let mut cursor = declaration.walk();
+        for child in declaration.children(&mut cursor) {
+            if Some(child) == type_node {
+                break;
+            }
+            let text = child.utf8_text(merged.as_bytes()).unwrap();
+            for (qualifier, replacement) in [("varying", "`in` or `out`"), ("attribute", "`in`")] {
+                let offset = match find_identifier(text, qualifier) {
+                    Some(offset) => offset,
+                    None => continue,
+                };
+                let (path, mut range) = match merged_location(&child, merged, &origins, source_mapper, graph) {
+                    Some(location) => location,
+                    None => continue,
+                };
+                range.start.character += linemap::utf16_column(text, offset) as u32;
+                range.end.character = range.start.character + qualifier.len() as u32;
+
+                let (severity, message) = if removed {
+                    let directive = format!("#version {}{}", version, profile.map(|p| format!(" {}", p)).unwrap_or_default());
+                    (
+                        DiagnosticSeverity::ERROR,
+                        format!(
+                            "`{}` is not available in `{}`, declare the variable {} instead",
+                            qualifier, directive, replacement
+                        ),
+                    )
+                } else {
+                    (
+                        DiagnosticSeverity::WARNING,
+                        format!(
+                            "`{}` is deprecated since GLSL 1.30, declare the variable {} instead",
+                            qualifier, replacement
+                        ),
+                    )
+                };
+                diagnostics
+                    .entry(Url::from_file_path(&path).unwrap())
+                    .or_default()
+                    .push(new_diagnostic("legacy-storage-qualifier", severity, range, message));
+            }
+        }
+    }
+    diagnostics
+}
+
+// the version and profile of the first `#version` directive
+fn version_directive(source: &str) -> Option<(u32, Option<&str>)> {
+    source.lines().find_map(|line| {
+        let cap = RE_VERSION_DIRECTIVE.captures(line)?;
+        Some((cap.get(1)?.as_str().parse().ok()?, cap.get(2).map(|p| p.as_str())))
+    })
+}
+
+// collects the identifiers with the given name
+fn collect_identifiers<'a>(node: Node<'a>, source: &str, name: &str, identifiers: &mut Vec<Node<'a>>) {
+    if node.kind() == "identifier" && node.utf8_text(source.as_bytes()).unwrap() == name {
+        identifiers.push(node);
+        return;
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_identifiers(child, source, name, identifiers);
+    }
+}
+
+/// Flags `in` and `out` declarations of a merged shader whose explicit `layout(location = N)` is already used by
+/// an earlier declaration of the same kind. Outputs with different `layout(index = N)`s share a location for dual
+/// source blending. Declarations in different preprocessor blocks aren't compared, as they may never be compiled
+/// together.
+pub fn location_collisions(
+    tree: &Tree, merged: &str, source_mapper: &SourceMapper, graph: &CachedStableGraph,
+) -> HashMap<Url, Vec<Diagnostic>> {
+    let mut diagnostics: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+
+    // the first declarator at each storage, location, blend index and enclosing block
+    let mut used: HashMap<(&str, u32, u32, usize), Node> = HashMap::new();
+
+    let origins = source_mapper::merged_line_origins(merged);
+    for declaration in shader_interface::global_declarations(tree.root_node()) {
+        let qualifiers = shader_interface::declaration_qualifiers(&declaration, merged);
+        let storage = match qualifiers.iter().find(|q| *q == "in" || *q == "out") {
+            Some(q) if q == "in" => "input",
+            Some(_) => "output",
+            None => continue,
+        };
+        let location = match shader_interface::layout_location(&qualifiers) {
+            Some(location) => location,
+            None => continue,
+        };
+        let index = shader_interface::layout_index(&qualifiers).unwrap_or(0);
+        let block = declaration.parent().map_or(0, |parent| parent.id());
+
+        let mut declarator_cursor = declaration.walk();
+        for declarator in declaration.children_by_field_name("declarator", &mut declarator_cursor) {
+            let ident = match shader_interface::declarator_identifier(declarator) {
+                Some(ident) => ident,
+                None => continue,
+            };
+            let first = match used.get(&(storage, location, index, block)) {
+                Some(first) => *first,
+                None => {
+                    used.insert((storage, location, index, block), ident);
+                    continue;
+                }
+            };
+
+            let (path, range) = match merged_location(&ident, merged, &origins, source_mapper, graph) {
+                Some(location) => location,
+                None => continue,
+            };
+            let mut diagnostic = new_diagnostic(
+                "location-collision",
+                DiagnosticSeverity::ERROR,
+                range,
+                format!(
+                    "`{}` uses {} location {}, which `{}` already uses",
+                    ident.utf8_text(merged.as_bytes()).unwrap(),
+                    storage,
+                    location,
+                    first.utf8_text(merged.as_bytes()).unwrap()
+                ),
+            );
+            if let Some((first_path, first_range)) = merged_location(&first, merged, &origins, source_mapper, graph) {
+                diagnostic.related_information = Some(vec![DiagnosticRelatedInformation {
+                    location: Location::new(Url::from_file_path(&first_path).unwrap(), first_range),
+                    message: format!("{} location {} is first used here", storage, location),
+                }]);
+            }
+            diagnostics.entry(Url::from_file_path(&path).unwrap()).or_default().push(diagnostic);
+        }
+    }
+    diagnostics
+}
+
+/// A uniform declared by one stage of a program, located in the file it was merged from.
+pub struct StageUniform {
+    // the stage declaring it, e.g. `vertex`
+    pub stage: String,
+    pub variable: InterfaceVariable,
+    pub path: PathBuf,
+    pub range: Range,
+}
+
+/// Returns the uniforms declared by a merged shader of the given stage.
+pub fn stage_uniforms(
+    tree: &Tree, merged: &str, source_mapper: &SourceMapper, graph: &CachedStableGraph, stage: &str,
+) -> Vec<StageUniform> {
+    let origins = source_mapper::merged_line_origins(merged);
+    let lines: Vec<&str> = merged.lines().collect();
+    ShaderInterface::from_tree(tree, merged, false)
+        .uniforms
+        .into_iter()
+        .filter_map(|variable| {
+            let (source_num, line) = origins.get(variable.line).copied().flatten()?;
+            // the whole declaration, without its indentation
+            let text = lines[variable.line];
+            let start = text.len() - text.trim_start().len();
+            let end = text.trim_end().len();
+            Some(StageUniform {
+                stage: stage.to_string(),
+                path: graph.get_node(source_mapper.get_node(source_num)),
+                range: Range::new(
+                    Position::new(line as u32, linemap::utf16_column(text, start) as u32),
+                    Position::new(line as u32, linemap::utf16_column(text, end) as u32),
+                ),
+                variable,
+            })
+        })
+        .collect()
+}
+
+/// Flags uniforms of a merged shader that another stage of the same program declares with a different type,
+/// which fails linking the program with a message that rarely says where. A uniform declared with several types,
+/// e.g. in the branches of a preprocessor conditional, is only flagged if none of them match.
+pub fn mismatched_uniforms(
+    tree: &Tree, merged: &str, source_mapper: &SourceMapper, graph: &CachedStableGraph, other_stages: &[StageUniform],
+) -> HashMap<Url, Vec<Diagnostic>> {
+    let mut diagnostics: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+
+    for uniform in stage_uniforms(tree, merged, source_mapper, graph, "") {
+        let declared: Vec<&StageUniform> = other_stages
+            .iter()
+            .filter(|other| other.variable.name == uniform.variable.name)
+            .collect();
+        if declared.iter().any(|other| other.variable.type_name == uniform.variable.type_name) {
+            continue;
+        }
+        let other = match declared.first() {
+            Some(other) => other,
+            None => continue,
+        };
+
+        let mut diagnostic = new_diagnostic(
+            "mismatched-uniform",
+            DiagnosticSeverity::ERROR,
+            uniform.range,
+            format!(
+                "`{}` is declared `{}` here but `{}` in the {} shader, so the program will fail to link",
+                uniform.variable.name, uniform.variable.type_name, other.variable.type_name, other.stage
+            ),
+        );
+        diagnostic.related_information = Some(vec![DiagnosticRelatedInformation {
+            location: Location::new(Url::from_file_path(&other.path).unwrap(), other.range),
+            message: format!(
+                "`{}` declared `{}` in the {} shader",
+                other.variable.name, other.variable.type_name, other.stage
+            ),
+        }]);
+        diagnostics
+            .entry(Url::from_file_path(&uniform.path).unwrap())
+            .or_default()
+            .push(diagnostic);
+    }
+    diagnostics
+}
+
+/// A `uniform` or `buffer` block declared by one stage of a program, located in the file it was merged from.
+pub struct StageBlock {
+    // the stage declaring it, e.g. `vertex`
+    pub stage: String,
+    pub block: InterfaceBlock,
+    pub path: PathBuf,
+    pub range: Range,
+}
+
+/// Returns the `uniform` and `buffer` blocks declared by a merged shader of the given stage.
+pub fn stage_blocks(tree: &Tree, merged: &str, source_mapper: &SourceMapper, graph: &CachedStableGraph, stage: &str) -> Vec<StageBlock> {
+    let origins = source_mapper::merged_line_origins(merged);
+    let lines: Vec<&str> = merged.lines().collect();
+    ShaderInterface::from_tree(tree, merged, false)
+        .blocks
+        .into_iter()
+        .filter_map(|block| {
+            let (source_num, line) = origins.get(block.line).copied().flatten()?;
+            // the block name
+            let text = lines[block.line];
+            let start = find_identifier(text, &block.name)?;
+            let end = start + block.name.len();
+            Some(StageBlock {
+                stage: stage.to_string(),
+                path: graph.get_node(source_mapper.get_node(source_num)),
+                range: Range::new(
+                    Position::new(line as u32, linemap::utf16_column(text, start) as u32),
+                    Position::new(line as u32, linemap::utf16_column(text, end) as u32),
+                ),
+                block,
+            })
+        })
+        .collect()
+}
+
+// the byte offset of `name` in `text` as a whole identifier, rather than part of a longer one
+fn find_identifier(text: &str, name: &str) -> Option<usize> {
+    let is_identifier = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    text.match_indices(name)
+        .map(|(start, _)| start)
+        .find(|&start| !text[..start].ends_with(is_identifier) && !text[start + name.len()..].starts_with(is_identifier))
+}
+
+/// Flags `uniform` and `buffer` blocks of a merged shader that share a binding point with a differently named block,
+/// whether in the same stage or another stage of the program, and blocks that another stage declares with the same
+/// name but a different binding or different members. Either fails linking the program, or silently feeds one block
+/// the other's data.
+pub fn block_conflicts(
+    tree: &Tree, merged: &str, source_mapper: &SourceMapper, graph: &CachedStableGraph, other_stages: &[StageBlock],
+) -> HashMap<Url, Vec<Diagnostic>> {
+    let mut diagnostics: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+
+    let blocks = stage_blocks(tree, merged, source_mapper, graph, "");
+    for (i, current) in blocks.iter().enumerate() {
+        let block = &current.block;
+        let mut push = |code: &str, message: String, other: &StageBlock, related: String| {
+            let mut diagnostic = new_diagnostic(code, DiagnosticSeverity::ERROR, current.range, message);
+            diagnostic.related_information = Some(vec![DiagnosticRelatedInformation {
+                location: Location::new(Url::from_file_path(&other.path).unwrap(), other.range),
+                message: related,
+            }]);
+            diagnostics
+                .entry(Url::from_file_path(&current.path).unwrap())
+                .or_default()
+                .push(diagnostic);
+        };
+
+        let declared: Vec<&StageBlock> = other_stages.iter().filter(|other| other.block.name == block.name).collect();
+        let matching = declared
+            .iter()
+            .any(|other| other.block.binding == block.binding && other.block.members == block.members);
+        if !matching {
+            if let Some(other) = declared.first() {
+                push(
+                    "mismatched-block",
+                    format!(
+                        "`{}` is declared differently here than in the {} shader, so the program will fail to link",
+                        block.name, other.stage
+                    ),
+                    other,
+                    format!("`{}` declared in the {} shader", other.block.name, other.stage),
+                );
+                continue;
+            }
+        }
+
+        let binding = match block.binding {
+            Some(binding) => binding,
+            None => continue,
+        };
+        // blocks that this stage declares too are compared where it declares them
+        let other_stage_only = other_stages
+            .iter()
+            .filter(|other| !blocks.iter().any(|b| b.block.name == other.block.name));
+        let sharing = blocks[..i]
+            .iter()
+            .chain(other_stage_only)
+            .find(|other| other.block.storage == block.storage && other.block.binding == Some(binding) && other.block.name != block.name);
+        if let Some(other) = sharing {
+            let stage = if other.stage.is_empty() {
+                String::new()
+            } else {
+                format!(" in the {} shader", other.stage)
+            };
+            push(
+                "block-binding-collision",
+                format!(
+                    "`{}` uses {} binding {}, which `{}` already uses{}",
+                    block.name, block.storage, binding, other.block.name, stage
+                ),
+                other,
+                format!("{} binding {} is first used here", block.storage, binding),
+            );
+        }
+    }
+    diagnostics
+}
+
+/// Flags assignments, compound assignments and increments of variables that can't be written: `const` variables
+/// and parameters, uniforms and the stage's inputs, with `varying` being an input of fragment shaders only. The
+/// variable is resolved to its declaration in the innermost scope declaring it, so locals shadowing a global are
+/// left alone. Only plain variables and their fields, swizzles and elements are checked, and a variable declared
+/// more than once, e.g. in both branches of a preprocessor conditional, only if every declaration is read-only.
+///
+/// A plain `in` parameter is a copy of the argument that may be written, unlike a `const in` one.
+pub fn readonly_assignments(
+    tree: &Tree, merged: &str, source_mapper: &SourceMapper, graph: &CachedStableGraph, is_fragment: bool,
+) -> HashMap<Url, Vec<Diagnostic>> {
+    let mut diagnostics: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+
+    let mut targets = Vec::new();
+    collect_assignment_targets(tree.root_node(), &mut targets);
+
+    let origins = source_mapper::merged_line_origins(merged);
+    for target in targets {
+        let name = target.utf8_text(merged.as_bytes()).unwrap();
+        let qualifier = match readonly_qualifier(target, name, merged, is_fragment) {
+            Some(qualifier) => qualifier,
+            None => continue,
+        };
+        let (path, range) = match merged_location(&target, merged, &origins, source_mapper, graph) {
+            Some(location) => location,
+            None => continue,
+        };
+        diagnostics
+            .entry(Url::from_file_path(&path).unwrap())
+            .or_default()
+            .push(new_diagnostic(
+                "readonly-assignment",
+                DiagnosticSeverity::ERROR,
+                range,
+                format!("`{}` can't be assigned to, as it is declared `{}`", name, qualifier),
+            ));
+    }
+    diagnostics
+}
+
+// collects the variables written by assignments and increments
+fn collect_assignment_targets<'a>(node: Node<'a>, targets: &mut Vec<Node<'a>>) {
+    let target = match node.kind() {
+        "assignment_expression" => node.child_by_field_name("left"),
+        "update_expression" => node.child_by_field_name("argument"),
+        _ => None,
+    };
+    if let Some(variable) = target.and_then(assigned_variable) {
+        targets.push(variable);
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_assignment_targets(child, targets);
+    }
+}
+
+// follows fields, swizzles and subscripts of an assigned expression down to the variable, if it is one
+fn assigned_variable(node: Node) -> Option<Node> {
+    match node.kind() {
+        "identifier" => Some(node),
+        "field_expression" | "subscript_expression" => assigned_variable(node.child_by_field_name("argument")?),
+        "parenthesized_expression" => assigned_variable(node.named_child(0)?),
+        _ => None,
+    }
+}
+
+// returns the qualifier that makes the assigned variable read-only, from its declarations in the innermost scope
+fn readonly_qualifier(target: Node, name: &str, source: &str, is_fragment: bool) -> Option<&'static str> {
+    let declares = |declarator: Option<Node>| {
+        declarator
+            .and_then(shader_interface::declarator_identifier)
+            .map_or(false, |ident| ident.utf8_text(source.as_bytes()).unwrap() == name)
+    };
+
+    let mut scope = target.parent();
+    while let Some(node) = scope {
+        let (declarations, are_parameters): (Vec<Node>, bool) = match node.kind() {
+            "compound_statement" | "for_statement" => {
+                let mut cursor = node.walk();
+                let declarations = node
+                    .children(&mut cursor)
+                    .filter(|child| child.kind() == "declaration" && child.start_byte() < target.start_byte())
+                    .filter(|declaration| {
+                        let mut cursor = declaration.walk();
+                        let declared = declaration
+                            .children_by_field_name("declarator", &mut cursor)
+                            .any(|d| declares(Some(d)));
+                        declared
+                    })
+                    .collect();
+                (declarations, false)
+            }
+            "function_definition" => {
+                let parameters = node
+                    .child_by_field_name("declarator")
+                    .and_then(|declarator| declarator.child_by_field_name("parameters"));
+                let declarations = match parameters {
+                    Some(parameters) => {
+                        let mut cursor = parameters.walk();
+                        let declarations = parameters
+                            .named_children(&mut cursor)
+                            .filter(|param| param.kind() == "parameter_declaration" && declares(param.child_by_field_name("declarator")))
+                            .collect();
+                        declarations
+                    }
+                    None => vec![],
+                };
+                (declarations, true)
+            }
+            "translation_unit" => {
+                let declarations = shader_interface::global_declarations(node)
+                    .into_iter()
+                    .filter(|declaration| {
+                        let mut cursor = declaration.walk();
+                        let declared = declaration
+                            .children_by_field_name("declarator", &mut cursor)
+                            .any(|d| declares(Some(d)));
+                        declared
+                    })
+                    .collect();
+                (declarations, false)
+            }
+            _ => (vec![], false),
+        };
+
+        if !declarations.is_empty() {
+            let qualifiers = declarations
+                .iter()
+                .map(|declaration| {
+                    let qualifiers = shader_interface::declaration_qualifiers(declaration, source);
+                    readonly_storage(&qualifiers, are_parameters, is_fragment)
+                })
+                .collect::<Option<Vec<_>>>()?;
+            return qualifiers.first().copied();
+        }
+        scope = node.parent();
+    }
+    None
+}
+
+// the qualifier that makes a variable or parameter with the given qualifiers read-only, if any
+fn readonly_storage(qualifiers: &[String], is_parameter: bool, is_fragment: bool) -> Option<&'static str> {
+    let has = |name: &str| qualifiers.iter().any(|qualifier| qualifier == name);
+    if has("const") {
+        Some("const")
+    } else if is_parameter {
+        None
+    } else if has("uniform") {
+        Some("uniform")
+    } else if has("in") {
+        Some("in")
+    } else if has("attribute") {
+        Some("attribute")
+    } else if is_fragment && has("varying") {
+        Some("varying")
+    } else {
+        None
+    }
+}
+
+/// Flags `discard` statements in a merged shader of a stage other than fragment, named by `stage`, as
+/// only fragment shaders may discard. Statements in preprocessor conditionals are ignored, as they are
+/// commonly only compiled for the fragment stage.
+pub fn discard_outside_fragment(
+    tree: &Tree, merged: &str, source_mapper: &SourceMapper, graph: &CachedStableGraph, stage: &str,
+) -> HashMap<Url, Vec<Diagnostic>> {
+    let mut diagnostics: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+
+    let mut discards = Vec::new();
+    collect_discards(tree.root_node(), merged, &mut discards);
+
+    let origins = source_mapper::merged_line_origins(merged);
+    for node in discards {
+        let (path, range) = match merged_location(&node, merged, &origins, source_mapper, graph) {
+            Some(location) => location,
+            None => continue,
+        };
+        diagnostics
+            .entry(Url::from_file_path(&path).unwrap())
+            .or_default()
+            .push(new_diagnostic(
+                "discard-outside-fragment",
+                DiagnosticSeverity::ERROR,
+                range,
+                format!("`discard` is only allowed in fragment shaders, not in {} shaders", stage),
+            ));
+    }
+    diagnostics
+}
+
+// collects the `discard` statements. Depending on the grammar version, `discard` is its own token or a plain identifier.
+// Statements nested in preprocessor conditionals are skipped, as includes shared between stages commonly only discard
+// under e.g. `#ifdef FRAGMENT_SHADER`
+fn collect_discards<'a>(node: Node<'a>, source: &str, discards: &mut Vec<Node<'a>>) {
+    let is_discard = match node.kind() {
+        "discard" => true,
+        "identifier" => node.utf8_text(source.as_bytes()).unwrap() == "discard",
+        "preproc_if" | "preproc_ifdef" | "preproc_else" | "preproc_elif" => return,
+        _ => false,
+    };
+    if is_discard {
+        discards.push(node);
+        return;
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_discards(child, source, discards);
+    }
+}
+
+// collects all assignments to `gl_FragData[i]` with a literal index `i`
+fn collect_frag_data_writes<'a>(node: Node<'a>, source: &str, writes: &mut Vec<(u32, Node<'a>)>) {
+    if node.kind() == "assignment_expression" {
+        if let Some(left) = node
+            .child_by_field_name("left")
+            .filter(|left| left.kind() == "subscript_expression")
+        {
+            let argument = left
+                .child_by_field_name("argument")
+                .map(|n| n.utf8_text(source.as_bytes()).unwrap());
+            let index = left
+                .child_by_field_name("index")
+                .and_then(|n| n.utf8_text(source.as_bytes()).unwrap().trim().parse::<u32>().ok());
+            if let (Some("gl_FragData"), Some(index)) = (argument, index) {
+                writes.push((index, left));
+            }
+        }
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_frag_data_writes(child, source, writes);
+    }
+}
+
+/// Flags array declarations whose initializer has a different number of elements than the array's size,
+/// e.g. `const float weights[4] = float[](0.1, 0.2, 0.3);`, as well as sized array constructors with the
+/// wrong number of arguments. Sizes that aren't literal numbers, such as macros, are not checked.
+pub fn array_initializer_sizes(
+    tree: &Tree, merged: &str, source_mapper: &SourceMapper, graph: &CachedStableGraph,
+) -> HashMap<Url, Vec<Diagnostic>> {
+    let mut declarations = Vec::new();
+    collect_declarations(tree.root_node(), &mut declarations);
+
+    let origins = source_mapper::merged_line_origins(merged);
+    let mut diagnostics: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+    for declaration in declarations {
+        let type_size = declaration
+            .child_by_field_name("type")
+            .and_then(|t| RE_ARRAY_TYPE_SIZE.captures(t.utf8_text(merged.as_bytes()).unwrap()))
+            .and_then(|cap| cap[1].parse::<usize>().ok());
+
+        let mut cursor = declaration.walk();
+        for declarator in declaration.children_by_field_name("declarator", &mut cursor) {
+            if declarator.kind() != "init_declarator" {
+                continue;
+            }
+            let (name, value) = match (
+                declarator.child_by_field_name("declarator"),
+                declarator.child_by_field_name("value"),
+            ) {
+                (Some(name), Some(value)) => (name, value),
+                _ => continue,
+            };
+
+            let declared_size = if name.kind() == "array_declarator" {
+                name.child_by_field_name("size")
+                    .and_then(|size| size.utf8_text(merged.as_bytes()).unwrap().trim().parse::<usize>().ok())
+            } else {
+                type_size
+            };
+
+            let value_text = value.utf8_text(merged.as_bytes()).unwrap();
+            let (constructor, elements) = match RE_ARRAY_CONSTRUCTOR.captures(value_text) {
+                Some(cap) => (
+                    Some((cap[1].to_string(), cap[2].parse::<usize>().ok())),
+                    count_elements(&value_text[cap.get(0).unwrap().end() - 1..]),
+                ),
+                None if value_text.starts_with('{') => (None, count_elements(value_text)),
+                None => continue,
+            };
+            let elements = match elements {
+                Some(elements) => elements,
+                None => continue,
+            };
+
+            let message = match (declared_size, constructor) {
+                (Some(size), _) if size != elements => format!(
+                    "`{}` is declared with {} element(s), but initialized with {}",
+                    shader_interface::declarator_identifier(name).map_or("array", |ident| ident.utf8_text(merged.as_bytes()).unwrap()),
+                    size,
+                    elements
+                ),
+                (_, Some((type_name, Some(size)))) if size != elements => {
+                    format!("`{}[{}]` constructor is given {} element(s)", type_name, size, elements)
+                }
+                _ => continue,
+            };
+
+            if let Some((path, range)) = merged_location(&value, merged, &origins, source_mapper, graph) {
+                diagnostics
+                    .entry(Url::from_file_path(&path).unwrap())
+                    .or_default()
+                    .push(new_diagnostic("array-size-mismatch", DiagnosticSeverity::ERROR, range, message));
+            }
+        }
+    }
+    diagnostics
+}
+
+/// Hints at divisions of two integer literals in the initializer of a floating point variable, e.g.
+/// `float x = 1 / 2;`, as the division is done on integers and truncated before the result is converted.
+/// Only the literals and arithmetic directly making up the initializer are looked at, so a division passed
+/// to a function or constructor isn't flagged.
+pub fn integer_divisions(
+    tree: &Tree, merged: &str, source_mapper: &SourceMapper, graph: &CachedStableGraph,
+) -> HashMap<Url, Vec<Diagnostic>> {
+    let mut declarations = Vec::new();
+    collect_declarations(tree.root_node(), &mut declarations);
+
+    let origins = source_mapper::merged_line_origins(merged);
+    let mut diagnostics: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+    for declaration in declarations {
+        let type_name = match declaration.child_by_field_name("type") {
+            Some(type_node) => type_node.utf8_text(merged.as_bytes()).unwrap(),
+            None => continue,
+        };
+        if !RE_FLOAT_TYPE.is_match(type_name) {
+            continue;
+        }
+
+        let mut divisions = Vec::new();
+        let mut declarator_cursor = declaration.walk();
+        for declarator in declaration.children_by_field_name("declarator", &mut declarator_cursor) {
+            if let Some(value) = declarator
+                .child_by_field_name("value")
+                .filter(|_| declarator.kind() == "init_declarator")
+            {
+                collect_integer_divisions(value, merged, &mut divisions);
+            }
+        }
+
+        for division in divisions {
+            let (path, range) = match merged_location(&division, merged, &origins, source_mapper, graph) {
+                Some(location) => location,
+                None => continue,
+            };
+            diagnostics
+                .entry(Url::from_file_path(&path).unwrap())
+                .or_default()
+                .push(new_diagnostic(
+                    "integer-division",
+                    DiagnosticSeverity::HINT,
+                    range,
+                    format!(
+                        "`{}` is an integer division, so its result is truncated before it is converted to `{}`",
+                        division.utf8_text(merged.as_bytes()).unwrap(),
+                        type_name
+                    ),
+                ));
+        }
+    }
+    diagnostics
+}
+
+// collects the divisions of two integer literals in an expression, looking only into arithmetic and parentheses
+fn collect_integer_divisions<'a>(node: Node<'a>, source: &str, divisions: &mut Vec<Node<'a>>) {
+    let is_integer = |operand: Option<Node>| {
+        operand.map_or(false, |n| {
+            n.kind() == "number_literal" && RE_INTEGER_LITERAL.is_match(n.utf8_text(source.as_bytes()).unwrap())
+        })
+    };
+
+    match node.kind() {
+        "binary_expression" => {
+            let operator = node
+                .child_by_field_name("operator")
+                .map(|n| n.utf8_text(source.as_bytes()).unwrap());
+            if operator == Some("/") && is_integer(node.child_by_field_name("left")) && is_integer(node.child_by_field_name("right")) {
+                divisions.push(node);
+                return;
+            }
+        }
+        "parenthesized_expression" => (),
+        _ => return,
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_integer_divisions(child, source, divisions);
+    }
+}
+
+// collects all declarations, global or local
+fn collect_declarations<'a>(node: Node<'a>, declarations: &mut Vec<Node<'a>>) {
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if child.kind() == "declaration" {
+            declarations.push(child);
+        }
+        collect_declarations(child, declarations);
+    }
+}
+
+// counts the comma separated elements between the opening bracket `list` starts with and its matching
+// closing bracket, ignoring commas nested in other brackets. A trailing comma doesn't add an element
+fn count_elements(list: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut elements = 0;
+    let mut element_empty = true;
+    for c in list.chars() {
+        match c {
+            '(' | '[' | '{' => {
+                depth += 1;
+                if depth == 1 {
+                    continue;
+                }
+            }
+            ')' | ']' | '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    if !element_empty {
+                        elements += 1;
+                    }
+                    return Some(elements);
+                }
+            }
+            ',' if depth == 1 => {
+                elements += 1;
+                element_empty = true;
+                continue;
+            }
+            _ => (),
+        }
+        if !c.is_whitespace() {
+            element_empty = false;
+        }
+    }
+    None
+}
+
+// maps a node of a merged source back to the file and range it was merged from
+fn merged_location(
+    node: &Node, merged: &str, origins: &[Option<(source_mapper::SourceNum, usize)>], source_mapper: &SourceMapper,
+    graph: &CachedStableGraph,
+) -> Option<(PathBuf, Range)> {
+    let (source_num, line) = origins.get(node.start_position().row).copied().flatten()?;
+    let path = graph.get_node(source_mapper.get_node(source_num));
+    let end_column = if node.end_position().row == node.start_position().row {
+        node.end_position().column
+    } else {
+        node.start_position().column
+    };
+    // tree-sitter's columns are in bytes, while LSP's count UTF-16 code units
+    let line_start = node.start_byte() - node.start_position().column;
+    let text = merged[line_start..].lines().next().unwrap_or_default();
+    Some((
+        path,
+        Range::new(
+            Position::new(line as u32, linemap::utf16_column(text, node.start_position().column) as u32),
+            Position::new(line as u32, linemap::utf16_column(text, end_column) as u32),
+        ),
+    ))
+}
+
+#[cfg(test)]
+mod internal_diagnostics_test {
+    use std::collections::HashSet;
+    use std::path::PathBuf;
+
+    use rust_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString, Position, Range};
+    use url::Url;
+
+    use crate::graph::CachedStableGraph;
+    use crate::internal_diagnostics::{
+        ambiguous_relative_includes, count_elements, declared_draw_buffers, duplicate_draw_buffers, foreign_workspace_includes,
+        include_path_whitespace, macro_redefinitions, unresolvable_includes, unsupported_extensions, unterminated_block_comment,
+    };
+    use crate::opengl::{self, MockShaderValidator};
+    use crate::source_mapper::SourceMapper;
+    use crate::test::{copy_to_and_set_root, new_temp_server, server_for_fixture, server_for_sources};
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_include_path_whitespace() {
+        let source = "#version 120\n#include \" common.glsl \"\n#include \"/lib/util.glsl\"\n";
+
+        let diagnostics = include_path_whitespace(source, "?");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range, Range::new(Position::new(1, 10), Position::new(1, 23)));
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(diagnostics[0].code, Some(NumberOrString::String("include-path-whitespace".into())));
+        assert!(diagnostics[0].message.contains("\"common.glsl\""));
+
+        // the suffix is kept, and columns count UTF-16 code units
+        let source = "#include \"/lib/ü.glsl ?v=2\"\n#include \"/lib/util.glsl?v= 2\"\n";
+        let diagnostics = include_path_whitespace(source, "?");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range, Range::new(Position::new(0, 10), Position::new(0, 26)));
+        assert!(diagnostics[0].message.contains("\"/lib/ü.glsl?v=2\""));
+
+        // without a separator the suffix is part of the path
+        assert!(include_path_whitespace("#include \"/lib/util.glsl ?v=2\"\n", "").is_empty());
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_unresolvable_includes() {
+        let source =
+            "#version 120\n#define STR(x) #x\n#include STR(common.glsl)\n#include \"common.glsl\"\n#include <lib.glsl> // comment\n";
+
+        let diagnostics = unresolvable_includes(source);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range, Range::new(Position::new(2, 9), Position::new(2, 25)));
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::INFORMATION));
+        assert_eq!(diagnostics[0].code, Some(NumberOrString::String("unresolvable-include".into())));
+
+        // columns count UTF-16 code units
+        let diagnostics = unresolvable_includes("#include STR(ü.glsl)\n");
+        assert_eq!(diagnostics[0].range, Range::new(Position::new(0, 9), Position::new(0, 20)));
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_unsupported_extensions() {
+        let source = "#version 120\n#extension GL_ARB_gpu_shader5 : require\n#extension GL_ARB_shader_texture_lod : require\n#extension GL_EXT_gpu_shader4 : enable\n";
+
+        let supported = HashSet::from(["GL_ARB_gpu_shader5".to_string()]);
+        let diagnostics = unsupported_extensions(source, || &supported);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range, Range::new(Position::new(2, 11), Position::new(2, 36)));
+        assert_eq!(diagnostics[0].code, Some(NumberOrString::String("unsupported-extension".into())));
+        assert!(diagnostics[0].message.starts_with("GL_ARB_shader_texture_lod is required"));
+
+        // extensions are only queried when required
+        assert!(unsupported_extensions("#version 120\n", || panic!("extensions queried")).is_empty());
+
+        // a context that doesn't list its extensions can't tell which are supported
+        let unlisted = HashSet::new();
+        assert!(unsupported_extensions(source, || &unlisted).is_empty());
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_unsupported_extension_lint() {
+        let mut mockgl = MockShaderValidator::new();
+        mockgl.expect_validate().returning(|_, _| None);
+        mockgl.expect_supported_stages().returning(opengl::all_stages);
+        mockgl
+            .expect_supported_extensions()
+            .times(1)
+            .returning(|| HashSet::from(["GL_ARB_gpu_shader5".to_string()]));
+        let mut server = new_temp_server(Some(Box::new(mockgl)));
+
+        let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/21", &mut server);
+        server.build_initial_graph();
+
+        let final_path = tmp_path.join("shaders").join("final.fsh");
+        let diagnostics = server.lint(&final_path).unwrap();
+
+        let final_diagnostics = &diagnostics[&Url::from_file_path(&final_path).unwrap()];
+        assert_eq!(final_diagnostics.len(), 1);
+        assert_eq!(
+            final_diagnostics[0].code,
+            Some(NumberOrString::String("unsupported-extension".into()))
+        );
+        assert_eq!(final_diagnostics[0].range.start.line, 2);
+
+        server.endpoint.request_shutdown();
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_foreign_workspace_includes() {
+        let mut mockgl = MockShaderValidator::new();
+        mockgl.expect_supported_stages().returning(opengl::all_stages);
+        let mut server = new_temp_server(Some(Box::new(mockgl)));
+
+        let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/14", &mut server);
+        server.build_initial_graph();
+
+        let nested = tmp_path.join("shaders").join("packs").join("foo").join("shaders");
+        let diagnostics = server.lint(&nested.join("final.fsh")).unwrap();
+
+        let final_diagnostics = &diagnostics[&Url::from_file_path(nested.join("final.fsh")).unwrap()];
+        assert_eq!(final_diagnostics.len(), 1);
+        assert_eq!(
+            final_diagnostics[0].code,
+            Some(NumberOrString::String("foreign-workspace-include".into()))
+        );
+        assert_eq!(final_diagnostics[0].range, Range::new(Position::new(2, 10), Position::new(2, 26)));
+
+        // the wrongly resolved file belongs to the workspace's own pack, so it isn't flagged itself
+        let common_path = tmp_path.join("shaders").join("lib").join("common.glsl");
+        assert!(diagnostics[&Url::from_file_path(common_path).unwrap()].is_empty());
+
+        // columns count UTF-16 code units
+        let diagnostics = foreign_workspace_includes(&nested.join("final.fsh"), "#include \"/lib/ü.glsl\"\n", &tmp_path.join("shaders"));
+        assert_eq!(diagnostics[0].range, Range::new(Position::new(0, 10), Position::new(0, 21)));
+
+        server.endpoint.request_shutdown();
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_ambiguous_relative_includes() {
+        let (server, _tmp_dir, tmp_path) = server_for_sources(&[
+            ("common.glsl", "const float PI = 3.14159265;\n"),
+            (
+                "final.fsh",
+                "#version 120\n\
+                 \n\
+                 #include \"/lib/sky.glsl\"\n\
+                 \n\
+                 void main() {\n\
+                 \tgl_FragColor = skyColor();\n\
+                 }\n",
+            ),
+            (
+                "lib/common.glsl",
+                "vec3 skyTint() {\n\
+                 \treturn vec3(0.4, 0.6, 1.0);\n\
+                 }\n",
+            ),
+            (
+                "lib/sky.glsl",
+                "#include \"common.glsl\"\n\
+                 \n\
+                 vec4 skyColor() {\n\
+                 \treturn vec4(skyTint(), 1.0);\n\
+                 }\n",
+            ),
+        ]);
+
+        let final_path = tmp_path.join("shaders").join("final.fsh");
+        let sky_path = tmp_path.join("shaders").join("lib").join("sky.glsl");
+
+        let diagnostics = server.lint(&final_path).unwrap();
+
+        // both shaders/common.glsl and shaders/lib/common.glsl exist
+        let sky_diagnostics = &diagnostics[&Url::from_file_path(&sky_path).unwrap()];
+        assert_eq!(sky_diagnostics.len(), 1);
+        assert_eq!(sky_diagnostics[0].code, Some(NumberOrString::String("ambiguous-include".into())));
+        assert_eq!(sky_diagnostics[0].severity, Some(DiagnosticSeverity::INFORMATION));
+        assert_eq!(sky_diagnostics[0].range, Range::new(Position::new(0, 10), Position::new(0, 21)));
+        assert_eq!(
+            sky_diagnostics[0].message,
+            "\"common.glsl\" includes /lib/common.glsl relative to this file, though /common.glsl exists too. Consider including \"/lib/common.glsl\" to make this explicit"
+        );
+
+        // rooted includes are never ambiguous
+        assert!(diagnostics[&Url::from_file_path(&final_path).unwrap()].is_empty());
+
+        // columns count UTF-16 code units
+        std::fs::write(tmp_path.join("shaders").join("ü.glsl"), "").unwrap();
+        let diagnostics = ambiguous_relative_includes(&sky_path, "#include \"ü.glsl\"\n", &tmp_path.join("shaders"));
+        assert_eq!(diagnostics[0].range, Range::new(Position::new(0, 10), Position::new(0, 16)));
+
+        server.endpoint.request_shutdown();
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_unbalanced_delimiters() {
+        let (server, _tmp_dir, tmp_path) = server_for_sources(&[
+            (
+                "final.fsh",
+                "#version 120\n\
+                 #include \"/lib/broken.glsl\"\n\
+                 \n\
+                 // a closing } in a comment\n\
+                 void main() {\n\
+                 #ifdef FOO\n\
+                 \tif (true) {\n\
+                 #else\n\
+                 \tif (false) {\n\
+                 #endif\n\
+                 \t\tgl_FragColor = vec4(broken(1.0));\n\
+                 \t}\n\
+                 }\n",
+            ),
+            (
+                "lib/broken.glsl",
+                "float broken(float x) {\n\
+                 \tif (x > 0.0) {\n\
+                 \t\treturn x;\n\
+                 \n\
+                 \treturn -x;\n\
+                 }\n",
+            ),
+        ]);
+
+        let final_path = tmp_path.join("shaders").join("final.fsh");
+        let broken_path = tmp_path.join("shaders").join("lib").join("broken.glsl");
+
+        let diagnostics = server.lint(&final_path).unwrap();
+
+        // the braces opened in both branches of the conditional in final.fsh are balanced
+        assert!(diagnostics[&Url::from_file_path(&final_path).unwrap()].is_empty());
+
+        let broken_diagnostics = &diagnostics[&Url::from_file_path(&broken_path).unwrap()];
+        assert_eq!(broken_diagnostics.len(), 1);
+        assert_eq!(
+            broken_diagnostics[0].code,
+            Some(NumberOrString::String("unbalanced-delimiter".into()))
+        );
+        assert_eq!(broken_diagnostics[0].range, Range::new(Position::new(0, 22), Position::new(0, 23)));
+        assert_eq!(broken_diagnostics[0].message, "`{` is never closed");
+
+        server.endpoint.request_shutdown();
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_duplicate_main() {
+        let (server, _tmp_dir, tmp_path) = server_for_sources(&[
+            (
+                "final.fsh",
+                "#version 120\n\
+                 \n\
+                 #include \"/lib/extra.glsl\"\n\
+                 \n\
+                 void main() {\n\
+                 \tgl_FragColor = vec4(extra());\n\
+                 }\n",
+            ),
+            (
+                "lib/extra.glsl",
+                "float extra() {\n\
+                 \treturn 1.0;\n\
+                 }\n\
+                 \n\
+                 void main() {\n\
+                 }\n",
+            ),
+        ]);
+
+        let final_path = tmp_path.join("shaders").join("final.fsh");
+        let extra_path = tmp_path.join("shaders").join("lib").join("extra.glsl");
+
+        let diagnostics = server.lint(&final_path).unwrap();
+
+        for path in [&final_path, &extra_path] {
+            let file_diagnostics = &diagnostics[&Url::from_file_path(path).unwrap()];
+            assert_eq!(file_diagnostics.len(), 1);
+            assert_eq!(file_diagnostics[0].code, Some(NumberOrString::String("duplicate-main".into())));
+            assert_eq!(file_diagnostics[0].range, Range::new(Position::new(4, 5), Position::new(4, 9)));
+            assert!(file_diagnostics[0].message.ends_with("extra.glsl, final.fsh"));
+        }
+
+        server.endpoint.request_shutdown();
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_declared_draw_buffers() {
+        assert_eq!(declared_draw_buffers("#version 120\nvoid main() {}\n"), None);
+        assert_eq!(declared_draw_buffers("/* DRAWBUFFERS:04 */\n"), Some(vec![0, 4]));
+        assert_eq!(
+            declared_draw_buffers("/* DRAWBUFFERS:0 */\n/* RENDERTARGETS: 1, 12 */\n"),
+            Some(vec![1, 12])
+        );
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_duplicate_draw_buffers() {
+        let source = "/* DRAWBUFFERS:001 */\n/* DRAWBUFFERS:012 */\n/* RENDERTARGETS: 4, 7, 04 */\n";
+
+        let diagnostics = duplicate_draw_buffers(source);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].code, Some(NumberOrString::String("duplicate-draw-buffer".into())));
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(diagnostics[0].range, Range::new(Position::new(0, 16), Position::new(0, 17)));
+        assert!(diagnostics[0].message.starts_with("Buffer 0 is already listed"));
+        assert_eq!(diagnostics[1].range, Range::new(Position::new(2, 24), Position::new(2, 26)));
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_missing_main() {
+        let (server, _tmp_dir, tmp_path) = server_for_sources(&[
+            (
+                "final.fsh",
+                "#version 120\n\
+                 \n\
+                 #include \"/lib/util.glsl\"\n\
+                 \n\
+                 float helper() {\n\
+                 \treturn luma(vec3(1.0));\n\
+                 }\n",
+            ),
+            (
+                "lib/util.glsl",
+                "float luma(vec3 color) {\n\
+                 \treturn dot(color, vec3(0.2126, 0.7152, 0.0722));\n\
+                 }\n",
+            ),
+        ]);
+
+        let final_path = tmp_path.join("shaders").join("final.fsh");
+        let util_path = tmp_path.join("shaders").join("lib").join("util.glsl");
+
+        let diagnostics = server.lint(&final_path).unwrap();
+
+        let final_diagnostics = &diagnostics[&Url::from_file_path(&final_path).unwrap()];
+        assert_eq!(final_diagnostics.len(), 1);
+        assert_eq!(final_diagnostics[0].code, Some(NumberOrString::String("missing-main".into())));
+        assert_eq!(final_diagnostics[0].range, Range::new(Position::new(0, 0), Position::new(0, 12)));
+        assert!(diagnostics[&Url::from_file_path(&util_path).unwrap()].is_empty());
+
+        server.endpoint.request_shutdown();
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_builtin_function_redefinition() {
+        let (server, _tmp_dir, tmp_path) = server_for_sources(&[
+            (
+                "final.fsh",
+                "#version 120\n\
+                 \n\
+                 #include \"/lib/color.glsl\"\n\
+                 \n\
+                 void main() {\n\
+                 \tgl_FragColor = vec4(mix(vec3(0.0), vec3(1.0), luma(vec3(0.5))), 1.0);\n\
+                 }\n",
+            ),
+            (
+                "lib/color.glsl",
+                "vec3 mix(vec3 a, vec3 b, float t) {\n\
+                 \treturn a + (b - a) * t;\n\
+                 }\n\
+                 \n\
+                 float luma(vec3 color) {\n\
+                 \treturn dot(color, vec3(0.2126, 0.7152, 0.0722));\n\
+                 }\n",
+            ),
+        ]);
+
+        let final_path = tmp_path.join("shaders").join("final.fsh");
+        let color_path = tmp_path.join("shaders").join("lib").join("color.glsl");
+
+        let diagnostics = server.lint(&final_path).unwrap();
+
+        let color_diagnostics = &diagnostics[&Url::from_file_path(&color_path).unwrap()];
+        assert_eq!(color_diagnostics.len(), 1);
+        assert_eq!(
+            color_diagnostics[0].code,
+            Some(NumberOrString::String("builtin-redefinition".into()))
+        );
+        assert_eq!(color_diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(color_diagnostics[0].range, Range::new(Position::new(0, 5), Position::new(0, 8)));
+        assert!(color_diagnostics[0].message.contains("`mix`"));
+        assert!(diagnostics[&Url::from_file_path(&final_path).unwrap()].is_empty());
+
+        server.endpoint.request_shutdown();
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_readonly_assignments() {
+        let (server, _tmp_dir, tmp_path) = server_for_sources(&[(
+            "final.fsh",
+            "#version 120\n\
+             \n\
+             const float EXPOSURE = 1.0;\n\
+             uniform float frameTime;\n\
+             \n\
+             vec3 tonemap(const in vec3 color, in float exposure) {\n\
+             \texposure *= EXPOSURE;\n\
+             \tcolor = color * exposure;\n\
+             \treturn color;\n\
+             }\n\
+             \n\
+             void main() {\n\
+             \tEXPOSURE = 2.0;\n\
+             \tfloat frameTime = 0.0;\n\
+             \tframeTime += 1.0;\n\
+             \tvec3 c = tonemap(vec3(1.0), 1.0);\n\
+             \tc.x = 0.5;\n\
+             \tgl_FragColor = vec4(c, 1.0);\n\
+             }\n",
+        )]);
+
+        let final_path = tmp_path.join("shaders").join("final.fsh");
+
+        let diagnostics = server.lint(&final_path).unwrap();
+
+        // the `in` parameter, the local shadowing the uniform and the swizzle of a local are all writable
+        let final_diagnostics = &diagnostics[&Url::from_file_path(&final_path).unwrap()];
+        assert_eq!(final_diagnostics.len(), 2);
+        assert!(final_diagnostics
+            .iter()
+            .all(|d| d.code == Some(NumberOrString::String("readonly-assignment".into()))));
+
+        assert_eq!(final_diagnostics[0].range, Range::new(Position::new(7, 1), Position::new(7, 6)));
+        assert_eq!(
+            final_diagnostics[0].message,
+            "`color` can't be assigned to, as it is declared `const`"
+        );
+        assert_eq!(final_diagnostics[1].range, Range::new(Position::new(12, 1), Position::new(12, 9)));
+        assert_eq!(
+            final_diagnostics[1].message,
+            "`EXPOSURE` can't be assigned to, as it is declared `const`"
+        );
+
+        server.endpoint.request_shutdown();
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_mixed_fragment_outputs() {
+        let (server, _tmp_dir, tmp_path) = server_for_sources(&[
+            (
+                "final.fsh",
+                "#version 130\n\
+                 \n\
+                 #include \"/lib/output.glsl\"\n\
+                 \n\
+                 void main() {\n\
+                 \tgl_FragColor = vec4(1.0);\n\
+                 \twriteNormal(vec3(0.0, 0.0, 1.0));\n\
+                 }\n",
+            ),
+            (
+                "lib/output.glsl",
+                "void writeNormal(vec3 normal) {\n\
+                 \tgl_FragData[1] = vec4(normal * 0.5 + 0.5, 1.0);\n\
+                 }\n",
+            ),
+        ]);
+
+        let final_path = tmp_path.join("shaders").join("final.fsh");
+        let output_path = tmp_path.join("shaders").join("lib").join("output.glsl");
+
+        let diagnostics = server.lint(&final_path).unwrap();
+
+        let final_diagnostics = &diagnostics[&Url::from_file_path(&final_path).unwrap()];
+        assert_eq!(final_diagnostics.len(), 1);
+        assert_eq!(
+            final_diagnostics[0].code,
+            Some(NumberOrString::String("mixed-fragment-outputs".into()))
+        );
+        assert_eq!(final_diagnostics[0].range, Range::new(Position::new(5, 1), Position::new(5, 13)));
+        assert_eq!(
+            final_diagnostics[0].message,
+            "`gl_FragColor` can't be written in a shader that also writes `gl_FragData`, write `gl_FragData[0]` instead. \
+             Both are deprecated since GLSL 1.30 in favour of `out` variables"
+        );
+        assert!(diagnostics[&Url::from_file_path(&output_path).unwrap()].is_empty());
+
+        server.endpoint.request_shutdown();
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_legacy_storage_qualifiers() {
+        let (server, _tmp_dir, tmp_path) = server_for_fixture("./testdata/47");
+
+        let varyings_url = Url::from_file_path(tmp_path.join("shaders").join("lib").join("varyings.glsl")).unwrap();
+
+        // allowed in #version 120
+        let diagnostics = server.lint(&tmp_path.join("shaders").join("final.fsh")).unwrap();
+        assert!(diagnostics[&varyings_url].is_empty());
+
+        // removed in #version 330 core
+        let diagnostics = server.lint(&tmp_path.join("shaders").join("composite.fsh")).unwrap();
+        let varyings_diagnostics = &diagnostics[&varyings_url];
+        assert_eq!(varyings_diagnostics.len(), 1);
+        assert_eq!(
+            varyings_diagnostics[0].code,
+            Some(NumberOrString::String("legacy-storage-qualifier".into()))
+        );
+        assert_eq!(varyings_diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(varyings_diagnostics[0].range, Range::new(Position::new(0, 0), Position::new(0, 7)));
+        assert_eq!(
+            varyings_diagnostics[0].message,
+            "`varying` is not available in `#version 330 core`, declare the variable `in` or `out` instead"
+        );
+
+        // deprecated in #version 130
+        let vertex_path = tmp_path.join("shaders").join("gbuffers_basic.vsh");
+        let diagnostics = server.lint(&vertex_path).unwrap();
+        let vertex_diagnostics = &diagnostics[&Url::from_file_path(&vertex_path).unwrap()];
+        assert_eq!(vertex_diagnostics.len(), 2);
+        assert!(vertex_diagnostics.iter().all(|d| d.severity == Some(DiagnosticSeverity::WARNING)));
+        assert_eq!(vertex_diagnostics[0].range, Range::new(Position::new(2, 0), Position::new(2, 9)));
+        assert_eq!(
+            vertex_diagnostics[0].message,
+            "`attribute` is deprecated since GLSL 1.30, declare the variable `in` instead"
+        );
+        assert_eq!(vertex_diagnostics[1].range, Range::new(Position::new(3, 9), Position::new(3, 16)));
+
+        server.endpoint.request_shutdown();
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_mismatched_uniforms() {
+        let (server, _tmp_dir, tmp_path) = server_for_sources(&[
+            (
+                "composite.fsh",
+                "#version 120\n\
+                 \n\
+                 uniform sampler2DShadow shadowtex0;\n\
+                 uniform float frameTimeCounter;\n\
+                 \n\
+                 void main() {\n\
+                 \tgl_FragColor = vec4(shadow2D(shadowtex0, vec3(0.5)).r * frameTimeCounter);\n\
+                 }\n",
+            ),
+            (
+                "composite.vsh",
+                "#version 120\n\
+                 \n\
+                 uniform sampler2D shadowtex0;\n\
+                 uniform float frameTimeCounter;\n\
+                 \n\
+                 void main() {\n\
+                 \tgl_Position = ftransform();\n\
+                 }\n",
+            ),
+        ]);
+
+        let fragment_path = tmp_path.join("shaders").join("composite.fsh");
+        let vertex_path = tmp_path.join("shaders").join("composite.vsh");
+
+        let diagnostics = server.lint(&fragment_path).unwrap();
+
+        // frameTimeCounter is declared the same in both stages
+        let fragment_diagnostics = &diagnostics[&Url::from_file_path(&fragment_path).unwrap()];
+        assert_eq!(fragment_diagnostics.len(), 1);
+        assert_eq!(
+            fragment_diagnostics[0].code,
+            Some(NumberOrString::String("mismatched-uniform".into()))
+        );
+        assert_eq!(fragment_diagnostics[0].range, Range::new(Position::new(2, 0), Position::new(2, 35)));
+        assert_eq!(
+            fragment_diagnostics[0].message,
+            "`shadowtex0` is declared `sampler2DShadow` here but `sampler2D` in the vertex shader, so the program will fail to link"
+        );
+
+        let related = fragment_diagnostics[0].related_information.as_ref().unwrap();
+        assert_eq!(related.len(), 1);
+        assert_eq!(related[0].location.uri, Url::from_file_path(&vertex_path).unwrap());
+        assert_eq!(related[0].location.range, Range::new(Position::new(2, 0), Position::new(2, 29)));
+
+        // the vertex shader gets its own when it is linted
+        let diagnostics = server.lint(&vertex_path).unwrap();
+        assert_eq!(diagnostics[&Url::from_file_path(&vertex_path).unwrap()].len(), 1);
+
+        server.endpoint.request_shutdown();
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_location_collisions() {
+        let (server, _tmp_dir, tmp_path) = server_for_fixture("./testdata/38");
+
+        let final_path = tmp_path.join("shaders").join("final.fsh");
+        let outputs_path = tmp_path.join("shaders").join("lib").join("outputs.glsl");
+
+        let diagnostics = server.lint(&final_path).unwrap();
+
+        // blendFactor shares location 0 under a different blend index
+        let collisions: Vec<&Diagnostic> = diagnostics[&Url::from_file_path(&final_path).unwrap()]
+            .iter()
+            .filter(|d| d.code == Some(NumberOrString::String("location-collision".into())))
+            .collect();
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(collisions[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(collisions[0].range, Range::new(Position::new(4, 30), Position::new(4, 35)));
+        assert_eq!(collisions[0].message, "`bloom` uses output location 0, which `albedo` already uses");
+
+        let related = collisions[0].related_information.as_ref().unwrap();
+        assert_eq!(related[0].location.uri, Url::from_file_path(&outputs_path).unwrap());
+        assert_eq!(related[0].location.range, Range::new(Position::new(0, 30), Position::new(0, 36)));
+
+        server.endpoint.request_shutdown();
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_block_conflicts() {
+        let (server, _tmp_dir, tmp_path) = server_for_fixture("./testdata/44");
+
+        let fragment_path = tmp_path.join("shaders").join("composite.fsh");
+        let vertex_path = tmp_path.join("shaders").join("composite.vsh");
+        let buffers_path = tmp_path.join("shaders").join("lib").join("buffers.glsl");
+
+        let diagnostics = server.lint(&fragment_path).unwrap();
+        let with_code = |code: &str| -> Vec<Diagnostic> {
+            diagnostics[&Url::from_file_path(&fragment_path).unwrap()]
+                .iter()
+                .filter(|d| d.code == Some(NumberOrString::String(code.into())))
+                .cloned()
+                .collect()
+        };
+
+        // LightData shares CameraData's binding, in both stages
+        let collisions = with_code("block-binding-collision");
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(collisions[0].range, Range::new(Position::new(4, 36), Position::new(4, 45)));
+        assert_eq!(
+            collisions[0].message,
+            "`LightData` uses uniform binding 0, which `CameraData` already uses"
+        );
+        let related = collisions[0].related_information.as_ref().unwrap();
+        assert_eq!(related[0].location.uri, Url::from_file_path(&buffers_path).unwrap());
+        assert_eq!(related[0].location.range, Range::new(Position::new(0, 36), Position::new(0, 46)));
+
+        // the vertex shader's ExposureData has another member
+        let mismatches = with_code("mismatched-block");
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].range, Range::new(Position::new(8, 36), Position::new(8, 48)));
+        let related = mismatches[0].related_information.as_ref().unwrap();
+        assert_eq!(related[0].location.uri, Url::from_file_path(&vertex_path).unwrap());
+        assert_eq!(related[0].location.range, Range::new(Position::new(8, 36), Position::new(8, 48)));
+
+        // CameraData is declared the same in both stages
+        assert!(diagnostics
+            .get(&Url::from_file_path(&buffers_path).unwrap())
+            .map_or(true, |d| d.is_empty()));
+
+        server.endpoint.request_shutdown();
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_macro_redefinitions() {
+        let (server, _tmp_dir, tmp_path) = server_for_fixture("./testdata/43");
+
+        let final_path = tmp_path.join("shaders").join("final.fsh");
+        let settings_path = tmp_path.join("shaders").join("lib").join("settings.glsl");
+        let defaults_path = tmp_path.join("shaders").join("lib").join("defaults.glsl");
+
+        let diagnostics = server.lint(&final_path).unwrap();
+
+        // the defaults are guarded by `#ifndef` and `#if !defined`, so aren't compiled after the definitions above them
+        assert!(diagnostics
+            .get(&Url::from_file_path(&defaults_path).unwrap())
+            .map_or(true, |diagnostics| diagnostics.is_empty()));
+
+        // BLOOM_STRENGTH expands the same and SAMPLES is defined in exclusive branches, so only SHADOW_RES is flagged
+        let redefinitions: Vec<&Diagnostic> = diagnostics[&Url::from_file_path(&final_path).unwrap()]
+            .iter()
+            .filter(|d| d.code == Some(NumberOrString::String("macro-redefinition".into())))
+            .collect();
+        assert_eq!(redefinitions.len(), 1);
+        assert_eq!(redefinitions[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(redefinitions[0].range, Range::new(Position::new(3, 8), Position::new(3, 18)));
+        assert_eq!(
+            redefinitions[0].message,
+            "`SHADOW_RES` is redefined as `2048`, but was already defined as `1024` on line 1 of settings.glsl"
+        );
+
+        let related = redefinitions[0].related_information.as_ref().unwrap();
+        assert_eq!(related[0].location.uri, Url::from_file_path(&settings_path).unwrap());
+        assert_eq!(related[0].location.range, Range::new(Position::new(0, 8), Position::new(0, 18)));
+
+        server.endpoint.request_shutdown();
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_macro_redefinition_continued_name() {
+        let mut graph = CachedStableGraph::new();
+        let idx = graph.add_node(&PathBuf::from("/pack/shaders/final.fsh"));
+        let mut source_mapper = SourceMapper::new(1);
+        source_mapper.get_num(idx);
+
+        // the names are on the lines continuing the directives
+        let merged = "#version 120\n#define \\\n    FOO 1\n#define \\\nFOO 2\n";
+        let diagnostics = macro_redefinitions(merged, &source_mapper, &graph);
+
+        let redefinitions = &diagnostics[&Url::from_file_path("/pack/shaders/final.fsh").unwrap()];
+        assert_eq!(redefinitions.len(), 1);
+        assert_eq!(redefinitions[0].range, Range::new(Position::new(4, 0), Position::new(4, 3)));
+        let related = redefinitions[0].related_information.as_ref().unwrap();
+        assert_eq!(related[0].location.range, Range::new(Position::new(2, 4), Position::new(2, 7)));
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_undeclared_draw_buffers() {
+        let (server, _tmp_dir, tmp_path) = server_for_sources(&[
+            (
+                "final.fsh",
+                "#version 120\n\
+                 \n\
+                 /* DRAWBUFFERS:04 */\n\
+                 \n\
+                 #include \"/lib/write.glsl\"\n\
+                 \n\
+                 void main() {\n\
+                 \tgl_FragData[0] = vec4(1.0);\n\
+                 \tgl_FragData[1] = vec4(0.5);\n\
+                 \twriteExtra();\n\
+                 }\n",
+            ),
+            (
+                "lib/write.glsl",
+                "void writeExtra() {\n\
+                 \t/* ü */ gl_FragData[2] = vec4(0.0);\n\
+                 }\n",
+            ),
+        ]);
+
+        let final_path = tmp_path.join("shaders").join("final.fsh");
+        let write_path = tmp_path.join("shaders").join("lib").join("write.glsl");
+
+        let diagnostics = server.lint(&final_path).unwrap();
+
+        assert!(diagnostics[&Url::from_file_path(&final_path).unwrap()].is_empty());
+
+        let write_diagnostics = &diagnostics[&Url::from_file_path(&write_path).unwrap()];
+        assert_eq!(write_diagnostics.len(), 1);
+        assert_eq!(
+            write_diagnostics[0].code,
+            Some(NumberOrString::String("undeclared-draw-buffer".into()))
+        );
+        // the comment before the write has a character taking two bytes but one UTF-16 code unit
+        assert_eq!(write_diagnostics[0].range, Range::new(Position::new(1, 9), Position::new(1, 23)));
+        assert!(write_diagnostics[0].message.starts_with("`gl_FragData[2]` writes to output 2"));
+
+        server.endpoint.request_shutdown();
+    }
+
+    // lints the composite program of the given stage, which calls a function that discards from lib/clip.glsl
+    fn lint_discard(ext: &str) -> Vec<Diagnostic> {
+        let (server, _tmp_dir, tmp_path) = server_for_fixture("./testdata/24");
+
+        let shader_path = tmp_path.join("shaders").join(format!("composite.{}", ext));
+        let clip_path = tmp_path.join("shaders").join("lib").join("clip.glsl");
+
+        let diagnostics = server.lint(&shader_path).unwrap();
+        assert!(diagnostics[&Url::from_file_path(&shader_path).unwrap()].is_empty());
+
+        server.endpoint.request_shutdown();
+        diagnostics[&Url::from_file_path(&clip_path).unwrap()].clone()
+    }
+
+    fn assert_discard_flagged(diagnostics: &[Diagnostic], stage: &str) {
+        // the discard under `#ifdef FRAGMENT_SHADER` isn't flagged
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, Some(NumberOrString::String("discard-outside-fragment".into())));
+        assert_eq!(diagnostics[0].range, Range::new(Position::new(1, 18), Position::new(1, 25)));
+        assert_eq!(
+            diagnostics[0].message,
+            format!("`discard` is only allowed in fragment shaders, not in {} shaders", stage)
+        );
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_discard_in_fragment() {
+        assert!(lint_discard("fsh").is_empty());
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_discard_in_vertex() {
+        assert_discard_flagged(&lint_discard("vsh"), "vertex");
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_discard_in_geometry() {
+        assert_discard_flagged(&lint_discard("gsh"), "geometry");
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_discard_in_compute() {
+        assert_discard_flagged(&lint_discard("csh"), "compute");
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_unterminated_block_comment() {
+        assert_eq!(unterminated_block_comment("/* a */ b /* c\nd */ e"), None);
+        assert_eq!(
+            unterminated_block_comment("// /* not a comment\na /* b\n*/ /*"),
+            Some(Position::new(2, 3))
+        );
+
+        let mut mockgl = MockShaderValidator::new();
+        mockgl.expect_validate().returning(|_, _| None);
+        mockgl.expect_supported_stages().returning(opengl::all_stages);
+        let mut server = new_temp_server(Some(Box::new(mockgl)));
+
+        let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/25", &mut server);
+        server.build_initial_graph();
+
+        // the include inside the comment is not tracked, so the missing file it names isn't reported
+        let final_path = tmp_path.join("shaders").join("final.fsh");
+        let final_node = server.graph.borrow_mut().find_node(&final_path).unwrap();
+        assert_eq!(server.graph.borrow().child_node_indexes(final_node).count(), 0);
+
+        let diagnostics = server.lint(&final_path).unwrap();
+        let final_diagnostics = &diagnostics[&Url::from_file_path(&final_path).unwrap()];
+        assert_eq!(final_diagnostics.len(), 1);
+        assert_eq!(
+            final_diagnostics[0].code,
+            Some(NumberOrString::String("unterminated-comment".into()))
+        );
+        assert_eq!(final_diagnostics[0].range, Range::new(Position::new(2, 38), Position::new(2, 40)));
+
+        server.endpoint.request_shutdown();
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_count_elements() {
+        assert_eq!(count_elements("()"), Some(0));
+        assert_eq!(count_elements("(0.1, vec2(1.0, 2.0).x, m[0][1])"), Some(3));
+        assert_eq!(count_elements("{ 1, 2, 3, }"), Some(3));
+        assert_eq!(count_elements("(1, 2"), None);
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_array_initializer_sizes() {
+        let (server, _tmp_dir, tmp_path) = server_for_fixture("./testdata/18");
+
+        let final_path = tmp_path.join("shaders").join("final.fsh");
+        let diagnostics = server.lint(&final_path).unwrap();
+
+        let final_diagnostics = &diagnostics[&Url::from_file_path(&final_path).unwrap()];
+        assert_eq!(final_diagnostics.len(), 1);
+        assert_eq!(
+            final_diagnostics[0].code,
+            Some(NumberOrString::String("array-size-mismatch".into()))
+        );
+        assert_eq!(final_diagnostics[0].range, Range::new(Position::new(2, 25), Position::new(2, 47)));
+        assert_eq!(
+            final_diagnostics[0].message,
+            "`weights` is declared with 4 element(s), but initialized with 3"
+        );
+
+        server.endpoint.request_shutdown();
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_integer_divisions() {
+        let (server, _tmp_dir, tmp_path) = server_for_sources(&[(
+            "final.fsh",
+            "#version 120\n\
+             \n\
+             const float oneHalf = 1 / 2;\n\
+             const float third = 1.0 / 3.0;\n\
+             const int quarter = 1 / 4;\n\
+             \n\
+             void main() {\n\
+             \tfloat ratio = (3 / 4) * 2.0;\n\
+             \tgl_FragColor = vec4(oneHalf, third, ratio, float(quarter));\n\
+             }\n",
+        )]);
+
+        let final_path = tmp_path.join("shaders").join("final.fsh");
+        let final_url = Url::from_file_path(&final_path).unwrap();
+
+        // float literals and integer variables are left alone
+        let diagnostics = server.lint(&final_path).unwrap();
+        let final_diagnostics = &diagnostics[&final_url];
+        assert_eq!(final_diagnostics.len(), 2);
+        assert!(final_diagnostics.iter().all(|d| d.severity == Some(DiagnosticSeverity::HINT)));
+        assert_eq!(final_diagnostics[0].code, Some(NumberOrString::String("integer-division".into())));
+        assert_eq!(final_diagnostics[0].range, Range::new(Position::new(2, 22), Position::new(2, 27)));
+        assert_eq!(
+            final_diagnostics[0].message,
+            "`1 / 2` is an integer division, so its result is truncated before it is converted to `float`"
+        );
+        assert_eq!(final_diagnostics[1].range, Range::new(Position::new(7, 16), Position::new(7, 21)));
+
+        server.config.borrow_mut().integer_division_hints = false;
+        let diagnostics = server.lint(&final_path).unwrap();
+        assert!(diagnostics[&final_url].is_empty());
+
+        server.endpoint.request_shutdown();
+    }
+}
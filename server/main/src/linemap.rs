@@ -21,6 +21,11 @@ impl LineMap {
     }
 }
 
+/// Converts a byte offset into a line to the column LSP expects, which counts UTF-16 code units.
+pub fn utf16_column(line: &str, byte_offset: usize) -> usize {
+    line[..byte_offset].encode_utf16().count()
+}
+
 #[cfg(test)]
 mod test {
     use rust_lsp::lsp_types::Position;
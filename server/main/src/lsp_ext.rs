@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use rust_lsp::lsp_types::notification::Notification;
 use serde::{Deserialize, Serialize};
 
@@ -14,3 +16,20 @@ pub struct StatusParams {
     pub message: Option<String>,
     pub icon: Option<String>,
 }
+
+/// The result of the `includeGraph` command, for clients to populate views of the include graph on demand.
+#[derive(Debug, Default, Eq, PartialEq, Clone, Deserialize, Serialize)]
+pub struct IncludeGraphResult {
+    /// Paths of all files in the graph, sorted.
+    pub nodes: Vec<PathBuf>,
+    /// One edge per `#include`, sorted by parent and line.
+    pub edges: Vec<IncludeGraphEdge>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Deserialize, Serialize)]
+pub struct IncludeGraphEdge {
+    pub parent: PathBuf,
+    pub child: PathBuf,
+    /// The 0-indexed line of the `#include` in the parent.
+    pub line: usize,
+}
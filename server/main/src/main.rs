@@ -7,9 +7,7 @@ use rust_lsp::lsp::*;
 use rust_lsp::lsp_types::{notification::*, *};
 
 use petgraph::stable_graph::NodeIndex;
-use path_slash::PathExt;
 
-use serde::Deserialize;
 use serde_json::{from_value, Value};
 
 use tree_sitter::Parser;
@@ -17,24 +15,24 @@ use url_norm::FromUrl;
 
 use walkdir::WalkDir;
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::convert::TryFrom;
 use std::fmt::{Debug, Display, Formatter};
 use std::fs;
-use std::io::{stdin, stdout, BufRead, BufReader};
+use std::io::{stdin, stdout};
 use std::iter::{Extend, FromIterator};
 use std::rc::Rc;
 use std::str::FromStr;
 
 use std::{
-    cell::RefCell,
-    path::{Path, PathBuf},
+    cell::{OnceCell, RefCell},
+    path::{Component, Path, PathBuf},
 };
 
 use slog::Level;
 use slog_scope::{debug, error, info, warn};
 
-use path_slash::PathBufExt;
+use path_slash::{PathBufExt, PathExt};
 
 use anyhow::{anyhow, Result};
 
@@ -42,96 +40,45 @@ use regex::Regex;
 
 use lazy_static::lazy_static;
 
+mod builtins;
 mod commands;
 mod configuration;
 mod consts;
 mod dfs;
 mod diagnostics_parser;
+mod file_cache;
 mod graph;
+mod include_guard;
+mod internal_diagnostics;
 mod linemap;
 mod lsp_ext;
 mod merge_views;
 mod navigation;
 mod opengl;
+mod shader_interface;
+mod shader_options;
 mod source_mapper;
+mod text_encoding;
+mod toplevel;
 mod url_norm;
+mod vendor_cache;
 
 #[cfg(test)]
 mod test;
 
-pub fn is_top_level(path: &Path) -> bool {
-    let path = path.to_slash().unwrap();
-    if !RE_WORLD_FOLDER.is_match(&path) {
-        return false;
-    }
-    let parts: Vec<&str> = path.split("/").collect();
-    let len = parts.len();
-    (len == 3 || len == 2) && TOPLEVEL_FILES.contains(parts[len - 1])
-}
-
 lazy_static! {
     static ref RE_INCLUDE: Regex = Regex::new(r#"^(?:\s)*?(?:#include) "(.+)"\r?"#).unwrap();
-    static ref RE_WORLD_FOLDER: Regex = Regex::new(r#"^shaders(/world-?\d+)?"#).unwrap();
-    static ref TOPLEVEL_FILES: HashSet<String> = {
-        let mut set = HashSet::with_capacity(1716);
-        for ext in ["fsh", "vsh", "gsh", "csh"] {
-            set.insert(format!("composite.{}", ext));
-            set.insert(format!("deferred.{}", ext));
-            set.insert(format!("prepare.{}", ext));
-            set.insert(format!("shadowcomp.{}", ext));
-            for i in 1..=99 {
-                set.insert(format!("composite{}.{}", i, ext));
-                set.insert(format!("deferred{}.{}", i, ext));
-                set.insert(format!("prepare{}.{}", i, ext));
-                set.insert(format!("shadowcomp{}.{}", i, ext));
-            }
-            set.insert(format!("composite_pre.{}", ext));
-            set.insert(format!("deferred_pre.{}", ext));
-            set.insert(format!("final.{}", ext));
-            set.insert(format!("gbuffers_armor_glint.{}", ext));
-            set.insert(format!("gbuffers_basic.{}", ext));
-            set.insert(format!("gbuffers_beaconbeam.{}", ext));
-            set.insert(format!("gbuffers_block.{}", ext));
-            set.insert(format!("gbuffers_clouds.{}", ext));
-            set.insert(format!("gbuffers_damagedblock.{}", ext));
-            set.insert(format!("gbuffers_entities.{}", ext));
-            set.insert(format!("gbuffers_entities_glowing.{}", ext));
-            set.insert(format!("gbuffers_hand.{}", ext));
-            set.insert(format!("gbuffers_hand_water.{}", ext));
-            set.insert(format!("gbuffers_item.{}", ext));
-            set.insert(format!("gbuffers_line.{}", ext));
-            set.insert(format!("gbuffers_skybasic.{}", ext));
-            set.insert(format!("gbuffers_skytextured.{}", ext));
-            set.insert(format!("gbuffers_spidereyes.{}", ext));
-            set.insert(format!("gbuffers_terrain.{}", ext));
-            set.insert(format!("gbuffers_terrain_cutout.{}", ext));
-            set.insert(format!("gbuffers_terrain_cutout_mip.{}", ext));
-            set.insert(format!("gbuffers_terrain_solid.{}", ext));
-            set.insert(format!("gbuffers_textured.{}", ext));
-            set.insert(format!("gbuffers_textured_lit.{}", ext));
-            set.insert(format!("gbuffers_water.{}", ext));
-            set.insert(format!("gbuffers_weather.{}", ext));
-            set.insert(format!("shadow.{}", ext));
-            set.insert(format!("shadow_cutout.{}", ext));
-            set.insert(format!("shadow_solid.{}", ext));
-        }
-        let base_char_num = 'a' as u8;
-        for suffix_num in 0u8..=25u8 {
-            let suffix_char = (base_char_num + suffix_num) as char;
-            set.insert(format!("composite_{}.csh", suffix_char));
-            set.insert(format!("deferred_{}.csh", suffix_char));
-            set.insert(format!("prepare_{}.csh", suffix_char));
-            set.insert(format!("shadowcomp_{}.csh", suffix_char));
-            for i in 1..=99 {
-                let total_suffix = format!("{}_{}", i, suffix_char);
-                set.insert(format!("composite{}.csh", total_suffix));
-                set.insert(format!("deferred{}.csh", total_suffix));
-                set.insert(format!("prepare{}.csh", total_suffix));
-                set.insert(format!("shadowcomp{}.csh", total_suffix));
-            }
-        }
-        set
+}
+
+// the byte range of the path within the quotes of an include, leaving out the whitespace surrounding it and any
+// suffix starting at `suffix_separator`, e.g. the `?v=2` of `common.glsl?v=2`
+fn include_path_range(quoted: &str, suffix_separator: &str) -> std::ops::Range<usize> {
+    let path = match suffix_separator {
+        "" => quoted,
+        separator => quoted.split(separator).next().unwrap(),
     };
+    let start = path.len() - path.trim_start().len();
+    start..start + path.trim().len()
 }
 
 fn main() {
@@ -144,36 +91,28 @@ fn main() {
     let mut parser = Parser::new();
     parser.set_language(tree_sitter_glsl::language()).unwrap();
 
+    let vendor_cache = vendor_cache::VendorCache::in_platform_cache_dir();
+
     let mut langserver = MinecraftShaderLanguageServer {
         endpoint: endpoint_output.clone(),
         graph: Rc::new(RefCell::new(cache_graph)),
         root: "".into(),
+        config: Rc::new(RefCell::new(configuration::Configuration::default())),
+        toplevel_files: Rc::new(RefCell::new(toplevel::TopLevelFiles::default())),
+        diagnostics: Rc::new(RefCell::new(HashMap::new())),
+        notified_unsupported_stages: Rc::new(RefCell::new(HashSet::new())),
+        unresolved_includes: Rc::new(RefCell::new(HashMap::new())),
+        open_files: Rc::new(RefCell::new(HashSet::new())),
+        file_cache: Rc::new(RefCell::new(file_cache::FileCache::default())),
+        diagnostics_regex: Rc::new(RefCell::new(None)),
         command_provider: None,
-        opengl_context: Rc::new(opengl::OpenGlContext::new()),
+        opengl_context: Rc::new(opengl::LazyOpenGlContext::new(vendor_cache.clone())),
+        vendor_cache,
         tree_sitter: Rc::new(RefCell::new(parser)),
         log_guard: Some(guard),
     };
 
-    langserver.command_provider = Some(commands::CustomCommandProvider::new(vec![
-        (
-            "graphDot",
-            Box::new(commands::graph_dot::GraphDotCommand {
-                graph: langserver.graph.clone(),
-            }),
-        ),
-        (
-            "virtualMerge",
-            Box::new(commands::merged_includes::VirtualMergedDocument {
-                graph: langserver.graph.clone(),
-            }),
-        ),
-        (
-            "parseTree",
-            Box::new(commands::parse_tree::TreeSitterSExpr {
-                tree_sitter: langserver.tree_sitter.clone(),
-            }),
-        ),
-    ]));
+    langserver.register_commands();
 
     LSPEndpoint::run_server_from_input(&mut stdin().lock(), endpoint_output, langserver);
 }
@@ -182,19 +121,70 @@ pub struct MinecraftShaderLanguageServer {
     endpoint: Endpoint,
     graph: Rc<RefCell<graph::CachedStableGraph>>,
     root: PathBuf,
+    config: Rc<RefCell<configuration::Configuration>>,
+    toplevel_files: Rc<RefCell<toplevel::TopLevelFiles>>,
+    // the diagnostics last published for each file that currently has any
+    diagnostics: Rc<RefCell<HashMap<Url, Vec<Diagnostic>>>>,
     command_provider: Option<commands::CustomCommandProvider>,
     opengl_context: Rc<dyn opengl::ShaderValidator>,
+    // the vendor detected on a previous run, which `opengl_context` answers with while it has one
+    vendor_cache: vendor_cache::VendorCache,
+    // shader types the user has already been told can't be validated by the OpenGL context
+    notified_unsupported_stages: Rc<RefCell<HashSet<gl::types::GLenum>>>,
+    // included files that don't exist (yet), with the files including them
+    unresolved_includes: Rc<RefCell<HashMap<PathBuf, HashSet<PathBuf>>>>,
+    // the files the client has open, whose changes are picked up when they are saved rather than when they are watched
+    open_files: Rc<RefCell<HashSet<PathBuf>>>,
+    // the contents of the files read so far, shared between scanning them for includes and merging them
+    file_cache: Rc<RefCell<file_cache::FileCache>>,
+    // the configured regex for compile log lines, if it is set and valid
+    diagnostics_regex: Rc<RefCell<Option<Regex>>>,
     tree_sitter: Rc<RefCell<Parser>>,
     log_guard: Option<slog_scope::GlobalLoggerGuard>,
 }
 
+// the maximum number of links followed when resolving a single path component
+const MAX_SYMLINK_HOPS: usize = 40;
+
+/// Returns the root of the shader pack for the workspace opened at `root`. The pack's `shaders` directory is
+/// sometimes opened itself rather than the pack, in which case the pack is its parent.
+fn pack_root(root: PathBuf) -> PathBuf {
+    if root.file_name().map_or(true, |name| name != "shaders") || root.join("shaders").is_dir() {
+        return root;
+    }
+    match root.parent() {
+        Some(parent) => parent.to_path_buf(),
+        None => root,
+    }
+}
+
+/// Lexically resolves `.` and `..` components, without touching the filesystem as included
+/// files may not exist (yet). On Windows, drive letters are uppercased, as clients differ on
+/// their case. Every path used as a key, in the include graph or the file cache, goes through
+/// this so that differently spelled paths to the same file find the same entry.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            #[cfg(target_family = "windows")]
+            Component::Prefix(prefix) => normalized.push(prefix.as_os_str().to_string_lossy().to_uppercase()),
+            c => normalized.push(c),
+        }
+    }
+    normalized
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct IncludePosition {
     // the 0-indexed line on which the include lives.
     line: usize,
-    // the 0-indexed char offset defining the start of the include path string.
+    // the 0-indexed UTF-16 column defining the start of the include path string.
     start: usize,
-    // the 0-indexed char offset defining the end of the include path string.
+    // the 0-indexed UTF-16 column defining the end of the include path string.
     end: usize,
 }
 
@@ -210,7 +200,7 @@ impl Display for IncludePosition {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TreeType {
     Fragment,
     Vertex,
@@ -218,6 +208,19 @@ pub enum TreeType {
     Compute,
 }
 
+impl TreeType {
+    /// The stage of a shader file, by its extension, if it is the file of a stage.
+    pub fn from_path(path: &Path) -> Option<TreeType> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("fsh") => Some(TreeType::Fragment),
+            Some("vsh") => Some(TreeType::Vertex),
+            Some("gsh") => Some(TreeType::Geometry),
+            Some("csh") => Some(TreeType::Compute),
+            _ => None,
+        }
+    }
+}
+
 impl MinecraftShaderLanguageServer {
     pub fn error_not_available<DATA>(data: DATA) -> MethodError<DATA> {
         let msg = "Functionality not implemented.".to_string();
@@ -228,11 +231,113 @@ impl MinecraftShaderLanguageServer {
         }
     }
 
+    // registers the commands run through `workspace/executeCommand`
+    fn register_commands(&mut self) {
+        self.command_provider = Some(commands::CustomCommandProvider::new(vec![
+            (
+                "graphDot",
+                Box::new(commands::graph_dot::GraphDotCommand { graph: self.graph.clone() }),
+            ),
+            (
+                "virtualMerge",
+                Box::new(commands::merged_includes::VirtualMergedDocument {
+                    graph: self.graph.clone(),
+                    config: self.config.clone(),
+                }),
+            ),
+            (
+                "parseTree",
+                Box::new(commands::parse_tree::TreeSitterSExpr {
+                    tree_sitter: self.tree_sitter.clone(),
+                    config: self.config.clone(),
+                }),
+            ),
+            (
+                "shaderInterface",
+                Box::new(commands::shader_interface::ShaderInterfaceCommand {
+                    graph: self.graph.clone(),
+                    tree_sitter: self.tree_sitter.clone(),
+                    config: self.config.clone(),
+                }),
+            ),
+            (
+                "checkLineMap",
+                Box::new(commands::check_line_map::CheckLineMapCommand {
+                    graph: self.graph.clone(),
+                    config: self.config.clone(),
+                }),
+            ),
+            (
+                "includeChain",
+                Box::new(commands::include_chain::IncludeChainCommand { graph: self.graph.clone() }),
+            ),
+            (
+                "includeGraph",
+                Box::new(commands::include_graph::IncludeGraphCommand { graph: self.graph.clone() }),
+            ),
+            (
+                "resolveInclude",
+                Box::new(commands::resolve_include::ResolveIncludeCommand { graph: self.graph.clone() }),
+            ),
+            (
+                "symbolIndex",
+                Box::new(commands::symbol_index::SymbolIndexCommand {
+                    graph: self.graph.clone(),
+                    tree_sitter: self.tree_sitter.clone(),
+                    config: self.config.clone(),
+                }),
+            ),
+            (
+                "rawCompileLog",
+                Box::new(commands::raw_compile_log::RawCompileLogCommand {
+                    graph: self.graph.clone(),
+                    config: self.config.clone(),
+                    opengl_context: self.opengl_context.clone(),
+                }),
+            ),
+            ("clearDiagnostics", Box::new(commands::clear_diagnostics::ClearDiagnosticsCommand)),
+            ("invalidate", Box::new(commands::invalidate::InvalidateCommand)),
+            ("validateAll", Box::new(commands::validate_all::ValidateAllCommand)),
+            (
+                "reloadShaderNames",
+                Box::new(commands::reload_shader_names::ReloadShaderNamesCommand),
+            ),
+            ("mergeAll", Box::new(commands::merge_all::MergeAllCommand)),
+        ]));
+    }
+
     fn build_initial_graph(&self) {
         info!("generating graph for current root"; "root" => self.root.to_str().unwrap());
 
+        if let Some(hint) = self.misplaced_shaders_hint() {
+            warn!("found shader files outside of a shaders directory"; "root" => self.root.to_str().unwrap());
+            self.endpoint
+                .send_notification(
+                    ShowMessage::METHOD,
+                    ShowMessageParams {
+                        typ: MessageType::WARNING,
+                        message: hint,
+                    },
+                )
+                .expect("failed to send popup/show message notification");
+        }
+
+        // iterate all valid found files, search for includes, add a node into the graph for each
+        // file and add a file->includes KV into the map
+        for path in self.shader_files() {
+            self.add_file_and_includes_to_graph(&path);
+        }
+
+        info!("finished building project include graph");
+    }
+
+    // all the shader source files under the root's shaders directory
+    fn shader_files(&self) -> Vec<PathBuf> {
+        // the walk's root is always followed, so a `shaders` directory that is a symlink to a pack
+        // checked out elsewhere is walked too, with its files keeping their paths under the root.
         // filter directories and files not ending in any of the 3 extensions
-        WalkDir::new(&self.root)
+        WalkDir::new(self.root.join("shaders"))
+            .follow_links(self.config.borrow().follow_symlinks)
             .into_iter()
             .filter_map(|entry| {
                 if entry.is_err() {
@@ -255,26 +360,75 @@ impl MinecraftShaderLanguageServer {
                     return None;
                 }
 
-                Some(entry.into_path())
+                Some(self.resolve_symlinks(entry.path()))
             })
-            .for_each(|path| {
-                // iterate all valid found files, search for includes, add a node into the graph for each
-                // file and add a file->includes KV into the map
-                self.add_file_and_includes_to_graph(&path);
-            });
-
-        info!("finished building project include graph");
+            .collect()
     }
 
-    fn add_file_and_includes_to_graph(&self, path: &Path) {
-        let includes = self.find_includes(path);
+    /// Describes the shaders that would be picked up under the root, for `dryRun` initializations that
+    /// report what was discovered instead of building the include graph.
+    fn dry_run_report(&self) -> String {
+        let shaders_dir = self.root.join("shaders");
+        if !shaders_dir.is_dir() {
+            return format!(
+                "Dry run: no shaders directory found in {}, shader files are expected under {}",
+                self.root.to_str().unwrap(),
+                shaders_dir.to_str().unwrap()
+            );
+        }
+
+        let files = self.shader_files();
+        let toplevel_files = self.toplevel_files.borrow();
+        let top_level = files
+            .iter()
+            .filter(|path| {
+                path.strip_prefix(&self.root)
+                    .map_or(false, |relative| toplevel_files.is_top_level(relative))
+            })
+            .count();
+
+        format!(
+            "Dry run: found shaders directory {} with {} shader file(s), {} of them top-level",
+            shaders_dir.to_str().unwrap(),
+            files.len(),
+            top_level
+        )
+    }
 
-        let idx = self.graph.borrow_mut().add_node(path);
+    // a hint for packs whose programs sit directly in the root rather than in its shaders directory, which is a
+    // common mistake when starting out. None if there is a shaders directory, or no such programs
+    fn misplaced_shaders_hint(&self) -> Option<String> {
+        if self.root.join("shaders").is_dir() {
+            return None;
+        }
 
-        debug!("adding includes for new file"; "file" => path.to_str().unwrap(), "includes" => format!("{:?}", includes));
-        for include in includes {
-            self.add_include(include, idx);
+        let toplevel_files = self.toplevel_files.borrow();
+        let mut misplaced: Vec<String> = fs::read_dir(&self.root)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_file())
+            .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+            .filter(|name| toplevel_files.is_top_level(&Path::new("shaders").join(name)))
+            .collect();
+        if misplaced.is_empty() {
+            return None;
         }
+        misplaced.sort();
+
+        Some(format!(
+            "Found shader programs directly in {} ({}), but shaders are only loaded from its shaders directory. Move them into {}",
+            self.root.to_str().unwrap(),
+            misplaced.join(", "),
+            self.root.join("shaders").to_str().unwrap()
+        ))
+    }
+
+    fn add_file_and_includes_to_graph(&self, path: &Path) {
+        // the file may already be in the graph, either as the include of a file that was scanned before it
+        // or because it was scanned before, so its includes are diffed against the edges it already has
+        // rather than added again
+        self.graph.borrow_mut().add_node(path);
+        self.update_includes(path);
     }
 
     fn add_include(&self, include: (PathBuf, IncludePosition), node: NodeIndex) {
@@ -285,34 +439,102 @@ impl MinecraftShaderLanguageServer {
     pub fn find_includes(&self, file: &Path) -> Vec<(PathBuf, IncludePosition)> {
         let mut includes = Vec::default();
 
-        let buf = BufReader::new(std::fs::File::open(file).unwrap());
-        buf.lines()
+        // lines that can't be decoded can't be includes anyway
+        let source = text_encoding::decode_lossy(&self.file_cache.borrow_mut().read(file).unwrap(), &self.config.borrow().encoding);
+        // includes after a block comment that is never closed are commented out
+        let last_line = match internal_diagnostics::unterminated_block_comment(&source) {
+            Some(opening) => opening.line as usize,
+            None => usize::MAX,
+        };
+        let max_line_length = self.config.borrow().max_include_line_length;
+        let suffix_separator = self.config.borrow().include_suffix_separator.clone();
+        source
+            .lines()
             .enumerate()
-            .filter_map(|line| match line.1 {
-                Ok(t) => Some((line.0, t)),
-                Err(_e) => None,
+            .take_while(|line| line.0 <= last_line)
+            // e.g. minified or generated code, which isn't worth running the regex over. It is still merged as is
+            .filter(|line| {
+                if max_line_length == 0 || line.1.len() <= max_line_length {
+                    return true;
+                }
+                debug!("skipping long line when scanning for includes";
+                    "path" => file.to_str().unwrap(), "line" => line.0, "length" => line.1.len());
+                false
             })
-            .filter(|line| RE_INCLUDE.is_match(line.1.as_str()))
+            .filter(|line| RE_INCLUDE.is_match(line.1))
             .for_each(|line| {
-                let cap = RE_INCLUDE.captures(line.1.as_str()).unwrap().get(1).unwrap();
-
-                let start = cap.start();
-                let end = cap.end();
-                let mut path: String = cap.as_str().into();
-
-                let full_include = if path.starts_with('/') {
+                let cap = RE_INCLUDE.captures(line.1).unwrap().get(1).unwrap();
+
+                // whitespace surrounding the path inside the quotes, and any suffix, are ignored
+                let range = include_path_range(cap.as_str(), &suffix_separator);
+                let mut path: String = cap.as_str()[range.clone()].into();
+                // LSP columns count UTF-16 code units rather than bytes
+                let start = linemap::utf16_column(line.1, cap.start() + range.start);
+                let end = linemap::utf16_column(line.1, cap.start() + range.end);
+
+                // includes starting with a configured alias, e.g. `@core/math.glsl`, are resolved in the directory it stands for
+                let aliased = path.split_once('/').and_then(|(prefix, rest)| {
+                    let config = self.config.borrow();
+                    let dir = config.include_aliases.get(prefix)?;
+                    Some(PathBuf::from_slash(dir).join(PathBuf::from_slash(rest)))
+                });
+
+                let full_include = if let Some(aliased) = aliased {
+                    self.root.join("shaders").join(aliased)
+                } else if path.starts_with('/') {
                     path = path.strip_prefix('/').unwrap().to_string();
                     self.root.join("shaders").join(PathBuf::from_slash(&path))
                 } else {
-                    file.parent().unwrap().join(PathBuf::from_slash(&path))
+                    let relative = normalize_path(&file.parent().unwrap().join(PathBuf::from_slash(&path)));
+                    // world folders fall back to the base pack for the files they don't override
+                    match toplevel::base_pack_path(&relative, &self.root.join("shaders")) {
+                        Some(base) if !relative.is_file() && base.is_file() => base,
+                        _ => relative,
+                    }
                 };
 
+                // dimension overrides commonly include files of the base pack via `..`, which must
+                // resolve to the same node as the base pack's own includes of that file
+                let full_include = self.resolve_symlinks(&full_include);
                 includes.push((full_include, IncludePosition { line: line.0, start, end }));
             });
 
         includes
     }
 
+    /// With `mcglsl.followSymlinks` set, replaces symlinks in the part of `path` below the workspace's
+    /// `shaders` directory with their targets, so that a file has the same graph node whether it is reached
+    /// through a link or not. Links may point outside of the workspace root, e.g. to a library shared between
+    /// packs. The `shaders` directory itself is left as is even if it is a link, as the client refers to files
+    /// through it. The path is normalized either way.
+    pub fn resolve_symlinks(&self, path: &Path) -> PathBuf {
+        let path = &normalize_path(path);
+        if !self.config.borrow().follow_symlinks {
+            return path.to_path_buf();
+        }
+
+        let shaders_root = self.root.join("shaders");
+        let relative = match path.strip_prefix(&shaders_root) {
+            Ok(relative) => relative,
+            Err(_) => return path.to_path_buf(),
+        };
+
+        let mut resolved = shaders_root;
+        for component in relative.components() {
+            resolved.push(component);
+            // guards against symlink loops
+            for _ in 0..MAX_SYMLINK_HOPS {
+                let target = match fs::read_link(&resolved) {
+                    Ok(target) => target,
+                    Err(_) => break,
+                };
+                // relative link targets are relative to the directory containing the link
+                resolved = normalize_path(&resolved.parent().unwrap().join(target));
+            }
+        }
+        resolved
+    }
+
     fn update_includes(&self, file: &Path) {
         let includes = self.find_includes(file);
 
@@ -348,6 +570,235 @@ impl MinecraftShaderLanguageServer {
     }
 
     pub fn lint(&self, uri: &Path) -> Result<HashMap<Url, Vec<Diagnostic>>> {
+        // the set of all filepath->content.
+        let mut all_sources: HashMap<PathBuf, String> = HashMap::new();
+        // the set of filepath->list of diagnostics to report
+        let mut diagnostics: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+
+        // the merge can't be built while included files are missing, so those are reported instead
+        let mut missing_includes = self.missing_includes(uri)?;
+        if !missing_includes.is_empty() {
+            internal_diagnostics::override_severities(&mut missing_includes, &self.config.borrow().internal_severity);
+            return Ok(missing_includes);
+        }
+
+        // an include is only part of a program once merged into a top-level file, so when linting is scoped to
+        // the file itself only its own source is checked
+        let is_include = self.get_file_toplevel_ancestors(uri)?.is_some();
+        if is_include && self.config.borrow().lint_scope == configuration::LintScope::File {
+            info!("lint scope is file, skipping validation of the including shaders"; "path" => uri.to_str().unwrap());
+            all_sources.insert(uri.to_path_buf(), self.read_source(uri)?);
+        } else if is_include && self.config.borrow().validate_standalone_includes && self.is_self_contained(uri)? {
+            info!("include is self-contained, validating it standalone"; "path" => uri.to_str().unwrap());
+            self.lint_standalone_include(uri, &mut all_sources, &mut diagnostics)?;
+        } else {
+            self.lint_toplevel_trees(uri, &mut all_sources, &mut diagnostics)?;
+        }
+
+        // diagnostics found by the server itself in each of the linked sources
+        let shaders_root = self.root.join("shaders");
+        let supported_extensions = OnceCell::new();
+        for (path, source) in &all_sources {
+            let mut file_diagnostics = internal_diagnostics::file_diagnostics(source, &self.config.borrow().include_suffix_separator);
+            file_diagnostics.extend(internal_diagnostics::foreign_workspace_includes(path, source, &shaders_root));
+            file_diagnostics.extend(internal_diagnostics::ambiguous_relative_includes(path, source, &shaders_root));
+            file_diagnostics.extend(internal_diagnostics::unsupported_extensions(source, || {
+                supported_extensions.get_or_init(|| self.opengl_context.supported_extensions())
+            }));
+            if !file_diagnostics.is_empty() {
+                diagnostics
+                    .entry(Url::from_file_path(path).unwrap())
+                    .or_default()
+                    .extend(file_diagnostics);
+            }
+        }
+
+        for (url, include_diagnostics) in self.included_toplevel_files(all_sources.keys()) {
+            diagnostics.entry(url).or_default().extend(include_diagnostics);
+        }
+
+        if self.config.borrow().diagnostic_target == configuration::DiagnosticTarget::Root {
+            self.remap_diagnostics_to_root(uri, &mut diagnostics)?;
+        }
+
+        // we want to backfill the diagnostics map with all linked sources
+        for path in all_sources.keys() {
+            diagnostics.entry(Url::from_file_path(path).unwrap()).or_default();
+        }
+
+        internal_diagnostics::override_severities(&mut diagnostics, &self.config.borrow().internal_severity);
+        internal_diagnostics::limit_diagnostics(&mut diagnostics, self.config.borrow().max_diagnostics_per_file);
+
+        Ok(diagnostics)
+    }
+
+    // flags includes of top-level files, e.g. `#include "composite.fsh"`, which are almost always a mistake as the
+    // included program brings its own `#version` and `main` along
+    fn included_toplevel_files<'a>(&self, paths: impl Iterator<Item = &'a PathBuf>) -> HashMap<Url, Vec<Diagnostic>> {
+        let mut graph = self.graph.borrow_mut();
+        let toplevel_files = self.toplevel_files.borrow();
+        let mut diagnostics: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+
+        for path in paths {
+            let node = match graph.find_node(path) {
+                Some(node) => node,
+                None => continue,
+            };
+            for (child, position) in graph.get_all_child_positions(node) {
+                let child_path = graph.get_node(child);
+                match child_path.strip_prefix(&self.root) {
+                    Ok(relative) if toplevel_files.is_top_level(relative) => (),
+                    _ => continue,
+                }
+
+                diagnostics
+                    .entry(Url::from_file_path(path).unwrap())
+                    .or_default()
+                    .push(internal_diagnostics::new_diagnostic(
+                        "included-shader",
+                        DiagnosticSeverity::WARNING,
+                        Range::new(
+                            Position::new(position.line as u32, position.start as u32),
+                            Position::new(position.line as u32, position.end as u32),
+                        ),
+                        format!(
+                            "`{}` is a shader program of its own, so including it brings along its `#version` and `main`",
+                            child_path.file_name().unwrap().to_str().unwrap()
+                        ),
+                    ));
+            }
+        }
+        diagnostics
+    }
+
+    // flags includes of files that don't exist in any of the trees the file is part of, and records them
+    // so that the including files can be linted again once the included file is created
+    fn missing_includes(&self, uri: &Path) -> Result<HashMap<Url, Vec<Diagnostic>>> {
+        let roots = match self.get_file_toplevel_ancestors(uri)? {
+            Some(ancestors) => ancestors,
+            None => vec![self.graph.borrow_mut().find_node(uri).unwrap()],
+        };
+
+        let graph = self.graph.borrow();
+        let mut unresolved = self.unresolved_includes.borrow_mut();
+        let mut diagnostics: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+
+        let mut visited = HashSet::new();
+        let mut stack = roots;
+        while let Some(node) = stack.pop() {
+            if !visited.insert(node) {
+                continue;
+            }
+            let path = graph.get_node(node);
+            for (child, position) in graph.get_all_child_positions(node) {
+                let child_path = graph.get_node(child);
+                if child_path.exists() {
+                    stack.push(child);
+                    continue;
+                }
+
+                let (code, message) = match self.unknown_include_alias(&path, &child_path) {
+                    Some(alias) => (
+                        "unknown-include-alias",
+                        format!(
+                            "`{}` isn't in `mcglsl.includeAliases`, so the include is resolved relative to this file, to {:?}, which doesn't exist",
+                            alias, child_path
+                        ),
+                    ),
+                    None => ("missing-include", format!("{:?} doesn't exist, so this shader can't be validated", child_path)),
+                };
+
+                unresolved.entry(child_path.clone()).or_default().insert(path.clone());
+                diagnostics
+                    .entry(Url::from_file_path(&path).unwrap())
+                    .or_default()
+                    .push(internal_diagnostics::new_diagnostic(
+                        code,
+                        DiagnosticSeverity::ERROR,
+                        Range::new(
+                            Position::new(position.line as u32, position.start as u32),
+                            Position::new(position.line as u32, position.end as u32),
+                        ),
+                        message,
+                    ));
+            }
+        }
+
+        Ok(diagnostics)
+    }
+
+    // the alias an include of `include` by `includer` was most likely meant to start with, if it starts with an
+    // `@`-prefixed directory and no such alias is configured, so it was resolved relative to `includer` instead
+    fn unknown_include_alias(&self, includer: &Path, include: &Path) -> Option<String> {
+        let relative = include.strip_prefix(includer.parent()?).ok()?;
+        let prefix = relative.components().next()?.as_os_str().to_str()?;
+        if !prefix.starts_with('@') || self.config.borrow().include_aliases.contains_key(prefix) {
+            return None;
+        }
+        Some(prefix.to_string())
+    }
+
+    // moves the diagnostics of included files onto the top-level files including them, at the line of
+    // the include through which they are (transitively) included
+    fn remap_diagnostics_to_root(&self, uri: &Path, diagnostics: &mut HashMap<Url, Vec<Diagnostic>>) -> Result<()> {
+        let roots = match self.get_file_toplevel_ancestors(uri)? {
+            Some(ancestors) => ancestors,
+            None => vec![self.graph.borrow_mut().find_node(uri).unwrap()],
+        };
+
+        let mut graph = self.graph.borrow_mut();
+        let root_urls: Vec<Url> = roots
+            .iter()
+            .map(|root| Url::from_file_path(graph.get_node(*root)).unwrap())
+            .collect();
+
+        let included: Vec<Url> = diagnostics.keys().filter(|url| !root_urls.contains(url)).cloned().collect();
+        for url in included {
+            let path = PathBuf::from_url(url.clone());
+            let chain = graph
+                .find_node(&path)
+                .and_then(|node| roots.iter().find_map(|root| graph.include_chain(*root, node)));
+            // a file of the tree always has a chain from one of the roots, but better safe than sorry
+            let (root, include) = match chain.as_ref().and_then(|chain| chain.first()) {
+                Some(hop) => *hop,
+                None => continue,
+            };
+
+            let display_path = path.strip_prefix(&self.root).unwrap_or(&path).to_path_buf();
+            let remapped: Vec<Diagnostic> = diagnostics
+                .remove(&url)
+                .unwrap()
+                .into_iter()
+                .map(|diagnostic| Diagnostic {
+                    range: Range::new(
+                        Position::new(include.line as u32, include.start as u32),
+                        Position::new(include.line as u32, include.end as u32),
+                    ),
+                    message: format!(
+                        "{} ({}:{})",
+                        diagnostic.message,
+                        display_path.to_str().unwrap(),
+                        diagnostic.range.start.line + 1
+                    ),
+                    ..diagnostic
+                })
+                .collect();
+
+            diagnostics.insert(url, vec![]);
+            diagnostics
+                .entry(Url::from_file_path(graph.get_node(root)).unwrap())
+                .or_default()
+                .extend(remapped);
+        }
+
+        Ok(())
+    }
+
+    // merges and validates every top-level tree the file is part of, collecting the sources of all files
+    // in those trees and the diagnostics reported by the driver
+    fn lint_toplevel_trees(
+        &self, uri: &Path, all_sources: &mut HashMap<PathBuf, String>, diagnostics: &mut HashMap<Url, Vec<Diagnostic>>,
+    ) -> Result<()> {
         // get all top level ancestors of this file
         let file_ancestors = match self.get_file_toplevel_ancestors(uri) {
             Ok(opt) => match opt {
@@ -369,17 +820,9 @@ impl MinecraftShaderLanguageServer {
                 .collect::<Vec<PathBuf>>())
         );
 
-        // the set of all filepath->content.
-        let mut all_sources: HashMap<PathBuf, String> = HashMap::new();
-        // the set of filepath->list of diagnostics to report
-        let mut diagnostics: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
-
-        // we want to backfill the diagnostics map with all linked sources
-        let back_fill = |all_sources: &HashMap<PathBuf, String>, diagnostics: &mut HashMap<Url, Vec<Diagnostic>>| {
-            for path in all_sources.keys() {
-                diagnostics.entry(Url::from_file_path(path).unwrap()).or_default();
-            }
-        };
+        // default states of the pack's options, which the driver otherwise doesn't know about. The define for
+        // the stage is added once it is known
+        let shader_options = shader_options::ShaderOptions::load(&self.root);
 
         // if we are a top-level file (this has to be one of the set defined by Optifine, right?)
         if file_ancestors.is_empty() {
@@ -389,7 +832,7 @@ impl MinecraftShaderLanguageServer {
                 Ok(tree) => tree,
                 Err(e) => {
                     diagnostics.insert(Url::from_file_path(uri).unwrap(), vec![e.into()]);
-                    return Ok(diagnostics);
+                    return Ok(());
                 }
             };
 
@@ -397,52 +840,36 @@ impl MinecraftShaderLanguageServer {
 
             let mut source_mapper = source_mapper::SourceMapper::new(all_sources.len());
 
-            let view = {
-                let graph = self.graph.borrow();
-                let merged_string = {
-                    merge_views::MergeViewBuilder::new(&tree, &all_sources, &graph, &mut source_mapper).build()
-                };
-                merged_string
-            };
-
-            let root_path = self.graph.borrow().get_node(root);
-            let ext = match root_path.extension() {
-                Some(ext) => ext.to_str().unwrap(),
-                None => {
-                    back_fill(&all_sources, &mut diagnostics);
-                    return Ok(diagnostics);
+            let view = match self.merge_tree(&tree, all_sources, &mut source_mapper) {
+                Ok(view) => view,
+                Err(e) => {
+                    warn!("merged shader exceeds size limit"; "path" => uri.to_str().unwrap(), "size" => e.size, "max_size" => e.max_size);
+                    diagnostics.insert(Url::from_file_path(uri).unwrap(), vec![e.into()]);
+                    return Ok(());
                 }
             };
 
-            if !is_top_level(root_path.strip_prefix(&self.root).unwrap()) {
-                warn!("got a non-valid toplevel file"; "root_ancestor" => root_path.to_str().unwrap(), "stripped" => root_path.strip_prefix(&self.root).unwrap().to_str().unwrap());
-                back_fill(&all_sources, &mut diagnostics);
-                return Ok(diagnostics);
-            }
-
-            let tree_type = if ext == "fsh" {
-                TreeType::Fragment
-            } else if ext == "vsh" {
-                TreeType::Vertex
-            } else if ext == "gsh" {
-                TreeType::Geometry
-            } else if ext == "csh" {
-                TreeType::Compute
-            } else {
-                unreachable!();
-            };
-
-            let stdout = match self.compile_shader_source(&view, tree_type, &root_path) {
-                Some(s) => s,
-                None => {
-                    back_fill(&all_sources, &mut diagnostics);
-                    return Ok(diagnostics);
+            let root_path = self.graph.borrow().get_node(root);
+            // files outside the root, e.g. the target of a symlink to elsewhere, are never top-level
+            let is_top_level = root_path
+                .strip_prefix(&self.root)
+                .map_or(false, |relative| self.toplevel_files.borrow().is_top_level(relative));
+            let tree_type = match TreeType::from_path(&root_path) {
+                Some(tree_type) if is_top_level => tree_type,
+                _ => {
+                    warn!("got a non-valid toplevel file"; "root_ancestor" => root_path.to_str().unwrap());
+                    // an include that no shader includes (yet) can't be validated, but the checks that don't need a
+                    // whole program still give feedback on it
+                    for (url, delimiter_diagnostics) in
+                        internal_diagnostics::unbalanced_delimiters(&view, &source_mapper, &self.graph.borrow())
+                    {
+                        diagnostics.entry(url).or_default().extend(delimiter_diagnostics);
+                    }
+                    return Ok(());
                 }
             };
 
-            let diagnostics_parser = diagnostics_parser::DiagnosticsParser::new(self.opengl_context.as_ref());
-
-            diagnostics.extend(diagnostics_parser.parse_diagnostics_output(stdout, uri, &source_mapper, &self.graph.borrow()));
+            self.validate_program(&view, &source_mapper, tree_type, &root_path, &shader_options, diagnostics);
         } else {
             let mut all_trees: Vec<(TreeType, Vec<FilialTuple>)> = Vec::new();
 
@@ -451,32 +878,20 @@ impl MinecraftShaderLanguageServer {
                     Ok(nodes) => nodes,
                     Err(e) => {
                         diagnostics.insert(Url::from_file_path(uri).unwrap(), vec![e.into()]);
-                        back_fill(&all_sources, &mut diagnostics); // TODO: confirm
-                        return Ok(diagnostics);
+                        return Ok(());
                     }
                 };
 
                 let root_path = self.graph.borrow().get_node(*root).clone();
-                let ext = match root_path.extension() {
-                    Some(ext) => ext.to_str().unwrap(),
-                    None => continue,
-                };
-
-                if !is_top_level(root_path.strip_prefix(&self.root).unwrap()) {
-                    warn!("got a non-valid toplevel file"; "root_ancestor" => root_path.to_str().unwrap(), "stripped" => root_path.strip_prefix(&self.root).unwrap().to_str().unwrap());
-                    continue;
-                }
-
-                let tree_type = if ext == "fsh" {
-                    TreeType::Fragment
-                } else if ext == "vsh" {
-                    TreeType::Vertex
-                } else if ext == "gsh" {
-                    TreeType::Geometry
-                } else if ext == "csh" {
-                    TreeType::Compute
-                } else {
-                    unreachable!();
+                let is_top_level = root_path
+                    .strip_prefix(&self.root)
+                    .map_or(false, |relative| self.toplevel_files.borrow().is_top_level(relative));
+                let tree_type = match TreeType::from_path(&root_path) {
+                    Some(tree_type) if is_top_level => tree_type,
+                    _ => {
+                        warn!("got a non-valid toplevel file"; "root_ancestor" => root_path.to_str().unwrap());
+                        continue;
+                    }
                 };
 
                 let sources = self.load_sources(&nodes)?;
@@ -487,31 +902,279 @@ impl MinecraftShaderLanguageServer {
             for tree in all_trees {
                 // bit over-zealous in allocation but better than having to resize
                 let mut source_mapper = source_mapper::SourceMapper::new(all_sources.len());
-                let view = {
-                    let graph = self.graph.borrow();
-                    let merged_string = {
-                        merge_views::MergeViewBuilder::new(&tree.1, &all_sources, &graph, &mut source_mapper).build()
-                    };
-                    merged_string
-                };
-
                 let root_path = self.graph.borrow().get_node(tree.1.first().unwrap().child);
-                let stdout = match self.compile_shader_source(&view, tree.0, &root_path) {
-                    Some(s) => s,
-                    None => continue,
+                let view = match self.merge_tree(&tree.1, all_sources, &mut source_mapper) {
+                    Ok(view) => view,
+                    Err(e) => {
+                        warn!("merged shader exceeds size limit"; "path" => root_path.to_str().unwrap(), "size" => e.size, "max_size" => e.max_size);
+                        diagnostics
+                            .entry(Url::from_file_path(&root_path).unwrap())
+                            .or_default()
+                            .push(e.into());
+                        continue;
+                    }
                 };
 
-                let diagnostics_parser = diagnostics_parser::DiagnosticsParser::new(self.opengl_context.as_ref());
+                self.validate_program(&view, &source_mapper, tree.0, &root_path, &shader_options, diagnostics);
+            }
+        };
+
+        Ok(())
+    }
 
-                diagnostics.extend(diagnostics_parser.parse_diagnostics_output(stdout, uri, &source_mapper, &self.graph.borrow()));
+    // whether a file can be compiled on its own, i.e. it has its own `#version` directive and defines `main`
+    fn is_self_contained(&self, path: &Path) -> Result<bool> {
+        let source = self.read_source(path)?;
+        if !source.lines().any(|line| line.trim_start().starts_with("#version")) {
+            return Ok(false);
+        }
+
+        let tree = match self.tree_sitter.borrow_mut().parse(&source, None) {
+            Some(tree) => tree,
+            None => return Ok(false),
+        };
+        Ok(internal_diagnostics::defines_main(tree.root_node(), &source))
+    }
+
+    // merges a self-contained include with its own includes and validates it as each of the stages of the
+    // top-level files that include it, instead of validating those files
+    fn lint_standalone_include(
+        &self, uri: &Path, all_sources: &mut HashMap<PathBuf, String>, diagnostics: &mut HashMap<Url, Vec<Diagnostic>>,
+    ) -> Result<()> {
+        let mut tree_types: Vec<TreeType> = Vec::new();
+        for ancestor in self.get_file_toplevel_ancestors(uri)?.unwrap_or_default() {
+            let ancestor_path = self.graph.borrow().get_node(ancestor);
+            // files outside the root, e.g. the target of a symlink to elsewhere, are never top-level
+            let is_top_level = ancestor_path
+                .strip_prefix(&self.root)
+                .map_or(false, |relative| self.toplevel_files.borrow().is_top_level(relative));
+            if !is_top_level {
+                continue;
+            }
+            let tree_type = match TreeType::from_path(&ancestor_path) {
+                Some(tree_type) => tree_type,
+                None => continue,
+            };
+            if !tree_types.contains(&tree_type) {
+                tree_types.push(tree_type);
+            }
+        }
+
+        let root = self.graph.borrow_mut().find_node(uri).unwrap();
+        let tree = match self.get_dfs_for_node(root) {
+            Ok(tree) => tree,
+            Err(e) => {
+                diagnostics.insert(Url::from_file_path(uri).unwrap(), vec![e.into()]);
+                return Ok(());
             }
         };
 
-        back_fill(&all_sources, &mut diagnostics);
-        Ok(diagnostics)
+        all_sources.extend(self.load_sources(&tree)?);
+
+        let mut source_mapper = source_mapper::SourceMapper::new(all_sources.len());
+        let view = match self.merge_tree(&tree, all_sources, &mut source_mapper) {
+            Ok(view) => view,
+            Err(e) => {
+                warn!("merged shader exceeds size limit"; "path" => uri.to_str().unwrap(), "size" => e.size, "max_size" => e.max_size);
+                diagnostics.insert(Url::from_file_path(uri).unwrap(), vec![e.into()]);
+                return Ok(());
+            }
+        };
+        let shader_options = shader_options::ShaderOptions::load(&self.root);
+
+        for tree_type in tree_types {
+            self.validate_program(&view, &source_mapper, tree_type, uri, &shader_options, diagnostics);
+        }
+
+        Ok(())
+    }
+
+    // merges a tree of files with their sources, as long as the merge stays within the configured size limit
+    fn merge_tree(
+        &self, tree: &[FilialTuple], sources: &HashMap<PathBuf, String>, source_mapper: &mut source_mapper::SourceMapper,
+    ) -> Result<String, merge_views::error::MergeSizeError> {
+        let max_merged_bytes = self.config.borrow().max_merged_bytes;
+        let graph = self.graph.borrow();
+        merge_views::MergeViewBuilder::new(tree, sources, &graph, source_mapper).build_bounded(max_merged_bytes)
+    }
+
+    // validates a merged program as the given stage, with the stage's define and the pack's options injected, and runs
+    // the server's own checks on it. The driver's diagnostics are reported against `path` when they can't be mapped
+    fn validate_program(
+        &self, view: &str, source_mapper: &source_mapper::SourceMapper, tree_type: TreeType, path: &Path,
+        shader_options: &shader_options::ShaderOptions, diagnostics: &mut HashMap<Url, Vec<Diagnostic>>,
+    ) {
+        let compiled = shader_options.for_stage(tree_type).inject(view);
+        let compiled = if self.config.borrow().compact_merge {
+            merge_views::compact_blank_lines(&compiled)
+        } else {
+            compiled
+        };
+
+        // only what the driver compiles is lowered, the server's own checks map through the directives as merged
+        let stdout = if self.config.borrow().lower_line_directives {
+            self.compile_shader_source(&merge_views::lower_line_directives(&compiled), tree_type, path)
+        } else {
+            self.compile_shader_source(&compiled, tree_type, path)
+        };
+        if let Some(stdout) = stdout {
+            let diagnostics_parser = self.diagnostics_parser();
+
+            diagnostics.extend(diagnostics_parser.parse_diagnostics_output(stdout, path, source_mapper, &self.graph.borrow()));
+        }
+
+        for (url, program_diagnostics) in self.program_diagnostics(&compiled, view, source_mapper, tree_type) {
+            diagnostics.entry(url).or_default().extend(program_diagnostics);
+        }
+    }
+
+    // runs the server's own checks that need the merged source of a whole program. `written` is the program before the
+    // shader options' defines are injected, as macro redefinitions are reported where they are written and the
+    // injected defines have no line of their own to be mapped back to
+    fn program_diagnostics(
+        &self, merged: &str, written: &str, source_mapper: &source_mapper::SourceMapper, tree_type: TreeType,
+    ) -> HashMap<Url, Vec<Diagnostic>> {
+        let (other_stage_uniforms, other_stage_blocks) = self.other_stage_declarations(source_mapper, tree_type);
+
+        let graph = self.graph.borrow();
+        let mut diagnostics = internal_diagnostics::unbalanced_delimiters(merged, source_mapper, &graph);
+        for (url, macro_diagnostics) in internal_diagnostics::macro_redefinitions(written, source_mapper, &graph) {
+            diagnostics.entry(url).or_default().extend(macro_diagnostics);
+        }
+
+        let tree = match self.tree_sitter.borrow_mut().parse(merged, None) {
+            Some(tree) => tree,
+            None => return diagnostics,
+        };
+
+        for (url, main_diagnostics) in internal_diagnostics::duplicate_main(&tree, merged, source_mapper, &graph) {
+            diagnostics.entry(url).or_default().extend(main_diagnostics);
+        }
+        for (url, main_diagnostics) in internal_diagnostics::missing_main(&tree, merged, source_mapper, &graph) {
+            diagnostics.entry(url).or_default().extend(main_diagnostics);
+        }
+        for (url, builtin_diagnostics) in internal_diagnostics::builtin_function_redefinitions(&tree, merged, source_mapper, &graph) {
+            diagnostics.entry(url).or_default().extend(builtin_diagnostics);
+        }
+        let is_fragment = tree_type == TreeType::Fragment;
+        for (url, readonly_diagnostics) in internal_diagnostics::readonly_assignments(&tree, merged, source_mapper, &graph, is_fragment) {
+            diagnostics.entry(url).or_default().extend(readonly_diagnostics);
+        }
+        for (url, array_diagnostics) in internal_diagnostics::array_initializer_sizes(&tree, merged, source_mapper, &graph) {
+            diagnostics.entry(url).or_default().extend(array_diagnostics);
+        }
+        if self.config.borrow().integer_division_hints {
+            for (url, division_diagnostics) in internal_diagnostics::integer_divisions(&tree, merged, source_mapper, &graph) {
+                diagnostics.entry(url).or_default().extend(division_diagnostics);
+            }
+        }
+        for (url, uniform_diagnostics) in
+            internal_diagnostics::mismatched_uniforms(&tree, merged, source_mapper, &graph, &other_stage_uniforms)
+        {
+            diagnostics.entry(url).or_default().extend(uniform_diagnostics);
+        }
+        for (url, block_diagnostics) in internal_diagnostics::block_conflicts(&tree, merged, source_mapper, &graph, &other_stage_blocks) {
+            diagnostics.entry(url).or_default().extend(block_diagnostics);
+        }
+        for (url, qualifier_diagnostics) in internal_diagnostics::legacy_storage_qualifiers(&tree, merged, source_mapper, &graph) {
+            diagnostics.entry(url).or_default().extend(qualifier_diagnostics);
+        }
+        for (url, location_diagnostics) in internal_diagnostics::location_collisions(&tree, merged, source_mapper, &graph) {
+            diagnostics.entry(url).or_default().extend(location_diagnostics);
+        }
+        if tree_type == TreeType::Fragment {
+            for (url, draw_buffer_diagnostics) in internal_diagnostics::undeclared_draw_buffers(&tree, merged, source_mapper, &graph) {
+                diagnostics.entry(url).or_default().extend(draw_buffer_diagnostics);
+            }
+            for (url, output_diagnostics) in internal_diagnostics::mixed_fragment_outputs(&tree, merged, source_mapper, &graph) {
+                diagnostics.entry(url).or_default().extend(output_diagnostics);
+            }
+        } else {
+            let stage = format!("{:?}", tree_type).to_lowercase();
+            for (url, discard_diagnostics) in internal_diagnostics::discard_outside_fragment(&tree, merged, source_mapper, &graph, &stage) {
+                diagnostics.entry(url).or_default().extend(discard_diagnostics);
+            }
+        }
+        diagnostics
+    }
+
+    // the uniforms and uniform blocks declared by the other stages of the program a merged shader belongs to, e.g.
+    // `composite.vsh` for `composite.fsh`. Compute shaders are programs of their own
+    fn other_stage_declarations(
+        &self, source_mapper: &source_mapper::SourceMapper, tree_type: TreeType,
+    ) -> (Vec<internal_diagnostics::StageUniform>, Vec<internal_diagnostics::StageBlock>) {
+        if tree_type == TreeType::Compute {
+            return (vec![], vec![]);
+        }
+        let root_path = self.graph.borrow().get_node(source_mapper.nodes()[0]);
+
+        let mut uniforms = vec![];
+        let mut blocks = vec![];
+        for (ext, stage) in [("vsh", TreeType::Vertex), ("gsh", TreeType::Geometry), ("fsh", TreeType::Fragment)] {
+            if stage == tree_type {
+                continue;
+            }
+            let path = root_path.with_extension(ext);
+            match path.strip_prefix(&self.root) {
+                Ok(relative) if self.toplevel_files.borrow().is_top_level(relative) => (),
+                _ => continue,
+            }
+            let node = match self.graph.borrow_mut().find_node(&path) {
+                Some(node) => node,
+                None => continue,
+            };
+
+            let nodes = match self.get_dfs_for_node(node) {
+                Ok(nodes) => nodes,
+                Err(_) => continue,
+            };
+            let sources = match self.load_sources(&nodes) {
+                Ok(sources) => sources,
+                Err(e) => {
+                    debug!("skipping other stage of program"; "path" => path.to_str().unwrap(), "error" => e.to_string());
+                    continue;
+                }
+            };
+
+            let mut stage_mapper = source_mapper::SourceMapper::new(sources.len());
+            let merged = match self.merge_tree(&nodes, &sources, &mut stage_mapper) {
+                Ok(merged) => merged,
+                Err(_) => continue,
+            };
+            let graph = self.graph.borrow();
+            let tree = match self.tree_sitter.borrow_mut().parse(&merged, None) {
+                Some(tree) => tree,
+                None => continue,
+            };
+
+            let stage = format!("{:?}", stage).to_lowercase();
+            uniforms.extend(internal_diagnostics::stage_uniforms(&tree, &merged, &stage_mapper, &graph, &stage));
+            blocks.extend(internal_diagnostics::stage_blocks(&tree, &merged, &stage_mapper, &graph, &stage));
+        }
+        (uniforms, blocks)
     }
 
     fn compile_shader_source(&self, source: &str, tree_type: TreeType, path: &Path) -> Option<String> {
+        let stage = opengl::shader_type(&tree_type);
+        if !self.opengl_context.supported_stages().contains(&stage) {
+            info!("skipping validation of unsupported shader stage"; "stage" => format!("{:?}", tree_type), "tree_root" => path.to_str().unwrap());
+            if self.notified_unsupported_stages.borrow_mut().insert(stage) {
+                self.endpoint
+                    .send_notification(
+                        ShowMessage::METHOD,
+                        ShowMessageParams {
+                            typ: MessageType::INFO,
+                            message: format!(
+                                "The OpenGL context doesn't support {:?} shaders, so they will not be validated.",
+                                tree_type
+                            ),
+                        },
+                    )
+                    .expect("failed to send popup/show message notification");
+            }
+            return None;
+        }
+
         let result = self.opengl_context.clone().validate(tree_type, source);
         match &result {
             Some(output) => {
@@ -522,6 +1185,17 @@ impl MinecraftShaderLanguageServer {
         result
     }
 
+    // a parser for the validator's logs, in the configured format, that skips the files matched by
+    // `ignoreDiagnosticsFrom`
+    fn diagnostics_parser(&self) -> diagnostics_parser::DiagnosticsParser<'_, dyn opengl::ShaderValidator> {
+        let parser = diagnostics_parser::DiagnosticsParser::new(self.opengl_context.as_ref())
+            .ignoring(&self.root, &self.config.borrow().ignore_diagnostics_from);
+        match self.diagnostics_regex.borrow().as_ref() {
+            Some(regex) => parser.with_line_regex(regex.clone()),
+            None => parser,
+        }
+    }
+
     pub fn get_dfs_for_node(&self, root: NodeIndex) -> Result<Vec<FilialTuple>, dfs::error::CycleError> {
         let graph_ref = self.graph.borrow();
 
@@ -530,6 +1204,12 @@ impl MinecraftShaderLanguageServer {
         dfs.collect::<Result<_, _>>()
     }
 
+    // reads a file in the encoding the pack is configured to be saved in
+    fn read_source(&self, path: &Path) -> std::io::Result<String> {
+        let contents = self.file_cache.borrow_mut().read(path)?;
+        text_encoding::decode(&contents, &self.config.borrow().encoding)
+    }
+
     pub fn load_sources(&self, nodes: &[FilialTuple]) -> Result<HashMap<PathBuf, String>> {
         let mut sources = HashMap::new();
 
@@ -541,7 +1221,7 @@ impl MinecraftShaderLanguageServer {
                 continue;
             }
 
-            let source = match fs::read_to_string(&path) {
+            let source = match self.read_source(&path) {
                 Ok(s) => s,
                 Err(e) => return Err(anyhow!("error reading {:?}: {}", path, e)),
             };
@@ -564,8 +1244,146 @@ impl MinecraftShaderLanguageServer {
         Ok(Some(roots))
     }
 
+    /// Returns whether the file is part of the workspace the server was started in. Files outside of it,
+    /// e.g. of another pack opened alongside, get no linting or navigation.
+    fn in_workspace(&self, path: &Path) -> bool {
+        path.starts_with(&self.root)
+    }
+
+    // the other files merged into the same programs as the given file, i.e. all files (transitively)
+    // included by its top-level ancestors, or by the file itself if it has none
+    fn program_files(&self, path: &Path) -> Vec<PathBuf> {
+        let roots = match self.get_file_toplevel_ancestors(path) {
+            Ok(Some(ancestors)) => ancestors,
+            Ok(None) => vec![self.graph.borrow_mut().find_node(path).unwrap()],
+            Err(_) => return vec![],
+        };
+
+        let graph = self.graph.borrow();
+        let mut visited = HashSet::new();
+        let mut stack = roots;
+        while let Some(node) = stack.pop() {
+            if visited.insert(node) {
+                stack.extend(graph.child_node_indexes(node));
+            }
+        }
+
+        visited
+            .into_iter()
+            .map(|node| graph.get_node(node))
+            .filter(|file| file != path)
+            .collect()
+    }
+
+    /// Returns whether the file lives in one of the configured library directories and is not
+    /// (yet) included by any other file. Such work-in-progress libraries get navigation but
+    /// are not linted when opened, as they have no top-level file to be merged into.
+    fn is_orphan_library(&self, path: &Path) -> bool {
+        if path.extension().map_or(true, |ext| ext != "glsl") {
+            return false;
+        }
+
+        let relative = match path.strip_prefix(self.root.join("shaders")) {
+            Ok(relative) => relative,
+            Err(_) => return false,
+        };
+
+        let in_library_dir = self
+            .config
+            .borrow()
+            .library_dirs
+            .iter()
+            .any(|dir| relative.starts_with(PathBuf::from_slash(dir)));
+        if !in_library_dir {
+            return false;
+        }
+
+        let node = match self.graph.borrow_mut().find_node(path) {
+            Some(n) => n,
+            None => return true,
+        };
+        let parents = self.graph.borrow().collect_root_ancestors(node);
+        parents.is_empty()
+    }
+
+    // replaces the current configuration with the `mcglsl` settings object, overridden by the workspace's
+    // `.mcshaderrc`, reloading the logger if the log level changed
+    fn apply_configuration(&mut self, settings: &Value) {
+        let settings = match configuration::workspace_settings(&self.root) {
+            Some(overrides) => configuration::merge_settings(settings, overrides),
+            None => settings.to_owned(),
+        };
+        let config: configuration::Configuration = match from_value(settings.clone()) {
+            Ok(config) => config,
+            Err(e) => {
+                error!("failed to parse configuration"; "error" => format!("{:?}", e), "config" => settings.to_string());
+                return;
+            }
+        };
+
+        if config.redetect_vendor {
+            self.vendor_cache.clear();
+        }
+        let previous = self.config.replace(config.clone());
+        if previous.diagnostics_regex != config.diagnostics_regex {
+            self.update_diagnostics_regex(config.diagnostics_regex.as_deref());
+        }
+        if previous.log_level == config.log_level && previous.log_file == config.log_file {
+            return;
+        }
+
+        let log_file = config.log_file.map(|log_file| self.root.join(log_file));
+        configuration::handle_log_level_change(config.log_level, |level| {
+            self.log_guard = None; // set to None so Drop is invoked
+            self.log_guard = Some(match logging::set_logger(level, log_file.as_deref()) {
+                Ok(guard) => guard,
+                Err(e) => {
+                    let guard = logging::set_logger_with_level(level);
+                    error!("failed to open log file, logging to stderr only"; "path" => format!("{:?}", log_file), "error" => e.to_string());
+                    guard
+                }
+            });
+        })
+    }
+
+    // compiles the configured compile log regex, falling back to the vendor's and telling the user if it is invalid
+    fn update_diagnostics_regex(&self, pattern: Option<&str>) {
+        let regex = pattern.and_then(|pattern| match diagnostics_parser::line_regex(pattern) {
+            Ok(regex) => Some(regex),
+            Err(e) => {
+                error!("invalid diagnostics regex"; "regex" => pattern, "error" => e.to_string());
+                self.endpoint
+                    .send_notification(
+                        ShowMessage::METHOD,
+                        ShowMessageParams {
+                            typ: MessageType::ERROR,
+                            message: format!("Invalid `mcglsl.diagnosticsRegex`, using the driver's format instead: {}", e),
+                        },
+                    )
+                    .expect("failed to send popup/show message notification");
+                None
+            }
+        });
+        self.diagnostics_regex.replace(regex);
+    }
+
     pub fn publish_diagnostic(&self, diagnostics: HashMap<Url, Vec<Diagnostic>>, document_version: Option<i32>) {
         // info!("DIAGNOSTICS:\n{:?}", diagnostics);
+        {
+            let mut published = self.diagnostics.borrow_mut();
+            for (uri, diagnostics) in &diagnostics {
+                if diagnostics.is_empty() {
+                    published.remove(uri);
+                } else {
+                    published.insert(uri.clone(), diagnostics.clone());
+                }
+            }
+        }
+
+        if let Err(e) = self.write_diagnostics_report() {
+            error!("failed to write diagnostics report"; "error" => format!("{:?}", e));
+        }
+
         for (uri, diagnostics) in diagnostics {
             self.endpoint
                 .send_notification(
@@ -580,7 +1398,82 @@ impl MinecraftShaderLanguageServer {
         }
     }
 
+    // publishes the diagnostics last published for the file again, if it has any
+    fn republish_diagnostics(&self, path: &Path) {
+        let uri = Url::from_file_path(path).unwrap();
+        let diagnostics = match self.diagnostics.borrow().get(&uri) {
+            Some(diagnostics) => diagnostics.clone(),
+            None => return,
+        };
+        info!("publishing previous diagnostics again"; "path" => path.to_str().unwrap(), "count" => diagnostics.len());
+        self.publish_diagnostic(HashMap::from([(uri, diagnostics)]), None);
+    }
+
+    // the paths of all top-level shaders in the workspace, sorted
+    fn toplevel_shaders(&self) -> Vec<PathBuf> {
+        let toplevel_files = self.toplevel_files.borrow();
+        let mut shaders: Vec<PathBuf> = self
+            .shader_files()
+            .into_iter()
+            .filter(|path| {
+                path.strip_prefix(&self.root)
+                    .map_or(false, |relative| toplevel_files.is_top_level(relative))
+            })
+            .collect();
+        shaders.sort();
+        shaders
+    }
+
+    fn send_progress(&self, token: &Option<ProgressToken>, value: WorkDoneProgress) {
+        let token = match token {
+            Some(token) => token.clone(),
+            None => return,
+        };
+        self.endpoint
+            .send_notification(
+                Progress::METHOD,
+                ProgressParams {
+                    token,
+                    value: ProgressParamsValue::WorkDone(value),
+                },
+            )
+            .unwrap_or(());
+    }
+
+    /// Writes all currently published diagnostics, keyed by file path, to the configured
+    /// `diagnosticsOutput` file. The report is written to a temporary file first and then
+    /// renamed over the target, so that readers never observe a partially written report.
+    fn write_diagnostics_report(&self) -> Result<()> {
+        let output = match &self.config.borrow().diagnostics_output {
+            Some(output) => self.root.join(output),
+            None => return Ok(()),
+        };
+
+        let published = self.diagnostics.borrow();
+        let report: BTreeMap<String, &Vec<Diagnostic>> = published
+            .iter()
+            .map(|(uri, diagnostics)| {
+                let path = PathBuf::from_url(uri.clone());
+                (path.to_str().unwrap().to_string(), diagnostics)
+            })
+            .collect();
+
+        let mut tmp_name = output
+            .file_name()
+            .ok_or_else(|| anyhow!("invalid diagnostics output path: {:?}", output))?
+            .to_os_string();
+        tmp_name.push(".tmp");
+        let tmp_path = output.with_file_name(tmp_name);
+
+        fs::write(&tmp_path, serde_json::to_string_pretty(&report)?)?;
+        fs::rename(&tmp_path, &output)?;
+        Ok(())
+    }
+
     fn set_status(&self, status: impl Into<String>, message: impl Into<String>, icon: impl Into<String>) {
+        if self.config.borrow().quiet {
+            return;
+        }
         self.endpoint
             .send_notification(
                 lsp_ext::Status::METHOD,
@@ -603,12 +1496,26 @@ impl LanguageServerHandling for MinecraftShaderLanguageServer {
                 definition_provider: Some(OneOf::Left(true)),
                 references_provider: Some(OneOf::Left(true)),
                 document_symbol_provider: Some(OneOf::Left(true)),
+                code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
                 document_link_provider: Some(DocumentLinkOptions {
                     resolve_provider: None,
                     work_done_progress_options: WorkDoneProgressOptions { work_done_progress: None },
                 }),
                 execute_command_provider: Some(ExecuteCommandOptions {
-                    commands: vec!["graphDot".into()],
+                    commands: vec![
+                        "graphDot".into(),
+                        "reloadShaderNames".into(),
+                        "shaderInterface".into(),
+                        "includeChain".into(),
+                        "includeGraph".into(),
+                        "resolveInclude".into(),
+                        "clearDiagnostics".into(),
+                        "invalidate".into(),
+                        "rawCompileLog".into(),
+                        "validateAll".into(),
+                        "mergeAll".into(),
+                        "symbolIndex".into(),
+                    ],
                     work_done_progress_options: WorkDoneProgressOptions { work_done_progress: None },
                 }),
                 text_document_sync: Some(TextDocumentSyncCapability::Options(TextDocumentSyncOptions {
@@ -622,7 +1529,7 @@ impl LanguageServerHandling for MinecraftShaderLanguageServer {
             };
 
             let root = match params.root_uri {
-                Some(uri) => PathBuf::from_url(uri),
+                Some(uri) => pack_root(PathBuf::from_url(uri)),
                 None => {
                     completable.complete(Err(MethodError {
                         code: 42069,
@@ -638,10 +1545,44 @@ impl LanguageServerHandling for MinecraftShaderLanguageServer {
                 server_info: None,
             }));
 
-            self.set_status("loading", "Building dependency graph...", "$(loading~spin)");
-
             self.root = root;
 
+            // clients that don't send workspace/didChangeConfiguration may pass the settings here instead. The
+            // workspace's own settings apply either way
+            let settings = params.initialization_options.as_ref().and_then(|options| options.get("mcglsl"));
+            if settings.is_some() || self.root.join(configuration::WORKSPACE_SETTINGS_FILE).is_file() {
+                let settings = settings.cloned().unwrap_or_else(|| Value::Object(Default::default()));
+                info!("got initial configuration"; "config" => settings.to_string());
+                self.apply_configuration(&settings);
+
+                let config = self.config.borrow();
+                *self.toplevel_files.borrow_mut() = toplevel::TopLevelFiles::new(&config.extra_shader_names, config.max_shader_index);
+                drop(config);
+            }
+
+            // after the settings are applied, as they may ask for quiet
+            self.set_status("loading", "Building dependency graph...", "$(loading~spin)");
+
+            let dry_run = params
+                .initialization_options
+                .as_ref()
+                .and_then(|options| options.get("dryRun"))
+                .and_then(Value::as_bool);
+            if dry_run == Some(true) {
+                let report = self.dry_run_report();
+                info!("dry run initialization, not building the include graph"; "report" => &report);
+                self.endpoint
+                    .send_notification(
+                        ShowMessage::METHOD,
+                        ShowMessageParams {
+                            typ: MessageType::INFO,
+                            message: report,
+                        },
+                    )
+                    .expect("failed to send popup/show message notification");
+                self.set_status("ready", "Dry run finished", "$(check)");
+                return;
+            }
 
             self.build_initial_graph();
 
@@ -660,21 +1601,9 @@ impl LanguageServerHandling for MinecraftShaderLanguageServer {
 
     fn workspace_change_configuration(&mut self, params: DidChangeConfigurationParams) {
         logging::slog_with_trace_id(|| {
-            #[derive(Deserialize)]
-            struct Configuration {
-                #[serde(alias = "logLevel")]
-                log_level: String,
-            }
-
             if let Some(settings) = params.settings.as_object().unwrap().get("mcglsl") {
-                let config: Configuration = from_value(settings.to_owned()).unwrap();
-
-                info!("got updated configuration"; "config" => params.settings.as_object().unwrap().get("mcglsl").unwrap().to_string());
-
-                configuration::handle_log_level_change(config.log_level, |level| {
-                    self.log_guard = None; // set to None so Drop is invoked
-                    self.log_guard = Some(logging::set_logger_with_level(level));
-                })
+                info!("got updated configuration"; "config" => settings.to_string());
+                self.apply_configuration(settings);
             }
         });
     }
@@ -683,30 +1612,51 @@ impl LanguageServerHandling for MinecraftShaderLanguageServer {
         logging::slog_with_trace_id(|| {
             //info!("opened doc {}", params.text_document.uri);
             let path = PathBuf::from_url(params.text_document.uri);
-            if !path.starts_with(&self.root) {
+            if !self.in_workspace(&path) {
                 return;
             }
+            let path = self.resolve_symlinks(&path);
+            self.open_files.borrow_mut().insert(path.clone());
 
             if self.graph.borrow_mut().find_node(&path) == None {
                 self.add_file_and_includes_to_graph(&path);
             }
+            // the editor may have dropped the file's diagnostics when it was closed, so when it isn't linted again
+            // the ones last published for it are shown again
+            if self.is_orphan_library(&path) {
+                debug!("skipping lint for library file not included anywhere"; "path" => path.to_str().unwrap());
+                self.republish_diagnostics(&path);
+                return;
+            }
             match self.lint(&path) {
                 Ok(diagnostics) => self.publish_diagnostic(diagnostics, None),
-                Err(e) => error!("error linting"; "error" => format!("{:?}", e), "path" => path.to_str().unwrap()),
+                Err(e) => {
+                    error!("error linting"; "error" => format!("{:?}", e), "path" => path.to_str().unwrap());
+                    self.republish_diagnostics(&path);
+                }
             }
         });
     }
 
     fn did_change_text_document(&mut self, _: DidChangeTextDocumentParams) {}
 
-    fn did_close_text_document(&mut self, _: DidCloseTextDocumentParams) {}
+    fn did_close_text_document(&mut self, params: DidCloseTextDocumentParams) {
+        let path = PathBuf::from_url(params.text_document.uri);
+        if !self.in_workspace(&path) {
+            return;
+        }
+        let path = self.resolve_symlinks(&path);
+        self.open_files.borrow_mut().remove(&path);
+    }
 
     fn did_save_text_document(&mut self, params: DidSaveTextDocumentParams) {
         logging::slog_with_trace_id(|| {
             let path = PathBuf::from_url(params.text_document.uri);
-            if !path.starts_with(&self.root) {
+            if !self.in_workspace(&path) {
                 return;
             }
+            let path = self.resolve_symlinks(&path);
+            self.file_cache.borrow_mut().invalidate(&path);
             self.update_includes(&path);
 
             match self.lint(&path) {
@@ -716,7 +1666,84 @@ impl LanguageServerHandling for MinecraftShaderLanguageServer {
         });
     }
 
-    fn did_change_watched_files(&mut self, _: DidChangeWatchedFilesParams) {}
+    fn did_change_watched_files(&mut self, params: DidChangeWatchedFilesParams) {
+        logging::slog_with_trace_id(|| {
+            // files in the graph that were changed or created
+            let mut changed = vec![];
+            // files that include a newly created file which was previously missing
+            let mut includers = HashSet::new();
+            // whether shaders.properties changed, whose options are defined in every shader
+            let mut options_changed = false;
+            let properties_path = self.root.join("shaders").join(shader_options::PROPERTIES_FILE);
+            for change in params.changes {
+                let path = PathBuf::from_url(change.uri);
+                if !self.in_workspace(&path) {
+                    continue;
+                }
+                let path = self.resolve_symlinks(&path);
+                self.file_cache.borrow_mut().invalidate(&path);
+                if path == properties_path {
+                    options_changed = true;
+                    continue;
+                }
+                // saving an open file is reported both as a save and as a watched change, and is linted on save
+                if change.typ == FileChangeType::CHANGED && self.open_files.borrow().contains(&path) {
+                    debug!("skipping watched change of open file"; "path" => path.to_str().unwrap());
+                    continue;
+                }
+                if change.typ == FileChangeType::DELETED {
+                    continue;
+                }
+
+                if change.typ == FileChangeType::CREATED {
+                    if let Some(including) = self.unresolved_includes.borrow_mut().remove(&path) {
+                        info!("previously missing include was created"; "path" => path.to_str().unwrap(), "included_by" => format!("{:?}", including));
+                        includers.extend(including);
+                    }
+                }
+                // an include created after it went missing already has a node, but its own includes weren't known yet
+                if self.graph.borrow_mut().find_node(&path).is_some() {
+                    changed.push(path);
+                }
+            }
+
+            // includes are only re-read once the whole batch is invalidated, and programs only linted once all
+            // includes are up to date, so that a shader changed along with one of its includes is never merged
+            // with a stale copy of either
+            for path in &changed {
+                self.update_includes(path);
+            }
+
+            // each program is linted once, however many of its files changed
+            let mut to_lint = BTreeSet::new();
+            for path in changed.into_iter().chain(includers) {
+                match self.get_file_toplevel_ancestors(&path) {
+                    Ok(Some(ancestors)) if self.config.borrow().lint_scope == configuration::LintScope::Dependents => {
+                        let graph = self.graph.borrow();
+                        to_lint.extend(ancestors.into_iter().map(|ancestor| graph.get_node(ancestor)));
+                    }
+                    Ok(_) if self.is_orphan_library(&path) => (),
+                    Ok(_) => {
+                        to_lint.insert(path);
+                    }
+                    Err(e) => error!("error finding shaders to lint"; "error" => format!("{:?}", e), "path" => path.to_str().unwrap()),
+                }
+            }
+
+            // the options are read again when linting, and may flip preprocessor branches in any of the shaders
+            if options_changed {
+                info!("shader options changed, linting all top-level shaders"; "path" => properties_path.to_str().unwrap());
+                to_lint.extend(self.toplevel_shaders());
+            }
+
+            for path in to_lint {
+                match self.lint(&path) {
+                    Ok(diagnostics) => self.publish_diagnostic(diagnostics, None),
+                    Err(e) => error!("error linting"; "error" => format!("{:?}", e), "path" => path.to_str().unwrap()),
+                }
+            }
+        });
+    }
 
     fn completion(&mut self, _: TextDocumentPositionParams, completable: LSCompletable<CompletionList>) {
         completable.complete(Err(Self::error_not_available(())));
@@ -738,23 +1765,22 @@ impl LanguageServerHandling for MinecraftShaderLanguageServer {
 
     fn execute_command(&mut self, params: ExecuteCommandParams, completable: LSCompletable<Option<Value>>) {
         logging::slog_with_trace_id(|| {
-            match self
-                .command_provider
-                .as_ref()
-                .unwrap()
-                .execute(&params.command, &params.arguments, &self.root)
-            {
+            let result = self.command_provider.as_ref().unwrap().execute(self, &params);
+
+            match result {
                 Ok(resp) => {
                     info!("executed command successfully"; "command" => params.command.clone());
-                    self.endpoint
-                        .send_notification(
-                            ShowMessage::METHOD,
-                            ShowMessageParams {
-                                typ: MessageType::INFO,
-                                message: format!("Command {} executed successfully.", params.command),
-                            },
-                        )
-                        .expect("failed to send popup/show message notification");
+                    if !self.config.borrow().quiet {
+                        self.endpoint
+                            .send_notification(
+                                ShowMessage::METHOD,
+                                ShowMessageParams {
+                                    typ: MessageType::INFO,
+                                    message: format!("Command {} executed successfully.", params.command),
+                                },
+                            )
+                            .expect("failed to send popup/show message notification");
+                    }
                     completable.complete(Ok(Some(resp)))
                 }
                 Err(err) => {
@@ -781,11 +1807,11 @@ impl LanguageServerHandling for MinecraftShaderLanguageServer {
     fn goto_definition(&mut self, params: TextDocumentPositionParams, completable: LSCompletable<Vec<Location>>) {
         logging::slog_with_trace_id(|| {
             let path = PathBuf::from_url(params.text_document.uri);
-            if !path.starts_with(&self.root) {
-                return;
+            if !self.in_workspace(&path) {
+                return completable.complete(Ok(vec![]));
             }
             let parser = &mut self.tree_sitter.borrow_mut();
-            let parser_ctx = match navigation::ParserContext::new(parser, &path) {
+            let mut parser_ctx = match navigation::ParserContext::new(parser, &path, &self.config.borrow().encoding) {
                 Ok(ctx) => ctx,
                 Err(e) => {
                     return completable.complete(Err(MethodError {
@@ -796,6 +1822,15 @@ impl LanguageServerHandling for MinecraftShaderLanguageServer {
                 }
             };
 
+            for program_file in self.program_files(&path) {
+                match self.read_source(&program_file) {
+                    Ok(source) => parser_ctx.add_macro_definitions(&program_file, &source),
+                    Err(e) => {
+                        warn!("failed to read file for macro definitions"; "path" => program_file.to_str().unwrap(), "error" => e.to_string())
+                    }
+                }
+            }
+
             match parser_ctx.find_definitions(&path, params.position) {
                 Ok(locations) => completable.complete(Ok(locations.unwrap_or_default())),
                 Err(e) => completable.complete(Err(MethodError {
@@ -810,11 +1845,11 @@ impl LanguageServerHandling for MinecraftShaderLanguageServer {
     fn references(&mut self, params: ReferenceParams, completable: LSCompletable<Vec<Location>>) {
         logging::slog_with_trace_id(|| {
             let path = PathBuf::from_url(params.text_document_position.text_document.uri);
-            if !path.starts_with(&self.root) {
-                return;
+            if !self.in_workspace(&path) {
+                return completable.complete(Ok(vec![]));
             }
             let parser = &mut self.tree_sitter.borrow_mut();
-            let parser_ctx = match navigation::ParserContext::new(parser, &path) {
+            let parser_ctx = match navigation::ParserContext::new(parser, &path, &self.config.borrow().encoding) {
                 Ok(ctx) => ctx,
                 Err(e) => {
                     return completable.complete(Err(MethodError {
@@ -843,11 +1878,11 @@ impl LanguageServerHandling for MinecraftShaderLanguageServer {
     fn document_symbols(&mut self, params: DocumentSymbolParams, completable: LSCompletable<DocumentSymbolResponse>) {
         logging::slog_with_trace_id(|| {
             let path = PathBuf::from_url(params.text_document.uri);
-            if !path.starts_with(&self.root) {
-                return;
+            if !self.in_workspace(&path) {
+                return completable.complete(Ok(DocumentSymbolResponse::from(Vec::<DocumentSymbol>::new())));
             }
             let parser = &mut self.tree_sitter.borrow_mut();
-            let parser_ctx = match navigation::ParserContext::new(parser, &path) {
+            let parser_ctx = match navigation::ParserContext::new(parser, &path, &self.config.borrow().encoding) {
                 Ok(ctx) => ctx,
                 Err(e) => {
                     return completable.complete(Err(MethodError {
@@ -875,8 +1910,42 @@ impl LanguageServerHandling for MinecraftShaderLanguageServer {
         completable.complete(Err(Self::error_not_available(())));
     }
 
-    fn code_action(&mut self, _: CodeActionParams, completable: LSCompletable<Vec<Command>>) {
-        completable.complete(Err(Self::error_not_available(())));
+    fn code_action(&mut self, params: CodeActionParams, completable: LSCompletable<Vec<Command>>) {
+        logging::slog_with_trace_id(|| {
+            let path = PathBuf::from_url(params.text_document.uri.clone());
+            if !self.in_workspace(&path) {
+                return completable.complete(Ok(vec![]));
+            }
+            // top-level files are never included, so have no use for a guard
+            let is_top_level = path
+                .strip_prefix(&self.root)
+                .map_or(false, |relative| self.toplevel_files.borrow().is_top_level(relative));
+            if is_top_level {
+                return completable.complete(Ok(vec![]));
+            }
+
+            let source = match self.read_source(&path) {
+                Ok(s) => s,
+                Err(e) => {
+                    return completable.complete(Err(MethodError {
+                        code: 42069,
+                        message: format!("error reading file: error={}, path={:?}", e, path),
+                        data: (),
+                    }))
+                }
+            };
+
+            // the client applies the edit carried by the command
+            let actions = include_guard::include_guard_edit(&self.root, &path, params.text_document.uri, &source)
+                .map(|edit| Command {
+                    title: "Add include guard".into(),
+                    command: "mcglsl.addIncludeGuard".into(),
+                    arguments: Some(vec![serde_json::to_value(edit).unwrap()]),
+                })
+                .into_iter()
+                .collect();
+            completable.complete(Ok(actions));
+        });
     }
 
     fn code_lens(&mut self, _: CodeLensParams, completable: LSCompletable<Vec<CodeLens>>) {
@@ -891,6 +1960,10 @@ impl LanguageServerHandling for MinecraftShaderLanguageServer {
         logging::slog_with_trace_id(|| {
             // node for current document
             let curr_doc = PathBuf::from_url(params.text_document.uri);
+            if !self.in_workspace(&curr_doc) {
+                completable.complete(Ok(vec![]));
+                return;
+            }
             let node = match self.graph.borrow_mut().find_node(&curr_doc) {
                 Some(n) => n,
                 None => {
@@ -900,13 +1973,14 @@ impl LanguageServerHandling for MinecraftShaderLanguageServer {
                 }
             };
 
-            let edges: Vec<DocumentLink> = self
-                .graph
-                .borrow()
-                .child_node_indexes(node)
-                .filter_map::<Vec<DocumentLink>, _>(|child| {
-                    let graph = self.graph.borrow();
-                    graph.get_child_positions(node, child).map(|value| {
+            let shaders_root = self.root.join("shaders");
+            let edges: Vec<DocumentLink> =
+                self.graph
+                    .borrow()
+                    .child_node_indexes(node)
+                    .filter_map::<Vec<DocumentLink>, _>(|child| {
+                        let graph = self.graph.borrow();
+                        graph.get_child_positions(node, child).map(|value| {
                         let path = graph.get_node(child);
                         let url = match Url::from_file_path(&path) {
                             Ok(url) => url,
@@ -915,20 +1989,25 @@ impl LanguageServerHandling for MinecraftShaderLanguageServer {
                                 return None;
                             }
                         };
-    
+                        // relative to the shaders folder, unless the include is outside it
+                        let tooltip = match path.strip_prefix(&shaders_root) {
+                            Ok(relative) => relative.to_slash_lossy(),
+                            Err(_) => path.to_str().unwrap().to_string(),
+                        };
+
                         Some(DocumentLink {
                             range: Range::new(
                                 Position::new(u32::try_from(value.line).unwrap(), u32::try_from(value.start).unwrap()),
                                 Position::new(u32::try_from(value.line).unwrap(), u32::try_from(value.end).unwrap()),
                             ),
                             target: Some(url.clone()),
-                            tooltip: Some(url.path().to_string()),
+                            tooltip: Some(tooltip),
                             data: None,
                         })
                     }).collect()
-                })
-                .flatten()
-                .collect();
+                    })
+                    .flatten()
+                    .collect();
             debug!("document link results";
                 "links" => format!("{:?}", edges.iter().map(|e| (e.range, e.target.as_ref().unwrap().path())).collect::<Vec<_>>()),
                 "path" => curr_doc.to_str().unwrap(),
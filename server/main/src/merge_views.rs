@@ -7,13 +7,19 @@ use std::{
 
 use core::slice::Iter;
 
+use lazy_static::lazy_static;
 use petgraph::stable_graph::NodeIndex;
+use regex::{Captures, Regex};
 use slog_scope::debug;
 
 use crate::graph::CachedStableGraph;
-use crate::source_mapper::SourceMapper;
+use crate::source_mapper::{self, SourceMapper};
 use crate::IncludePosition;
 
+lazy_static! {
+    static ref RE_LINE_NUMBER: Regex = Regex::new(r#"^(\s*#\s*line\s+)([0-9]+)"#).unwrap();
+}
+
 /// FilialTuple represents a tuple (not really) of a child and any legitimate
 /// parent. Parent can be nullable in the case of the child being a top level
 /// node in the tree.
@@ -42,6 +48,13 @@ pub struct MergeViewBuilder<'a> {
     // is included into the parent in line-sorted order. This is necessary for files that are imported
     // more than once into the same parent, so we can easily get the next include position.
     parent_child_edge_iterator: HashMap<FilialTuple, Box<(dyn Iterator<Item = IncludePosition> + 'a)>>,
+
+    // whether included content is indented to the column of the `#include` it replaces
+    indent_includes: bool,
+    // the indentation of the file whose content is currently being added to the merge list
+    current_indent: String,
+    // the indentation of the merge list entries from the given index onwards, in increasing index order
+    indents: Vec<(usize, String)>,
 }
 
 impl<'a> MergeViewBuilder<'a> {
@@ -56,10 +69,48 @@ impl<'a> MergeViewBuilder<'a> {
             source_mapper,
             last_offset_set: HashMap::new(),
             parent_child_edge_iterator: HashMap::new(),
+            indent_includes: false,
+            current_indent: String::new(),
+            indents: Vec::new(),
         }
     }
 
+    /// Indents the content of each include, along with the `#line` directives around it, to match the
+    /// indentation of the `#include` directive it replaces. Only meant for merges read by humans, the
+    /// driver gets the included content verbatim.
+    pub fn indent_includes(mut self, indent_includes: bool) -> Self {
+        self.indent_includes = indent_includes;
+        self
+    }
+
     pub fn build(&mut self) -> String {
+        // without a size limit, building can't fail
+        self.build_bounded(usize::MAX).unwrap()
+    }
+
+    /// Builds the merged source, failing instead of allocating the merged string if it would
+    /// be larger than `max_bytes`.
+    pub fn build_bounded(&mut self, max_bytes: usize) -> Result<String, error::MergeSizeError> {
+        // a file without includes merges to itself, so there is no merge list to build
+        if self.nodes.len() == 1 {
+            let first = self.nodes[0].child;
+            let first_source = self.sources.get(&self.graph.get_node(first)).unwrap();
+
+            self.source_mapper.get_num(first);
+
+            if first_source.len() > max_bytes {
+                return Err(error::MergeSizeError {
+                    size: first_source.len(),
+                    max_size: max_bytes,
+                });
+            }
+            return Ok(first_source.clone());
+        }
+
+        self.build_merge_list(max_bytes)
+    }
+
+    fn build_merge_list(&mut self, max_bytes: usize) -> Result<String, error::MergeSizeError> {
         // contains additionally inserted lines such as #line and other directives, preamble defines etc
         let mut extra_lines: Vec<String> = Vec::new();
         extra_lines.reserve((self.nodes.len() * 2) + 2);
@@ -105,12 +156,52 @@ impl<'a> MergeViewBuilder<'a> {
 
         let total_len = merge_list.iter().fold(0, |a, b| a + b.len());
 
+        if total_len > max_bytes {
+            return Err(error::MergeSizeError {
+                size: total_len,
+                max_size: max_bytes,
+            });
+        }
+
+        if self.indent_includes {
+            return Ok(self.indent(merge_list));
+        }
+
         let mut merged = String::with_capacity(total_len);
         merged.extend(merge_list);
 
+        Ok(merged)
+    }
+
+    // joins the merge list, prefixing each non-empty line with the indentation recorded for its entry
+    fn indent(&self, merge_list: LinkedList<&str>) -> String {
+        let mut merged = String::new();
+        let mut indents = self.indents.iter().peekable();
+        let mut indent = "";
+        let mut at_line_start = true;
+        for (i, view) in merge_list.into_iter().enumerate() {
+            while let Some((_, next)) = indents.next_if(|(from, _)| *from <= i) {
+                indent = next.as_str();
+            }
+            for c in view.chars() {
+                if at_line_start && c != '\n' {
+                    merged.push_str(indent);
+                }
+                merged.push(c);
+                at_line_start = c == '\n';
+            }
+        }
         merged
     }
 
+    // sets the indentation of the merge list entries added from now on
+    fn set_indent(&mut self, indent: String, merge_list: &LinkedList<&str>) {
+        if self.indent_includes {
+            self.indents.push((merge_list.len(), indent.clone()));
+            self.current_indent = indent;
+        }
+    }
+
     fn create_merge_views(&mut self, merge_list: &mut LinkedList<&'a str>, extra_lines: &mut Vec<String>, stack: &mut VecDeque<NodeIndex>) {
         loop {
             let n = match self.nodes_peeker.next() {
@@ -148,6 +239,13 @@ impl<'a> MergeViewBuilder<'a> {
             );
 
             merge_list.push_back(&parent_source[offset..char_for_line]);
+
+            // the included content is indented by the whitespace before the `#include`, on top of its parent's indentation
+            let parent_indent = self.current_indent.clone();
+            let include_line = parent_source[char_for_line..].lines().next().unwrap_or_default();
+            let include_indent = &include_line[..include_line.len() - include_line.trim_start().len()];
+            self.set_indent(format!("{}{}", parent_indent, include_indent), merge_list);
+
             self.add_opening_line_directive(&child_path, child, merge_list, extra_lines);
 
             match self.nodes_peeker.peek() {
@@ -165,6 +263,7 @@ impl<'a> MergeViewBuilder<'a> {
                         };
                         merge_list.push_back(&child_source[..offset]);
                         self.set_last_offset_for_tuple(Some(parent), child, 0);
+                        self.set_indent(parent_indent, merge_list);
                         // +2 because edge.line is 0 indexed but #line is 1 indexed and references the *following* line
                         self.add_closing_line_directive(edge.line + 2, &parent_path, parent, merge_list, extra_lines);
                         // if the next pair's parent is not the current pair's parent, we need to bubble up
@@ -191,6 +290,7 @@ impl<'a> MergeViewBuilder<'a> {
                         self.set_last_offset_for_tuple(Some(parent), child, 0);
                     }
 
+                    self.set_indent(parent_indent, merge_list);
                     // +2 because edge.line is 0 indexed but #line is 1 indexed and references the *following* line
                     self.add_closing_line_directive(edge.line + 2, &parent_path, parent, merge_list, extra_lines);
 
@@ -208,6 +308,7 @@ impl<'a> MergeViewBuilder<'a> {
                     };
                     merge_list.push_back(&child_source[..offset]);
                     self.set_last_offset_for_tuple(Some(parent), child, 0);
+                    self.set_indent(parent_indent, merge_list);
                     // +2 because edge.line is 0 indexed but #line is 1 indexed and references the *following* line
                     self.add_closing_line_directive(edge.line + 2, &parent_path, parent, merge_list, extra_lines);
                 }
@@ -322,12 +423,143 @@ impl<'a> MergeViewBuilder<'a> {
     }
 }
 
+/// Replaces every run of two or more blank lines in a merged source with a `#line` directive for the line
+/// following it, so that line numbers reported by the driver stay correct. Blank lines up to the `#version`
+/// directive and inside block comments are kept, as a directive would be ignored there.
+pub fn compact_blank_lines(merged: &str) -> String {
+    let origins = source_mapper::merged_line_origins(merged);
+    let lines: Vec<&str> = merged.lines().collect();
+
+    let first_compacted = lines
+        .iter()
+        .position(|line| line.trim_start().starts_with("#version"))
+        .map_or(0, |line| line + 1);
+
+    let mut compacted = String::with_capacity(merged.len());
+    let mut in_comment = false;
+    let mut i = 0;
+    while i < lines.len() {
+        if i < first_compacted || in_comment || !lines[i].trim().is_empty() {
+            in_comment = ends_in_block_comment(lines[i], in_comment);
+            compacted.push_str(lines[i]);
+            compacted.push('\n');
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        while i < lines.len() && lines[i].trim().is_empty() {
+            i += 1;
+        }
+
+        if i - start < 2 {
+            compacted.push_str(lines[start]);
+            compacted.push('\n');
+            continue;
+        }
+
+        // not needed if the run is followed by a #line directive anyway, or nothing at all
+        if let Some(Some((source_num, line))) = origins.get(i) {
+            // +1 because #line is 1 indexed
+            compacted.push_str(&format!("#line {} {}\n", line + 1, source_num));
+        }
+    }
+
+    if !merged.ends_with('\n') {
+        compacted.pop();
+    }
+    compacted
+}
+
+/// Lowers the line number of every `#line` directive in a merged source by one, turning `#line 1` into `#line 0`.
+/// Intel drivers number the line following a directive one higher than the directive says, which this cancels out.
+pub fn lower_line_directives(merged: &str) -> String {
+    merged
+        .split_inclusive('\n')
+        .map(|line| {
+            RE_LINE_NUMBER.replace(line, |cap: &Captures| {
+                format!("{}{}", &cap[1], cap[2].parse::<usize>().map_or(0, |n| n.saturating_sub(1)))
+            })
+        })
+        .collect()
+}
+
+// whether a block comment is still open at the end of the line, given whether one was open at its start
+fn ends_in_block_comment(line: &str, mut in_comment: bool) -> bool {
+    let mut rest = line;
+    loop {
+        if in_comment {
+            match rest.find("*/") {
+                Some(end) => {
+                    rest = &rest[end + 2..];
+                    in_comment = false;
+                }
+                None => return true,
+            }
+        } else {
+            let line_comment = rest.find("//");
+            match rest.find("/*") {
+                Some(start) if line_comment.map_or(true, |l| start < l) => {
+                    rest = &rest[start + 2..];
+                    in_comment = true;
+                }
+                _ => return false,
+            }
+        }
+    }
+}
+
+pub mod error {
+    use std::{
+        error::Error as StdError,
+        fmt::{Debug, Display},
+    };
+
+    use crate::consts;
+
+    use rust_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
+
+    #[derive(Debug)]
+    pub struct MergeSizeError {
+        pub size: usize,
+        pub max_size: usize,
+    }
+
+    impl StdError for MergeSizeError {}
+
+    impl Display for MergeSizeError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(
+                f,
+                "Merged shader is {} bytes, exceeding the limit of {} bytes. This is likely caused by files being included many times over, e.g. a heavily fanned-out include tree or a generated file",
+                self.size, self.max_size
+            )
+        }
+    }
+
+    impl From<MergeSizeError> for Diagnostic {
+        fn from(e: MergeSizeError) -> Diagnostic {
+            Diagnostic {
+                severity: Some(DiagnosticSeverity::ERROR),
+                range: Range::new(Position::new(0, 0), Position::new(0, 500)),
+                source: Some(consts::SOURCE.into()),
+                message: format!("{}", e),
+                code: None,
+                tags: None,
+                related_information: None,
+                code_description: Option::None,
+                data: Option::None,
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod merge_view_test {
     use std::fs;
     use std::path::PathBuf;
 
-    use crate::merge_views::MergeViewBuilder;
+    use crate::merge_views::{compact_blank_lines, MergeViewBuilder};
     use crate::source_mapper::SourceMapper;
     use crate::test::{copy_to_and_set_root, new_temp_server};
     use crate::IncludePosition;
@@ -598,6 +830,74 @@ mod merge_view_test {
         assert_eq!(result, truth);
     }
 
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_merge_size_limit() {
+        let mut server = new_temp_server(None);
+
+        let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/09", &mut server);
+        server.endpoint.request_shutdown();
+
+        server.build_initial_graph();
+
+        let final_idx = server
+            .graph
+            .borrow_mut()
+            .find_node(&tmp_path.join("shaders").join("final.fsh"))
+            .unwrap();
+
+        let nodes = server.get_dfs_for_node(final_idx).unwrap();
+        let sources = server.load_sources(&nodes).unwrap();
+
+        let graph_borrow = server.graph.borrow();
+
+        let mut source_mapper = SourceMapper::new(0);
+        let merged = MergeViewBuilder::new(&nodes, &sources, &graph_borrow, &mut source_mapper).build();
+        // c.glsl is reached 2*2*2 times
+        assert_eq!(merged.matches("float c()").count(), 8);
+
+        let mut source_mapper = SourceMapper::new(0);
+        let result = MergeViewBuilder::new(&nodes, &sources, &graph_borrow, &mut source_mapper).build_bounded(merged.len());
+        assert_eq!(result.unwrap(), merged);
+
+        let mut source_mapper = SourceMapper::new(0);
+        let result = MergeViewBuilder::new(&nodes, &sources, &graph_borrow, &mut source_mapper).build_bounded(1024);
+        let err = result.unwrap_err();
+        assert_eq!(err.size, merged.len());
+        assert_eq!(err.max_size, 1024);
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_merge_without_includes() {
+        let mut server = new_temp_server(None);
+
+        let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/18", &mut server);
+        server.endpoint.request_shutdown();
+
+        server.build_initial_graph();
+
+        let final_path = tmp_path.join("shaders").join("final.fsh");
+        let final_idx = server.graph.borrow_mut().find_node(&final_path).unwrap();
+
+        let nodes = server.get_dfs_for_node(final_idx).unwrap();
+        assert_eq!(nodes.len(), 1);
+        let sources = server.load_sources(&nodes).unwrap();
+
+        let graph_borrow = server.graph.borrow();
+
+        let mut source_mapper = SourceMapper::new(0);
+        let result = MergeViewBuilder::new(&nodes, &sources, &graph_borrow, &mut source_mapper).build();
+
+        let mut source_mapper = SourceMapper::new(0);
+        let truth = MergeViewBuilder::new(&nodes, &sources, &graph_borrow, &mut source_mapper)
+            .build_merge_list(usize::MAX)
+            .unwrap();
+
+        assert_eq!(result, truth);
+        assert_eq!(&result, sources.get(&final_path).unwrap());
+    }
+
     #[test]
     #[logging_macro::log_scope]
     fn test_generate_merge_list_06() {
@@ -642,4 +942,87 @@ mod merge_view_test {
 
         assert_eq!(result, truth);
     }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_include_on_first_line() {
+        let mut server = new_temp_server(None);
+
+        let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/37", &mut server);
+        server.endpoint.request_shutdown();
+
+        server.build_initial_graph();
+
+        let final_idx = server
+            .graph
+            .borrow_mut()
+            .find_node(&tmp_path.join("shaders").join("final.fsh"))
+            .unwrap();
+
+        let nodes = server.get_dfs_for_node(final_idx).unwrap();
+        let sources = server.load_sources(&nodes).unwrap();
+
+        let graph_borrow = server.graph.borrow();
+        let mut source_mapper = SourceMapper::new(0);
+        let result = MergeViewBuilder::new(&nodes, &sources, &graph_borrow, &mut source_mapper).build();
+
+        let merge_file = tmp_path.join("shaders").join("final.fsh.merge");
+
+        let mut truth = fs::read_to_string(merge_file).unwrap();
+
+        // b.glsl is included by the very first line of a.glsl
+        for file in &[
+            PathBuf::new().join("lib").join("a.glsl"),
+            PathBuf::new().join("lib").join("b.glsl"),
+            PathBuf::new().join("lib").join("a.glsl"),
+            PathBuf::new().join("final.fsh"),
+        ] {
+            truth = truth.replacen(
+                "!!",
+                &tmp_path.join("shaders").join(file).to_str().unwrap().replace('\\', "\\\\"),
+                1,
+            );
+        }
+
+        assert_eq!(result, truth);
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_compact_blank_lines() {
+        let merged = "#version 120\n\
+            \n\
+            #line 1 1 // /lib/a.glsl\n\
+            float a() {\n\
+            \n\
+            \n\
+            \treturn 1.0;\n\
+            }\n\
+            \n\
+            \n\
+            #line 2 0 // /final.fsh\n\
+            /*\n\
+            \n\
+            \n\
+            */\n\
+            \n\
+            void main() {}\n";
+
+        let expected = "#version 120\n\
+            \n\
+            #line 1 1 // /lib/a.glsl\n\
+            float a() {\n\
+            #line 4 1\n\
+            \treturn 1.0;\n\
+            }\n\
+            #line 2 0 // /final.fsh\n\
+            /*\n\
+            \n\
+            \n\
+            */\n\
+            \n\
+            void main() {}\n";
+
+        assert_eq!(compact_blank_lines(merged), expected);
+    }
 }
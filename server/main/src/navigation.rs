@@ -1,12 +1,60 @@
-use std::{collections::HashMap, fs::read_to_string, path::Path, vec};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    vec,
+};
 
 use anyhow::Result;
+use lazy_static::lazy_static;
+use regex::Regex;
 use rust_lsp::lsp_types::{DocumentSymbol, Location, Position, Range, SymbolKind};
 use slog_scope::{debug, info, trace};
 use tree_sitter::{Node, Parser, Point, Query, QueryCursor, Tree};
 use url::Url;
 
 use crate::linemap::LineMap;
+use crate::shader_interface;
+use crate::text_encoding;
+
+lazy_static! {
+    // opaque types such as sampler2D, usampler3D or image2D, which are handles rather than values
+    static ref RE_OPAQUE_TYPE: Regex = Regex::new(r#"^[iu]?(?:sampler|image)"#).unwrap();
+    // builtin scalar, vector and matrix types, whose constructors look like function calls
+    static ref RE_BUILTIN_TYPE: Regex = Regex::new(r#"^(?:float|double|int|uint|bool|[dbiu]?vec[234]|d?mat[234](?:x[234])?)$"#).unwrap();
+    static ref RE_DEFINE: Regex = Regex::new(r#"^\s*#\s*define\s+([A-Za-z_][A-Za-z0-9_]*)"#).unwrap();
+    static ref RE_PRAGMA: Regex = Regex::new(r#"^\s*(#\s*pragma\b)\s*(.*?)\s*$"#).unwrap();
+}
+
+/// Maps macro names to the locations they are `#define`d at, across all the files added to it. The GLSL
+/// grammar parses each directive on its own, so macro invocations can't be resolved from the tree alone.
+#[derive(Clone, Debug, Default)]
+pub struct MacroTable {
+    definitions: HashMap<String, Vec<Location>>,
+}
+
+impl MacroTable {
+    pub fn add_file(&mut self, path: &Path, source: &str) {
+        let uri = Url::from_file_path(path).unwrap();
+        for (line_num, line) in source.lines().enumerate() {
+            let name = match RE_DEFINE.captures(line) {
+                Some(cap) => cap.get(1).unwrap(),
+                None => continue,
+            };
+            self.definitions.entry(name.as_str().to_string()).or_default().push(Location {
+                uri: uri.clone(),
+                range: Range::new(
+                    Position::new(line_num as u32, name.start() as u32),
+                    Position::new(line_num as u32, name.end() as u32),
+                ),
+            });
+        }
+    }
+
+    pub fn definitions(&self, name: &str) -> Option<&Vec<Location>> {
+        self.definitions.get(name)
+    }
+}
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq, Default)]
 struct SymbolName(String);
@@ -26,6 +74,11 @@ impl SymbolName {
                     let func_ident = node.child_by_field_name("declarator").unwrap().child(0).unwrap();
                     fqname.push(format!("{}[{}]", func_ident.utf8_text(source.as_bytes()).unwrap(), func_ident.id()));
                 }
+                // parameters, as opposed to the function's own name
+                ("function_declarator", "parameter_list") => {
+                    let func_ident = node.child_by_field_name("declarator").unwrap();
+                    fqname.push(format!("{}[{}]", func_ident.utf8_text(source.as_bytes()).unwrap(), func_ident.id()));
+                }
                 ("struct_specifier", "field_declaration_list") => {
                     let struct_ident = node.child_by_field_name("name").unwrap();
                     fqname.push(format!(
@@ -123,6 +176,25 @@ const LIST_SYMBOLS_STR: &str = r#"
     (function_declarator
         (identifier) @func_ident)
 
+    ; function parameters
+    (parameter_declaration
+        [
+            (identifier) @param_ident
+            (array_declarator
+                (identifier) @param_ident)
+        ])
+
+    ; local variables
+    (compound_statement
+        (declaration
+            [
+                (identifier) @local_ident
+                (init_declarator
+                    (identifier) @local_ident)
+                (array_declarator
+                    (identifier) @local_ident)
+            ]))
+
     ; struct definitions
     (struct_specifier
         (type_identifier) @struct_ident)
@@ -142,23 +214,39 @@ pub struct ParserContext<'a> {
     source: String,
     tree: Tree,
     linemap: LineMap,
+    macros: MacroTable,
     parser: &'a mut Parser,
 }
 
 impl<'a> ParserContext<'a> {
-    pub fn new(parser: &'a mut Parser, path: &Path) -> Result<Self> {
-        let source = read_to_string(path)?;
+    /// Builds a context over the file at `path`, saved in the encoding with the given label.
+    pub fn new(parser: &'a mut Parser, path: &Path, encoding: &str) -> Result<Self> {
+        let source = text_encoding::decode(&fs::read(path)?, encoding)?;
+        Ok(ParserContext::from_source(parser, path, source))
+    }
 
+    /// Builds a context over in-memory source instead of reading it from `path`, which is then only
+    /// used for the locations returned.
+    pub fn from_source(parser: &'a mut Parser, path: &Path, source: String) -> Self {
         let tree = parser.parse(&source, None).unwrap();
 
         let linemap = LineMap::new(&source);
 
-        Ok(ParserContext {
+        let mut macros = MacroTable::default();
+        macros.add_file(path, &source);
+
+        ParserContext {
             source,
             tree,
             linemap,
+            macros,
             parser,
-        })
+        }
+    }
+
+    /// Makes the macros defined in another file, such as an include, resolvable from this file.
+    pub fn add_macro_definitions(&mut self, path: &Path, source: &str) {
+        self.macros.add_file(path, source);
     }
 
     pub fn list_symbols(&self, _path: &Path) -> Result<Option<Vec<DocumentSymbol>>> {
@@ -180,10 +268,11 @@ impl<'a> ParserContext<'a> {
 
             trace!("next capture name"; "name" => capture_name, "capture" => format!("{:?}", capture));
 
-            let (kind, node) = match capture_name {
+            let (mut kind, node) = match capture_name {
                 "const_qualifier" => (SymbolKind::CONSTANT, capture_iter.next().unwrap().node),
                 "ident" => (SymbolKind::VARIABLE, capture.node),
                 "func_ident" => (SymbolKind::FUNCTION, capture.node),
+                "param_ident" | "local_ident" => (SymbolKind::VARIABLE, capture.node),
                 "define_ident" => (SymbolKind::STRING, capture.node),
                 "struct_ident" => (SymbolKind::STRUCT, capture.node),
                 "field_list" => (SymbolKind::FIELD, capture_iter.next().unwrap().node),
@@ -204,12 +293,24 @@ impl<'a> ParserContext<'a> {
             let name = node.utf8_text(self.source.as_bytes()).unwrap().to_string();
 
             let fqname = SymbolName::new(&node, self.source.as_str(), node.id());
+            // local consts are matched as both consts and locals
+            if fqname_to_index.contains_key(&fqname) {
+                continue;
+            }
+
+            let mut detail = None;
+            if capture_name == "ident" || capture_name == "param_ident" || capture_name == "local_ident" {
+                if let Some((declaration_kind, declaration_detail)) = declaration_kind_and_detail(&node, &self.source) {
+                    kind = declaration_kind;
+                    detail = Some(declaration_detail);
+                }
+            }
 
             debug!("found symbol"; "node_name" => &name, "kind" => format!("{:?}", kind), "fqname" => &fqname);
 
             let child_symbol = DocumentSymbol {
                 name,
-                detail: None,
+                detail,
                 kind,
                 tags: None,
                 deprecated: None,
@@ -235,15 +336,50 @@ impl<'a> ParserContext<'a> {
             }
         }
 
-        let symbols = parent_child_vec
+        let mut symbols: Vec<DocumentSymbol> = parent_child_vec
             .iter()
             .filter(|tuple| tuple.0.is_none())
             .map(|tuple| tuple.1.clone())
             .collect();
+        symbols.extend(self.pragma_symbols());
 
         Ok(Some(symbols))
     }
 
+    // `#pragma` directives, which the driver ignores but pack loaders may read settings from. The grammar
+    // doesn't model them, so they're found line by line
+    fn pragma_symbols(&self) -> Vec<DocumentSymbol> {
+        self.source
+            .lines()
+            .enumerate()
+            .filter_map(|(line_num, line)| {
+                let cap = RE_PRAGMA.captures(line)?;
+                let (directive, body) = (cap.get(1).unwrap(), cap.get(2).unwrap());
+                if body.as_str().is_empty() {
+                    return None;
+                }
+
+                let line_num = line_num as u32;
+                Some(DocumentSymbol {
+                    name: body.as_str().to_string(),
+                    detail: Some("#pragma".into()),
+                    kind: SymbolKind::KEY,
+                    tags: None,
+                    deprecated: None,
+                    range: Range::new(
+                        Position::new(line_num, directive.start() as u32),
+                        Position::new(line_num, body.end() as u32),
+                    ),
+                    selection_range: Range::new(
+                        Position::new(line_num, body.start() as u32),
+                        Position::new(line_num, body.end() as u32),
+                    ),
+                    children: None,
+                })
+            })
+            .collect()
+    }
+
     pub fn find_definitions(&self, path: &Path, point: Position) -> Result<Option<Vec<Location>>> {
         let current_node = match self.find_node_at_point(point) {
             Some(node) => node,
@@ -255,13 +391,16 @@ impl<'a> ParserContext<'a> {
             None => return Ok(None),
         };
 
+        // the preprocessor expands macros before anything else, so a macro shadows any function or variable
+        if let Some(locations) = self.macro_definition_search(current_node) {
+            info!("found macro definitions"; "count" => locations.len(), "definitions" => format!("{:?}", locations));
+            return Ok(Some(locations));
+        }
+
         debug!("matching location lookup method for parent-child tuple"; "parent" => parent.kind(), "child" => current_node.kind());
 
         let locations = match (current_node.kind(), parent.kind()) {
-            (_, "call_expression") => {
-                let query_str = format!(find_function_def_str!(), current_node.utf8_text(self.source.as_bytes())?);
-                self.simple_global_search(path, &query_str)?
-            }
+            (_, "call_expression") => self.function_definition_search(path, current_node, parent)?,
             ("identifier", "argument_list")
             | ("identifier", "field_expression")
             | ("identifier", "binary_expression")
@@ -298,6 +437,15 @@ impl<'a> ParserContext<'a> {
         Ok(Some(locations))
     }
 
+    // resolves an identifier to the definitions of the macro of that name, if there is one
+    fn macro_definition_search(&self, ident: Node) -> Option<Vec<Location>> {
+        if ident.kind() != "identifier" {
+            return None;
+        }
+        let name = ident.utf8_text(self.source.as_bytes()).unwrap();
+        self.macros.definitions(name).cloned()
+    }
+
     fn tree_climbing_search(&self, path: &Path, start_node: Node) -> Result<Vec<Location>> {
         let mut locations = vec![];
 
@@ -351,6 +499,138 @@ impl<'a> ParserContext<'a> {
         Ok(locations)
     }
 
+    // finds the definitions of the called function. Of overloaded functions, only the overload matching the
+    // arguments of the call is returned if it can be told apart, else all overloads are
+    fn function_definition_search(&self, path: &Path, function: Node, call: Node) -> Result<Vec<Location>> {
+        let query_str = format!(find_function_def_str!(), function.utf8_text(self.source.as_bytes())?);
+        let query = Query::new(tree_sitter_glsl::language(), &query_str)?;
+        let mut query_cursor = QueryCursor::new();
+
+        let overloads: Vec<(Node, Vec<String>)> = query_cursor
+            .matches(&query, self.root_node(), self.source.as_bytes())
+            .flat_map(|m| m.captures.iter().map(|capture| capture.node).collect::<Vec<_>>())
+            .map(|ident| (ident, self.parameter_types(ident)))
+            .collect();
+
+        let argument_types: Vec<Option<String>> = match call.child_by_field_name("arguments") {
+            Some(arguments) => {
+                let mut cursor = arguments.walk();
+                arguments
+                    .named_children(&mut cursor)
+                    .filter(|arg| arg.kind() != "comment")
+                    .map(|arg| self.expression_type(path, arg))
+                    .collect()
+            }
+            None => vec![],
+        };
+
+        // arguments whose type can't be determined match any parameter type
+        let matching: Vec<&(Node, Vec<String>)> = overloads
+            .iter()
+            .filter(|(_, params)| {
+                params.len() == argument_types.len()
+                    && params
+                        .iter()
+                        .zip(&argument_types)
+                        .all(|(param, arg)| arg.as_ref().map_or(true, |arg| arg == param))
+            })
+            .collect();
+
+        // a prototype and the definition of an overload share their parameter types
+        let signatures: HashSet<&Vec<String>> = matching.iter().map(|(_, params)| params).collect();
+
+        debug!("resolving function overload";
+            "argument_types" => format!("{:?}", argument_types),
+            "overloads" => format!("{:?}", overloads.iter().map(|(_, params)| params).collect::<Vec<_>>()),
+            "matching_signatures" => signatures.len());
+
+        let selected: Vec<&(Node, Vec<String>)> = if signatures.len() == 1 {
+            matching
+        } else {
+            overloads.iter().collect()
+        };
+
+        Ok(selected
+            .into_iter()
+            .map(|(ident, _)| Location {
+                uri: Url::from_file_path(path).unwrap(),
+                range: node_range(ident),
+            })
+            .collect())
+    }
+
+    // returns the types of the parameters of the function whose declarator contains `ident`
+    fn parameter_types(&self, ident: Node) -> Vec<String> {
+        let parameters = match ident.parent().and_then(|declarator| declarator.child_by_field_name("parameters")) {
+            Some(parameters) => parameters,
+            None => return vec![],
+        };
+
+        let mut cursor = parameters.walk();
+        let types: Vec<String> = parameters
+            .named_children(&mut cursor)
+            .filter(|param| param.kind() == "parameter_declaration")
+            .filter_map(|param| param.child_by_field_name("type"))
+            .map(|t| t.utf8_text(self.source.as_bytes()).unwrap().to_string())
+            .collect();
+
+        // `f(void)` takes no parameters
+        if types == ["void"] {
+            return vec![];
+        }
+        types
+    }
+
+    // best-effort type of an expression, for literals, constructor calls and variables
+    fn expression_type(&self, path: &Path, expression: Node) -> Option<String> {
+        let text = expression.utf8_text(self.source.as_bytes()).unwrap();
+        match expression.kind() {
+            "number_literal" => {
+                let lowercase = text.to_lowercase();
+                let is_hex = lowercase.starts_with("0x");
+                Some(
+                    if lowercase.ends_with('u') {
+                        "uint"
+                    } else if lowercase.ends_with("lf") {
+                        "double"
+                    } else if !is_hex && (lowercase.contains('.') || lowercase.contains('e') || lowercase.ends_with('f')) {
+                        "float"
+                    } else {
+                        "int"
+                    }
+                    .to_string(),
+                )
+            }
+            "true" | "false" => Some("bool".to_string()),
+            "parenthesized_expression" => self.expression_type(path, expression.named_child(0)?),
+            "call_expression" => {
+                let function = expression
+                    .child_by_field_name("function")?
+                    .utf8_text(self.source.as_bytes())
+                    .unwrap();
+                RE_BUILTIN_TYPE.is_match(function).then(|| function.to_string())
+            }
+            "identifier" => {
+                let definition = self.tree_climbing_search(path, expression).ok()?.into_iter().next()?;
+                let point = Point {
+                    row: definition.range.start.line as usize,
+                    column: definition.range.start.character as usize,
+                };
+                let mut node = self.root_node().named_descendant_for_point_range(point, point)?;
+                while !matches!(node.kind(), "declaration" | "parameter_declaration") {
+                    node = node.parent()?;
+                }
+                Some(
+                    node.child_by_field_name("type")?
+                        .utf8_text(self.source.as_bytes())
+                        .unwrap()
+                        .to_string(),
+                )
+            }
+            _ => None,
+        }
+    }
+
     fn simple_global_search(&self, path: &Path, query_str: &str) -> Result<Vec<Location>> {
         let query = Query::new(tree_sitter_glsl::language(), query_str)?;
         let mut query_cursor = QueryCursor::new();
@@ -427,3 +707,265 @@ impl<'a> ParserContext<'a> {
         }
     }
 }
+
+fn node_range(node: &Node) -> Range {
+    Range {
+        start: Position {
+            line: node.start_position().row as u32,
+            character: node.start_position().column as u32,
+        },
+        end: Position {
+            line: node.end_position().row as u32,
+            character: node.end_position().column as u32,
+        },
+    }
+}
+
+// returns the symbol kind and detail, e.g. `uniform sampler2D`, of a declaration or parameter from its identifier
+fn declaration_kind_and_detail(ident: &Node, source: &str) -> Option<(SymbolKind, String)> {
+    // through any initializer or array size, to the declaration or parameter holding the type
+    let mut declaration = ident.parent()?;
+    while declaration.kind() == "init_declarator" || declaration.kind() == "array_declarator" {
+        declaration = declaration.parent()?;
+    }
+    let type_name = declaration.child_by_field_name("type")?.utf8_text(source.as_bytes()).unwrap();
+
+    let kind = if RE_OPAQUE_TYPE.is_match(type_name) {
+        SymbolKind::OBJECT
+    } else {
+        SymbolKind::VARIABLE
+    };
+
+    let mut detail = shader_interface::declaration_qualifiers(&declaration, source);
+    detail.push(type_name.to_string());
+
+    Some((kind, detail.join(" ")))
+}
+
+#[cfg(test)]
+mod navigation_test {
+    use std::fs;
+    use std::path::Path;
+
+    use rust_lsp::lsp_types::{DocumentSymbol, Location, Position, Range, SymbolKind};
+    use tempdir::TempDir;
+    use tree_sitter::Parser;
+    use url::Url;
+
+    use crate::navigation::ParserContext;
+
+    fn glsl_parser() -> Parser {
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_glsl::language()).unwrap();
+        parser
+    }
+
+    fn ranges(locations: Option<Vec<Location>>) -> Vec<Range> {
+        locations.unwrap().into_iter().map(|location| location.range).collect()
+    }
+
+    fn range(line: u32, start: u32, end: u32) -> Range {
+        Range::new(Position::new(line, start), Position::new(line, end))
+    }
+
+    fn find_symbol<'a>(symbols: &'a [DocumentSymbol], name: &str) -> &'a DocumentSymbol {
+        symbols
+            .iter()
+            .find(|symbol| symbol.name == name)
+            .unwrap_or_else(|| panic!("no symbol named {}", name))
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_declaration_symbol_kinds() {
+        let source = "uniform sampler2D colortex0;\nuniform mat4 gbufferModelView;\nvarying vec2 texcoord;\n";
+
+        let tmp_dir = TempDir::new("mcshader").unwrap();
+        let path = tmp_dir.path().join("final.fsh");
+        fs::write(&path, source).unwrap();
+
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_glsl::language()).unwrap();
+        let context = ParserContext::new(&mut parser, &path, "utf-8").unwrap();
+
+        let symbols: Vec<(String, SymbolKind, Option<String>)> = context
+            .list_symbols(&path)
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .map(|symbol| (symbol.name, symbol.kind, symbol.detail))
+            .collect();
+
+        assert_eq!(
+            symbols,
+            vec![
+                ("colortex0".into(), SymbolKind::OBJECT, Some("uniform sampler2D".into())),
+                ("gbufferModelView".into(), SymbolKind::VARIABLE, Some("uniform mat4".into())),
+                ("texcoord".into(), SymbolKind::VARIABLE, Some("varying vec2".into())),
+            ]
+        );
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_overloaded_function_definitions() {
+        let source = r#"float scale(float x) {
+    return x;
+}
+vec2 scale(vec2 v, float s) {
+    return v * s;
+}
+float pick(float a) {
+    return a;
+}
+int pick(int a) {
+    return a;
+}
+void main() {
+    vec2 uv = scale(vec2(1.0), 2.0);
+    float x = pick(1.0);
+    int i = 1;
+    int y = pick(i);
+    float z = pick(scale(1.0));
+}
+"#;
+
+        let tmp_dir = TempDir::new("mcshader").unwrap();
+        let path = tmp_dir.path().join("final.fsh");
+        fs::write(&path, source).unwrap();
+
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_glsl::language()).unwrap();
+        let context = ParserContext::new(&mut parser, &path, "utf-8").unwrap();
+
+        let definition_lines = |line: u32, character: u32| -> Vec<u32> {
+            context
+                .find_definitions(&path, Position::new(line, character))
+                .unwrap()
+                .unwrap()
+                .into_iter()
+                .map(|location| location.range.start.line)
+                .collect()
+        };
+
+        // selected by the number of arguments
+        assert_eq!(definition_lines(13, 15), vec![3]);
+        // selected by the type of a literal
+        assert_eq!(definition_lines(14, 15), vec![6]);
+        // selected by the type of a variable
+        assert_eq!(definition_lines(16, 13), vec![9]);
+        // the argument type is unknown, so both overloads are returned
+        assert_eq!(definition_lines(17, 15), vec![6, 9]);
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_function_navigation() {
+        let source = r#"float square(float x) {
+    return x * x;
+}
+void main() {
+    float a = square(2.0);
+    float b = square(a);
+}
+"#;
+
+        let path = Path::new("/shaders/final.fsh");
+        let mut parser = glsl_parser();
+        let context = ParserContext::from_source(&mut parser, path, source.to_string());
+
+        let definitions = context.find_definitions(path, Position::new(4, 16)).unwrap();
+        assert_eq!(ranges(definitions), vec![range(0, 6, 12)]);
+
+        let references = context.find_references(path, Position::new(0, 8)).unwrap();
+        assert_eq!(ranges(references), vec![range(4, 14, 20), range(5, 14, 20)]);
+
+        let symbols = context.list_symbols(path).unwrap().unwrap();
+        assert_eq!(find_symbol(&symbols, "square").kind, SymbolKind::FUNCTION);
+        assert_eq!(find_symbol(&symbols, "main").kind, SymbolKind::FUNCTION);
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_struct_symbols() {
+        let source = r#"struct Light {
+    vec3 color;
+    float intensity[2];
+};
+"#;
+
+        let path = Path::new("/shaders/lib/light.glsl");
+        let mut parser = glsl_parser();
+        let context = ParserContext::from_source(&mut parser, path, source.to_string());
+
+        let symbols = context.list_symbols(path).unwrap().unwrap();
+        let light = find_symbol(&symbols, "Light");
+        assert_eq!(light.kind, SymbolKind::STRUCT);
+        assert_eq!(light.range, range(0, 7, 12));
+
+        let fields = light.children.as_ref().unwrap();
+        assert_eq!(find_symbol(fields, "color").kind, SymbolKind::FIELD);
+        assert_eq!(find_symbol(fields, "intensity").range, range(2, 10, 19));
+        assert!(symbols.iter().all(|symbol| symbol.name != "color"));
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_function_symbol_children() {
+        let source = r#"vec3 shade(Light light, float shadow[4]) {
+    vec3 color = light.color;
+    float weights[4];
+    if (shadow[0] > 0.5) {
+        Light ambient;
+        color *= 0.5;
+    }
+    return color;
+}
+"#;
+
+        let path = Path::new("/shaders/lib/shade.glsl");
+        let mut parser = glsl_parser();
+        let context = ParserContext::from_source(&mut parser, path, source.to_string());
+
+        let symbols = context.list_symbols(path).unwrap().unwrap();
+        assert_eq!(symbols.len(), 1);
+
+        // parameters and locals, including those of nested blocks, belong to the function
+        let children = symbols[0].children.as_ref().unwrap();
+        let names: Vec<&str> = children.iter().map(|symbol| symbol.name.as_str()).collect();
+        assert_eq!(names, vec!["light", "shadow", "color", "weights", "ambient"]);
+
+        assert_eq!(find_symbol(children, "light").detail.as_deref(), Some("Light"));
+        assert_eq!(find_symbol(children, "shadow").range, range(0, 30, 36));
+        assert_eq!(find_symbol(children, "color").detail.as_deref(), Some("vec3"));
+        assert_eq!(find_symbol(children, "color").kind, SymbolKind::VARIABLE);
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_uniform_navigation() {
+        let source = r#"uniform sampler2D colortex0;
+uniform float frameTime;
+void main() {
+    vec4 color = texture2D(colortex0, vec2(frameTime));
+}
+"#;
+
+        let path = Path::new("/shaders/final.fsh");
+        let mut parser = glsl_parser();
+        let context = ParserContext::from_source(&mut parser, path, source.to_string());
+
+        let definitions = context.find_definitions(path, Position::new(3, 29)).unwrap();
+        assert_eq!(ranges(definitions), vec![range(0, 18, 27)]);
+
+        let definitions = context.find_definitions(path, Position::new(3, 45)).unwrap();
+        assert_eq!(ranges(definitions), vec![range(1, 14, 23)]);
+
+        let symbols = context.list_symbols(path).unwrap().unwrap();
+        let colortex0 = find_symbol(&symbols, "colortex0");
+        assert_eq!(colortex0.kind, SymbolKind::OBJECT);
+        assert_eq!(colortex0.detail.as_deref(), Some("uniform sampler2D"));
+        assert_eq!(find_symbol(&symbols, "frameTime").detail.as_deref(), Some("uniform float"));
+        assert!(symbols.iter().all(|symbol| symbol.name != "color"));
+    }
+}
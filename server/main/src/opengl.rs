@@ -1,8 +1,14 @@
+use std::cell::OnceCell;
+use std::collections::HashSet;
 use std::ffi::{CStr, CString};
 use std::ptr;
 
+use gl::types::GLenum;
 use slog_scope::info;
 
+use crate::vendor_cache::VendorCache;
+use crate::TreeType;
+
 #[cfg(test)]
 use mockall::automock;
 
@@ -10,10 +16,31 @@ use mockall::automock;
 pub trait ShaderValidator {
     fn validate(&self, tree_type: super::TreeType, source: &str) -> Option<String>;
     fn vendor(&self) -> String;
+    /// The shader types, e.g. `gl::COMPUTE_SHADER`, that the context is able to compile.
+    fn supported_stages(&self) -> HashSet<GLenum>;
+    /// The names of the extensions the context advertises, e.g. `GL_ARB_gpu_shader5`.
+    fn supported_extensions(&self) -> HashSet<String>;
+}
+
+/// Returns the OpenGL shader type used to compile a tree of the given type.
+pub fn shader_type(tree_type: &TreeType) -> GLenum {
+    match tree_type {
+        TreeType::Fragment => gl::FRAGMENT_SHADER,
+        TreeType::Vertex => gl::VERTEX_SHADER,
+        TreeType::Geometry => gl::GEOMETRY_SHADER,
+        TreeType::Compute => gl::COMPUTE_SHADER,
+    }
+}
+
+/// All shader types that the server validates.
+pub fn all_stages() -> HashSet<GLenum> {
+    HashSet::from([gl::FRAGMENT_SHADER, gl::VERTEX_SHADER, gl::GEOMETRY_SHADER, gl::COMPUTE_SHADER])
 }
 
 pub struct OpenGlContext {
     _ctx: glutin::Context<glutin::PossiblyCurrent>,
+    supported_stages: HashSet<GLenum>,
+    supported_extensions: HashSet<String>,
 }
 
 impl OpenGlContext {
@@ -29,19 +56,71 @@ impl OpenGlContext {
             gl_window
         };
 
-        let gl_ctx = OpenGlContext { _ctx: gl_window };
+        let supported_extensions = unsafe { Self::query_supported_extensions() };
+        let gl_ctx = OpenGlContext {
+            _ctx: gl_window,
+            supported_stages: unsafe { Self::query_supported_stages(&supported_extensions) },
+            supported_extensions,
+        };
 
         unsafe {
             info!(
                 "OpenGL device";
                 "vendor" => gl_ctx.vendor(),
                 "version" => String::from_utf8(CStr::from_ptr(gl::GetString(gl::VERSION) as *const _).to_bytes().to_vec()).unwrap(),
-                "renderer" => String::from_utf8(CStr::from_ptr(gl::GetString(gl::RENDERER) as *const _).to_bytes().to_vec()).unwrap()
+                "renderer" => String::from_utf8(CStr::from_ptr(gl::GetString(gl::RENDERER) as *const _).to_bytes().to_vec()).unwrap(),
+                "supported_stages" => format!("{:?}", gl_ctx.supported_stages),
+                "extension_count" => gl_ctx.supported_extensions.len()
             );
         }
         gl_ctx
     }
 
+    // geometry shaders are core since 3.2 and compute shaders since 4.3, or through GL_ARB_compute_shader
+    unsafe fn query_supported_stages(extensions: &HashSet<String>) -> HashSet<GLenum> {
+        let mut major: gl::types::GLint = 0;
+        let mut minor: gl::types::GLint = 0;
+        // not available before 3.0, in which case both are left at 0
+        gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+
+        let mut stages = HashSet::from([gl::FRAGMENT_SHADER, gl::VERTEX_SHADER]);
+        if (major, minor) >= (3, 2) {
+            stages.insert(gl::GEOMETRY_SHADER);
+        }
+
+        if (major, minor) >= (4, 3) || extensions.contains("GL_ARB_compute_shader") {
+            stages.insert(gl::COMPUTE_SHADER);
+        }
+        stages
+    }
+
+    unsafe fn query_supported_extensions() -> HashSet<String> {
+        let mut num_extensions: gl::types::GLint = 0;
+        // not available before 3.0, where the extensions are listed in a single space separated string instead
+        gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut num_extensions);
+        if num_extensions == 0 {
+            let extensions = gl::GetString(gl::EXTENSIONS);
+            if extensions.is_null() {
+                return HashSet::new();
+            }
+            return CStr::from_ptr(extensions as *const _)
+                .to_string_lossy()
+                .split_whitespace()
+                .map(String::from)
+                .collect();
+        }
+        (0..num_extensions)
+            .filter_map(|i| {
+                let extension = gl::GetStringi(gl::EXTENSIONS, i as gl::types::GLuint);
+                if extension.is_null() {
+                    return None;
+                }
+                Some(CStr::from_ptr(extension as *const _).to_string_lossy().into_owned())
+            })
+            .collect()
+    }
+
     unsafe fn compile_and_get_shader_log(&self, shader: gl::types::GLuint, source: &str) -> Option<String> {
         let mut success = i32::from(gl::FALSE);
         let c_str_frag = CString::new(source).unwrap();
@@ -73,32 +152,86 @@ impl OpenGlContext {
 impl ShaderValidator for OpenGlContext {
     fn validate(&self, tree_type: super::TreeType, source: &str) -> Option<String> {
         unsafe {
-            match tree_type {
-                crate::TreeType::Fragment => {
-                    // Fragment shader
-                    let fragment_shader = gl::CreateShader(gl::FRAGMENT_SHADER);
-                    self.compile_and_get_shader_log(fragment_shader, source)
-                }
-                crate::TreeType::Vertex => {
-                    // Vertex shader
-                    let vertex_shader = gl::CreateShader(gl::VERTEX_SHADER);
-                    self.compile_and_get_shader_log(vertex_shader, source)
-                }
-                crate::TreeType::Geometry => {
-                    // Geometry shader
-                    let geometry_shader = gl::CreateShader(gl::GEOMETRY_SHADER);
-                    self.compile_and_get_shader_log(geometry_shader, source)
-                }
-                crate::TreeType::Compute => {
-                    // Compute shader
-                    let compute_shader = gl::CreateShader(gl::COMPUTE_SHADER);
-                    self.compile_and_get_shader_log(compute_shader, source)
-                }
-            }
+            let shader = gl::CreateShader(shader_type(&tree_type));
+            self.compile_and_get_shader_log(shader, source)
         }
     }
 
     fn vendor(&self) -> String {
         unsafe { String::from_utf8(CStr::from_ptr(gl::GetString(gl::VENDOR) as *const _).to_bytes().to_vec()).unwrap() }
     }
+
+    fn supported_stages(&self) -> HashSet<GLenum> {
+        self.supported_stages.clone()
+    }
+
+    fn supported_extensions(&self) -> HashSet<String> {
+        self.supported_extensions.clone()
+    }
+}
+
+/// An `OpenGlContext` created the first time it is needed rather than on startup. The vendor is answered from the
+/// cache of the one detected on a previous run while it has one, so the compile log format is picked without
+/// creating a context or asking the driver again, and cached once detected otherwise.
+pub struct LazyOpenGlContext {
+    context: OnceCell<OpenGlContext>,
+    vendor_cache: VendorCache,
+}
+
+impl LazyOpenGlContext {
+    pub fn new(vendor_cache: VendorCache) -> LazyOpenGlContext {
+        LazyOpenGlContext {
+            context: OnceCell::new(),
+            vendor_cache,
+        }
+    }
+
+    fn context(&self) -> &OpenGlContext {
+        self.context.get_or_init(OpenGlContext::new)
+    }
+}
+
+impl ShaderValidator for LazyOpenGlContext {
+    fn validate(&self, tree_type: super::TreeType, source: &str) -> Option<String> {
+        self.context().validate(tree_type, source)
+    }
+
+    fn vendor(&self) -> String {
+        if let Some(vendor) = self.vendor_cache.read() {
+            return vendor;
+        }
+        let vendor = self.context().vendor();
+        self.vendor_cache.write(&vendor);
+        vendor
+    }
+
+    fn supported_stages(&self) -> HashSet<GLenum> {
+        self.context().supported_stages()
+    }
+
+    fn supported_extensions(&self) -> HashSet<String> {
+        self.context().supported_extensions()
+    }
+}
+
+#[cfg(test)]
+mod opengl_test {
+    use std::path::PathBuf;
+    use std::rc::Rc;
+
+    use crate::opengl::{LazyOpenGlContext, ShaderValidator};
+    use crate::vendor_cache::{MockCacheFs, VendorCache};
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_cached_vendor_spares_the_context() {
+        // read again each time, so clearing the cache takes effect without a restart
+        let mut fs = MockCacheFs::new();
+        fs.expect_read_to_string().times(2).returning(|_| Ok("ATI Technologies\n".into()));
+        let context = LazyOpenGlContext::new(VendorCache::new(Some(PathBuf::from("/cache/mcshader-lsp/vendor")), Rc::new(fs)));
+
+        assert_eq!(context.vendor(), "ATI Technologies");
+        assert_eq!(context.vendor(), "ATI Technologies");
+        assert!(context.context.get().is_none());
+    }
 }
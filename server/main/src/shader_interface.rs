@@ -0,0 +1,287 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Serialize;
+use tree_sitter::{Node, Tree};
+
+lazy_static! {
+    static ref RE_LAYOUT_LOCATION: Regex = Regex::new(r#"location\s*=\s*(\d+)"#).unwrap();
+    static ref RE_LAYOUT_INDEX: Regex = Regex::new(r#"\bindex\s*=\s*(\d+)"#).unwrap();
+    static ref RE_LAYOUT_BINDING: Regex = Regex::new(r#"\bbinding\s*=\s*(\d+)"#).unwrap();
+}
+
+/// A single global `uniform`, `in` or `out` declaration.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct InterfaceVariable {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_name: String,
+    // the explicit location from a `layout(location = N)` qualifier, if any
+    pub location: Option<u32>,
+    // the 0-indexed line of the declaration in the parsed source
+    pub line: usize,
+}
+
+/// A single global `uniform` or `buffer` block declaration.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct InterfaceBlock {
+    pub name: String,
+    // `uniform` or `buffer`
+    pub storage: String,
+    // the explicit binding point from a `layout(binding = N)` qualifier, if any
+    pub binding: Option<u32>,
+    // the member declarations, with comments removed and whitespace collapsed
+    pub members: String,
+    // the 0-indexed line of the block name in the parsed source
+    pub line: usize,
+}
+
+/// The global interface of a single shader stage, as declared in its (merged) source.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ShaderInterface {
+    pub uniforms: Vec<InterfaceVariable>,
+    pub inputs: Vec<InterfaceVariable>,
+    pub outputs: Vec<InterfaceVariable>,
+    pub blocks: Vec<InterfaceBlock>,
+}
+
+impl ShaderInterface {
+    /// Collects all global interface declarations from the tree. `varying` is treated as an output
+    /// or an input depending on whether `is_fragment` is set, as it is for pre-1.30 GLSL.
+    pub fn from_tree(tree: &Tree, source: &str, is_fragment: bool) -> ShaderInterface {
+        let mut interface = ShaderInterface::default();
+
+        for declaration in global_declarations(tree.root_node()) {
+            if let Some(block) = interface_block(&declaration, source) {
+                interface.blocks.push(block);
+                continue;
+            }
+
+            let qualifiers = declaration_qualifiers(&declaration, source);
+
+            let location = layout_location(&qualifiers);
+
+            let list = if qualifiers.iter().any(|q| q == "uniform") {
+                &mut interface.uniforms
+            } else if qualifiers.iter().any(|q| q == "in" || q == "attribute") {
+                &mut interface.inputs
+            } else if qualifiers.iter().any(|q| q == "out") {
+                &mut interface.outputs
+            } else if qualifiers.iter().any(|q| q == "varying") {
+                if is_fragment {
+                    &mut interface.inputs
+                } else {
+                    &mut interface.outputs
+                }
+            } else {
+                continue;
+            };
+
+            let type_name = match declaration.child_by_field_name("type") {
+                Some(t) => t.utf8_text(source.as_bytes()).unwrap().to_string(),
+                None => continue,
+            };
+
+            let mut declarator_cursor = declaration.walk();
+            for declarator in declaration.children_by_field_name("declarator", &mut declarator_cursor) {
+                let name = match declarator_identifier(declarator) {
+                    Some(ident) => ident.utf8_text(source.as_bytes()).unwrap().to_string(),
+                    None => continue,
+                };
+
+                list.push(InterfaceVariable {
+                    name,
+                    type_name: type_name.clone(),
+                    location,
+                    line: declarator.start_position().row,
+                });
+            }
+        }
+
+        interface
+    }
+}
+
+/// Returns all declarations at global scope, including those nested in preprocessor conditionals.
+pub fn global_declarations(root: Node) -> Vec<Node> {
+    let mut declarations = vec![];
+    let mut cursor = root.walk();
+    for child in root.children(&mut cursor) {
+        match child.kind() {
+            "declaration" => declarations.push(child),
+            "preproc_if" | "preproc_ifdef" | "preproc_else" | "preproc_elif" => declarations.extend(global_declarations(child)),
+            _ => (),
+        }
+    }
+    declarations
+}
+
+/// Returns the qualifiers of a declaration as individual words e.g. `["uniform"]` or `["layout(location = 0)", "out"]`.
+pub fn declaration_qualifiers(declaration: &Node, source: &str) -> Vec<String> {
+    let type_node = declaration.child_by_field_name("type");
+
+    let mut qualifiers = vec![];
+    let mut cursor = declaration.walk();
+    for child in declaration.children(&mut cursor) {
+        if Some(child) == type_node {
+            break;
+        }
+        let text = child.utf8_text(source.as_bytes()).unwrap();
+        if child.kind() == "layout_specification" {
+            qualifiers.push(text.to_string());
+        } else {
+            qualifiers.extend(text.split_whitespace().map(|s| s.to_string()));
+        }
+    }
+    qualifiers
+}
+
+/// Returns the explicit location from a `layout(location = N)` qualifier, if any.
+pub fn layout_location(qualifiers: &[String]) -> Option<u32> {
+    qualifiers.iter().find_map(|q| {
+        RE_LAYOUT_LOCATION
+            .captures(q)
+            .and_then(|c| c.get(1).unwrap().as_str().parse::<u32>().ok())
+    })
+}
+
+/// Returns the explicit blend index from a `layout(index = N)` qualifier, if any.
+pub fn layout_index(qualifiers: &[String]) -> Option<u32> {
+    qualifiers.iter().find_map(|q| {
+        RE_LAYOUT_INDEX
+            .captures(q)
+            .and_then(|c| c.get(1).unwrap().as_str().parse::<u32>().ok())
+    })
+}
+
+/// Returns the explicit binding point from a `layout(binding = N)` qualifier, if any.
+pub fn layout_binding(qualifiers: &[String]) -> Option<u32> {
+    qualifiers.iter().find_map(|q| {
+        RE_LAYOUT_BINDING
+            .captures(q)
+            .and_then(|c| c.get(1).unwrap().as_str().parse::<u32>().ok())
+    })
+}
+
+/// Reads a `uniform` or `buffer` block declaration e.g. `layout(std140) uniform Settings { float exposure; };`.
+/// Returns `None` for any other declaration.
+pub fn interface_block(declaration: &Node, source: &str) -> Option<InterfaceBlock> {
+    let mut cursor = declaration.walk();
+    let children: Vec<Node> = declaration.children(&mut cursor).collect();
+    let body = children.iter().position(|child| child.kind() == "field_declaration_list")?;
+    let name = children[..body]
+        .iter()
+        .rev()
+        .find(|child| child.kind() == "identifier" || child.kind() == "type_identifier")?;
+
+    let mut qualifiers = vec![];
+    for child in children.iter().take_while(|child| *child != name) {
+        let text = child.utf8_text(source.as_bytes()).unwrap();
+        if child.kind() == "layout_specification" {
+            qualifiers.push(text.to_string());
+        } else {
+            qualifiers.extend(text.split_whitespace().map(|s| s.to_string()));
+        }
+    }
+    let storage = qualifiers.iter().find(|q| *q == "uniform" || *q == "buffer")?.clone();
+
+    let mut member_cursor = children[body].walk();
+    let members: Vec<&str> = children[body]
+        .children(&mut member_cursor)
+        .filter(|member| member.kind() != "comment")
+        .map(|member| member.utf8_text(source.as_bytes()).unwrap())
+        .collect();
+
+    Some(InterfaceBlock {
+        name: name.utf8_text(source.as_bytes()).unwrap().to_string(),
+        binding: layout_binding(&qualifiers),
+        storage,
+        members: members.join(" ").split_whitespace().collect::<Vec<_>>().join(" "),
+        line: name.start_position().row,
+    })
+}
+
+/// Follows nested declarators (arrays, initializers etc) down to the declared identifier.
+pub fn declarator_identifier(node: Node) -> Option<Node> {
+    let mut node = node;
+    loop {
+        if node.kind() == "identifier" || node.kind() == "field_identifier" {
+            return Some(node);
+        }
+        node = node.child_by_field_name("declarator")?;
+    }
+}
+
+#[cfg(test)]
+mod shader_interface_test {
+    use pretty_assertions::assert_eq;
+    use serde_json::Value;
+
+    use crate::commands::shader_interface::ShaderInterfaceCommand;
+    use crate::commands::Invokeable;
+    use crate::test::{copy_to_and_set_root, new_temp_server};
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_shader_interface_command() {
+        let mut server = new_temp_server(None);
+        server.tree_sitter.borrow_mut().set_language(tree_sitter_glsl::language()).unwrap();
+
+        let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/08", &mut server);
+        server.build_initial_graph();
+
+        let command = ShaderInterfaceCommand {
+            graph: server.graph.clone(),
+            tree_sitter: server.tree_sitter.clone(),
+            config: server.config.clone(),
+        };
+
+        let program = tmp_path.join("shaders").join("composite");
+        let result = command
+            .run_command(&tmp_path, &[Value::String(program.to_str().unwrap().to_string())])
+            .unwrap();
+
+        let names = |stage: &str, kind: &str| -> Vec<(String, String, Value)> {
+            result[stage][kind]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|v| {
+                    (
+                        v["name"].as_str().unwrap().to_string(),
+                        v["type"].as_str().unwrap().to_string(),
+                        v["location"].clone(),
+                    )
+                })
+                .collect()
+        };
+
+        assert_eq!(
+            names("vertex", "uniforms"),
+            vec![("gbufferModelView".into(), "mat4".into(), Value::Null)]
+        );
+        assert_eq!(
+            names("vertex", "inputs"),
+            vec![
+                ("vaPosition".into(), "vec3".into(), Value::from(0)),
+                ("vaUV0".into(), "vec2".into(), Value::from(1))
+            ]
+        );
+        assert_eq!(names("vertex", "outputs"), vec![("texcoord".into(), "vec2".into(), Value::Null)]);
+
+        assert_eq!(
+            names("fragment", "uniforms"),
+            vec![
+                ("gbufferModelView".into(), "mat4".into(), Value::Null),
+                ("colortex0".into(), "sampler2D".into(), Value::Null),
+            ]
+        );
+        assert_eq!(names("fragment", "inputs"), vec![("texcoord".into(), "vec2".into(), Value::Null)]);
+        assert_eq!(
+            names("fragment", "outputs"),
+            vec![("outColor".into(), "vec4".into(), Value::from(0))]
+        );
+
+        assert!(result.get("geometry").is_none());
+        server.endpoint.request_shutdown();
+    }
+}
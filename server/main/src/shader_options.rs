@@ -0,0 +1,197 @@
+use std::fs;
+use std::path::Path;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use slog_scope::{debug, warn};
+
+use crate::TreeType;
+
+lazy_static! {
+    // option macros are by convention upper case, which sets them apart from the properties
+    // Optifine and Iris define themselves such as `clouds` or `oldLighting`
+    static ref RE_OPTION_NAME: Regex = Regex::new(r#"^[A-Z_][A-Z0-9_]*$"#).unwrap();
+}
+
+pub const PROPERTIES_FILE: &str = "shaders.properties";
+
+/// Default states of the pack's `#define` options, as set in `shaders/shaders.properties`.
+/// Only plain `NAME=value` entries with an upper case macro name are read: `NAME=true` defines
+/// `NAME`, `NAME=<value>` defines it as `<value>` and `NAME=false` is ignored, as an option that
+/// the source defines can't be undefined ahead of it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ShaderOptions {
+    defines: Vec<(String, Option<String>)>,
+}
+
+impl ShaderOptions {
+    /// Reads the options from `shaders/shaders.properties` in `root`, if it exists.
+    pub fn load(root: &Path) -> ShaderOptions {
+        let path = root.join("shaders").join(PROPERTIES_FILE);
+        match fs::read_to_string(&path) {
+            Ok(content) => ShaderOptions::parse(&content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => ShaderOptions::default(),
+            Err(e) => {
+                warn!("failed to read shader options"; "path" => path.to_str().unwrap(), "error" => e.to_string());
+                ShaderOptions::default()
+            }
+        }
+    }
+
+    pub fn parse(content: &str) -> ShaderOptions {
+        let mut defines = vec![];
+
+        // values may be continued on the next line with a trailing backslash
+        let content = content.replace("\\\r\n", "").replace("\\\n", "");
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+                continue;
+            }
+
+            let (name, value) = match line.split_once('=') {
+                Some((name, value)) => (name.trim(), value.trim()),
+                None => continue,
+            };
+            if !RE_OPTION_NAME.is_match(name) {
+                continue;
+            }
+
+            match value {
+                "true" => defines.push((name.to_string(), None)),
+                "false" => (),
+                value => defines.push((name.to_string(), Some(value.to_string()))),
+            }
+        }
+
+        debug!("parsed shader options"; "defines" => format!("{:?}", defines));
+        ShaderOptions { defines }
+    }
+
+    /// Returns these options preceded by the macro that Iris and Optifine define for the stage being compiled,
+    /// such as `FRAGMENT_SHADER`, which shaders commonly gate stage-specific code on.
+    pub fn for_stage(&self, tree_type: TreeType) -> ShaderOptions {
+        let mut defines = vec![(stage_define(tree_type).to_string(), None)];
+        defines.extend(self.defines.iter().cloned());
+        ShaderOptions { defines }
+    }
+
+    /// Inserts the option defines into a merged shader, after its `#version` directive. A `#line` directive
+    /// follows them so that line numbers reported for the top-level file remain correct.
+    pub fn inject(&self, merged: &str) -> String {
+        if self.defines.is_empty() {
+            return merged.to_string();
+        }
+
+        let version_line = merged.lines().position(|line| line.trim_start().starts_with("#version"));
+        let insert_offset = match version_line {
+            Some(line) => merged.split_inclusive('\n').take(line + 1).map(|l| l.len()).sum(),
+            None => 0,
+        };
+
+        let mut injected = String::with_capacity(merged.len() + self.defines.len() * 32);
+        injected.push_str(&merged[..insert_offset]);
+        if !injected.is_empty() && !injected.ends_with('\n') {
+            injected.push('\n');
+        }
+        for (name, value) in &self.defines {
+            match value {
+                Some(value) => injected.push_str(&format!("#define {} {}\n", name, value)),
+                None => injected.push_str(&format!("#define {}\n", name)),
+            }
+        }
+        // +2 because the version line is 0 indexed but #line is 1 indexed and references the *following* line
+        injected.push_str(&format!("#line {} 0\n", version_line.map_or(1, |line| line + 2)));
+        injected.push_str(&merged[insert_offset..]);
+        injected
+    }
+}
+
+/// Returns the macro defined when compiling a shader of the given stage.
+pub fn stage_define(tree_type: TreeType) -> &'static str {
+    match tree_type {
+        TreeType::Fragment => "FRAGMENT_SHADER",
+        TreeType::Vertex => "VERTEX_SHADER",
+        TreeType::Geometry => "GEOMETRY_SHADER",
+        TreeType::Compute => "COMPUTE_SHADER",
+    }
+}
+
+#[cfg(test)]
+mod shader_options_test {
+    use pretty_assertions::assert_eq;
+
+    use crate::opengl::{self, MockShaderValidator};
+    use crate::shader_options::ShaderOptions;
+    use crate::test::{copy_to_and_set_root, new_temp_server, RecordingValidator};
+    use crate::TreeType;
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_inject_options() {
+        let options = ShaderOptions::parse("# comment\nclouds=off\nBLOOM=true\nSSAO=false\nQUALITY=\\\n  2\n");
+
+        let merged = "#version 120\n#ifdef BLOOM\n#endif\n";
+        assert_eq!(
+            options.inject(merged),
+            "#version 120\n#define BLOOM\n#define QUALITY 2\n#line 2 0\n#ifdef BLOOM\n#endif\n"
+        );
+
+        assert_eq!(ShaderOptions::default().inject(merged), merged);
+
+        assert_eq!(
+            options.for_stage(TreeType::Vertex).inject(merged),
+            "#version 120\n#define VERTEX_SHADER\n#define BLOOM\n#define QUALITY 2\n#line 2 0\n#ifdef BLOOM\n#endif\n"
+        );
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_stage_defines_validated() {
+        let validator = RecordingValidator::new(None);
+        let validated = validator.validated.clone();
+        let mut server = new_temp_server(Some(Box::new(validator)));
+
+        let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/30", &mut server);
+        server.build_initial_graph();
+
+        // the program's code is gated on the stage macro, each stage must only get its own
+        server
+            .lint(&tmp_path.join("shaders").join("program").join("composite.glsl"))
+            .unwrap();
+
+        let mut validated = validated.borrow().clone();
+        validated.sort_by_key(|(tree_type, _)| *tree_type == TreeType::Vertex);
+        assert_eq!(validated.len(), 2);
+
+        let (fragment, vertex) = (&validated[0], &validated[1]);
+        assert_eq!(fragment.0, TreeType::Fragment);
+        assert!(fragment.1.starts_with("#version 120\n#define FRAGMENT_SHADER\n#line 2 0\n"));
+        assert!(!fragment.1.contains("#define VERTEX_SHADER"));
+        assert_eq!(vertex.0, TreeType::Vertex);
+        assert!(vertex.1.starts_with("#version 120\n#define VERTEX_SHADER\n#line 2 0\n"));
+        assert!(!vertex.1.contains("#define FRAGMENT_SHADER"));
+
+        server.endpoint.request_shutdown();
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_options_validated() {
+        let mut mockgl = MockShaderValidator::new();
+        mockgl.expect_supported_stages().returning(opengl::all_stages);
+        mockgl
+            .expect_validate()
+            .withf(|_, source| source.starts_with("#version 120\n#define FRAGMENT_SHADER\n#define BLOOM\n#line 2 0\n"))
+            .times(1)
+            .returning(|_, _| None);
+        let mut server = new_temp_server(Some(Box::new(mockgl)));
+
+        let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/13", &mut server);
+        server.build_initial_graph();
+
+        server.lint(&tmp_path.join("shaders").join("final.fsh")).unwrap();
+
+        server.endpoint.request_shutdown();
+    }
+}
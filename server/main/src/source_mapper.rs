@@ -1,8 +1,14 @@
 use std::{collections::HashMap, fmt::Display};
 
+use lazy_static::lazy_static;
 use petgraph::graph::NodeIndex;
+use regex::Regex;
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+lazy_static! {
+    static ref RE_LINE_DIRECTIVE: Regex = Regex::new(r#"^\s*#line\s+(\d+)\s+(\d+)"#).unwrap();
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct SourceNum(usize);
 
 impl Display for SourceNum {
@@ -49,4 +55,61 @@ impl SourceMapper {
     pub fn get_node(&self, num: SourceNum) -> NodeIndex {
         self.reverse_mapping[num.0]
     }
+
+    /// Like `get_node`, but for source numbers that may not have been assigned, e.g. ones reported by the driver.
+    pub fn find_node(&self, num: SourceNum) -> Option<NodeIndex> {
+        self.reverse_mapping.get(num.0).copied()
+    }
+
+    /// Returns the nodes that have been assigned a source number, indexed by their source number.
+    pub fn nodes(&self) -> &[NodeIndex] {
+        &self.reverse_mapping
+    }
+}
+
+/// Maps each line of a merged source back to the source number and 0-indexed line it came from,
+/// by following the `#line` directives inserted while merging. Lines before the first directive
+/// belong to the top-level file, which is always source number 0. The directives themselves map
+/// to `None`.
+pub fn merged_line_origins(merged: &str) -> Vec<Option<(SourceNum, usize)>> {
+    let mut origins = Vec::new();
+    let mut current = (SourceNum(0), 0);
+    for line in merged.lines() {
+        match RE_LINE_DIRECTIVE.captures(line) {
+            Some(cap) => {
+                // #line is 1 indexed and references the *following* line
+                let line_num = cap.get(1).unwrap().as_str().parse::<usize>().unwrap_or(1);
+                let source_num = cap.get(2).unwrap().as_str().parse::<usize>().unwrap_or(0);
+                current = (SourceNum(source_num), line_num.saturating_sub(1));
+                origins.push(None);
+            }
+            None => {
+                origins.push(Some(current));
+                current.1 += 1;
+            }
+        }
+    }
+    origins
+}
+
+#[cfg(test)]
+mod source_mapper_test {
+    use crate::source_mapper::{merged_line_origins, SourceNum};
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_merged_line_origins() {
+        let merged = "#version 120\n#line 1 1 // /lib/a.glsl\nfloat a() {}\n#line 3 0 // /final.fsh\nvoid main() {}\n";
+
+        assert_eq!(
+            merged_line_origins(merged),
+            vec![
+                Some((SourceNum(0), 0)),
+                None,
+                Some((SourceNum(1), 0)),
+                None,
+                Some((SourceNum(0), 2)),
+            ]
+        );
+    }
 }
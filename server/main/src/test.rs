@@ -31,19 +31,131 @@ impl io::Write for StdoutNewline {
     }
 }
 
+// collects everything written to the client, for tests asserting on what the server sends unprompted
+#[derive(Clone, Default)]
+struct SharedOutput(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+impl io::Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        io::Write::write(&mut *self.0.lock().unwrap(), buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
 pub fn new_temp_server(opengl_context: Option<Box<dyn opengl::ShaderValidator>>) -> MinecraftShaderLanguageServer {
     let endpoint = LSPEndpoint::create_lsp_output_with_output_stream(|| StdoutNewline { s: Box::new(io::sink()) });
 
-    let context = opengl_context.unwrap_or_else(|| Box::new(opengl::MockShaderValidator::new()));
+    let context = opengl_context.unwrap_or_else(|| {
+        let mut mockgl = opengl::MockShaderValidator::new();
+        mockgl.expect_supported_stages().returning(opengl::all_stages);
+        Box::new(mockgl)
+    });
 
-    MinecraftShaderLanguageServer {
+    let mut server = MinecraftShaderLanguageServer {
         endpoint,
         graph: Rc::new(RefCell::new(graph::CachedStableGraph::new())),
         root: "".into(),
+        config: Rc::new(RefCell::new(configuration::Configuration::default())),
+        toplevel_files: Rc::new(RefCell::new(toplevel::TopLevelFiles::default())),
+        diagnostics: Rc::new(RefCell::new(HashMap::new())),
+        notified_unsupported_stages: Rc::new(RefCell::new(HashSet::new())),
+        unresolved_includes: Rc::new(RefCell::new(HashMap::new())),
+        open_files: Rc::new(RefCell::new(HashSet::new())),
+        file_cache: Rc::new(RefCell::new(file_cache::FileCache::default())),
+        diagnostics_regex: Rc::new(RefCell::new(None)),
         command_provider: None,
         opengl_context: context.into(),
+        vendor_cache: vendor_cache::VendorCache::default(),
         log_guard: None,
         tree_sitter: Rc::new(RefCell::new(Parser::new())),
+    };
+    server.register_commands();
+    server
+}
+
+// a server parsing with tree-sitter whose validator reports nothing, for tests of the internal diagnostics
+fn linting_server() -> MinecraftShaderLanguageServer {
+    let mut mockgl = opengl::MockShaderValidator::new();
+    mockgl.expect_validate().returning(|_, _| None);
+    mockgl.expect_supported_stages().returning(opengl::all_stages);
+    let server = new_temp_server(Some(Box::new(mockgl)));
+    server.tree_sitter.borrow_mut().set_language(tree_sitter_glsl::language()).unwrap();
+    server
+}
+
+/// A server linting with tree-sitter and a validator reporting nothing, whose workspace is a copy of the fixture at
+/// `test_path` with the include graph built.
+pub fn server_for_fixture(test_path: &str) -> (MinecraftShaderLanguageServer, Rc<TempDir>, PathBuf) {
+    let mut server = linting_server();
+    let (tmp_dir, tmp_path) = copy_to_and_set_root(test_path, &mut server);
+    server.build_initial_graph();
+    (server, tmp_dir, tmp_path)
+}
+
+/// A server linting with tree-sitter and a validator reporting nothing, whose workspace holds the given files, each
+/// a path relative to the `shaders` directory and its source, with the include graph built.
+pub fn server_for_sources(files: &[(&str, &str)]) -> (MinecraftShaderLanguageServer, Rc<TempDir>, PathBuf) {
+    let mut server = linting_server();
+    let tmp_dir = Rc::new(TempDir::new("mcshader").unwrap());
+    let tmp_path: PathBuf = tmp_dir.path().into();
+    for (path, source) in files {
+        let path = tmp_path.join("shaders").join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, source).unwrap();
+    }
+
+    server.root = tmp_path.clone();
+    server.build_initial_graph();
+    (server, tmp_dir, tmp_path)
+}
+
+// runs `command` as the client would through `workspace/executeCommand`, without a work done token
+fn run_server_command(
+    server: &MinecraftShaderLanguageServer, command: &dyn commands::Invokeable, arguments: Vec<Value>,
+) -> anyhow::Result<Value> {
+    let params = ExecuteCommandParams {
+        command: String::new(),
+        arguments,
+        work_done_progress_params: Default::default(),
+    };
+    command.run_server_command(server, &params)
+}
+
+/// A `ShaderValidator` that records the stage and source of every shader it is asked to validate, answering
+/// each with the same compile log. Lets tests assert exactly what the server hands the driver.
+pub struct RecordingValidator {
+    log: Option<String>,
+    pub validated: Rc<RefCell<Vec<(TreeType, String)>>>,
+}
+
+impl RecordingValidator {
+    pub fn new(log: Option<String>) -> RecordingValidator {
+        RecordingValidator {
+            log,
+            validated: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+}
+
+impl opengl::ShaderValidator for RecordingValidator {
+    fn validate(&self, tree_type: TreeType, source: &str) -> Option<String> {
+        self.validated.borrow_mut().push((tree_type, source.to_string()));
+        self.log.clone()
+    }
+
+    fn vendor(&self) -> String {
+        "NVIDIA Corporation".into()
+    }
+
+    fn supported_stages(&self) -> HashSet<gl::types::GLenum> {
+        opengl::all_stages()
+    }
+
+    fn supported_extensions(&self) -> HashSet<String> {
+        HashSet::new()
     }
 }
 
@@ -187,6 +299,99 @@ fn test_01_initialize() {
     assert_eq!(server.graph.borrow().graph.edge_weight(edge).unwrap().line, 2);
 }
 
+#[allow(deprecated)]
+#[test]
+#[logging_macro::log_scope]
+fn test_shaders_directory_as_root() {
+    let mut server = new_temp_server(None);
+
+    let (_tmp_dir, tmp_path) = copy_to_tmp_dir("./testdata/01");
+
+    let initialize_params = InitializeParams {
+        process_id: None,
+        root_path: None,
+        root_uri: Some(Url::from_directory_path(tmp_path.join("shaders")).unwrap()),
+        client_info: None,
+        initialization_options: None,
+        capabilities: ClientCapabilities {
+            workspace: None,
+            text_document: None,
+            experimental: None,
+            window: None,
+            general: Option::None,
+        },
+        trace: None,
+        workspace_folders: None,
+        locale: Option::None,
+    };
+
+    let on_response = |resp: Option<Response>| match resp.unwrap().result_or_error {
+        ResponseResult::Result(_) => {}
+        ResponseResult::Error(e) => panic!("expected ResponseResult::Result(..), got {:?}", e),
+    };
+
+    let completable = MethodCompletable::new(ResponseCompletable::new(Some(Id::Number(1)), Box::new(on_response)));
+    server.initialize(initialize_params, completable);
+    server.endpoint.request_shutdown();
+
+    // the pack is the shaders directory's parent
+    assert_eq!(server.root, tmp_path);
+
+    let mut graph = server.graph.borrow_mut();
+    let final_idx = graph.find_node(&tmp_path.join("shaders").join("final.fsh")).unwrap();
+    let common_idx = graph.find_node(&tmp_path.join("shaders").join("common.glsl")).unwrap();
+    assert_eq!(graph.child_node_indexes(final_idx).collect::<Vec<_>>(), vec![common_idx]);
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_05_validated_source() {
+    let validator = RecordingValidator::new(None);
+    let validated = validator.validated.clone();
+    let mut server = new_temp_server(Some(Box::new(validator)));
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/05", &mut server);
+    server.build_initial_graph();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    server.lint(&final_path).unwrap();
+
+    let path = |file: &str| tmp_path.join("shaders").join(file).to_str().unwrap().replace('\\', "\\\\");
+    let expected = format!(
+        "#version 120\n\
+        #define FRAGMENT_SHADER\n\
+        #line 2 0\n\
+        \n\
+        #line 1 1 // {common}\n\
+        float test() {{\n\
+        \treturn 0.5;\n\
+        }}\n\
+        #line 4 0 // {root}\n\
+        #line 1 2 // {banana}\n\
+        #line 1 3 // {burger}\n\
+        void dont() {{\n\
+        \n\
+        }}\n\
+        #line 2 2 // {banana}\n\
+        \n\
+        void ok() {{\n\
+        \t\n\
+        }}\n\
+        #line 5 0 // {root}\n\
+        \n\
+        void main() {{\n\
+        \tgl_FragColor = vec4(0.0);\n\
+        }}",
+        common = path("common.glsl"),
+        banana = path("test/banana.glsl"),
+        burger = path("test/burger.glsl"),
+        root = path("final.fsh"),
+    );
+    assert_eq!(*validated.borrow(), vec![(TreeType::Fragment, expected)]);
+
+    server.endpoint.request_shutdown();
+}
+
 #[allow(deprecated)]
 #[test]
 #[logging_macro::log_scope]
@@ -279,3 +484,1948 @@ fn test_05_initialize() {
         assert!(contains, "doesn't contain ({:?}, {:?})", first, second);
     }
 }
+
+#[test]
+#[logging_macro::log_scope]
+fn test_orphan_include_diagnostics() {
+    let mut server = new_temp_server(None);
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/36", &mut server);
+    server.build_initial_graph();
+
+    let draft_path = tmp_path.join("shaders").join("lib").join("draft.glsl");
+    let draft_url = Url::from_file_path(&draft_path).unwrap();
+
+    server.did_save_text_document(DidSaveTextDocumentParams {
+        text_document: TextDocumentIdentifier { uri: draft_url.clone() },
+        text: None,
+    });
+
+    // nothing includes the file, so it isn't validated, but the server's own checks still run on it
+    let diagnostics = server.diagnostics.borrow();
+    let mut codes: Vec<_> = diagnostics[&draft_url].iter().map(|d| d.code.clone().unwrap()).collect();
+    codes.sort_by_key(|code| format!("{:?}", code));
+    assert_eq!(
+        codes,
+        vec![
+            NumberOrString::String("unbalanced-delimiter".into()),
+            NumberOrString::String("unterminated-comment".into())
+        ]
+    );
+    let unbalanced = diagnostics[&draft_url].iter().find(|d| d.message == "`{` is never closed").unwrap();
+    assert_eq!(unbalanced.range, Range::new(Position::new(0, 21), Position::new(0, 22)));
+    drop(diagnostics);
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_open_republishes_diagnostics() {
+    let mut server = new_temp_server(None);
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/36", &mut server);
+    server.config.borrow_mut().diagnostics_output = Some("diagnostics.json".into());
+    server.build_initial_graph();
+
+    let draft_path = tmp_path.join("shaders").join("lib").join("draft.glsl");
+    let draft_url = Url::from_file_path(&draft_path).unwrap();
+    let report_path = tmp_path.join("diagnostics.json");
+
+    server.did_save_text_document(DidSaveTextDocumentParams {
+        text_document: TextDocumentIdentifier { uri: draft_url.clone() },
+        text: None,
+    });
+    let published = server.diagnostics.borrow()[&draft_url].clone();
+    assert!(!published.is_empty());
+    fs::remove_file(&report_path).unwrap();
+
+    // the library isn't included anywhere, so opening it doesn't lint it, but what was found before is published again
+    server.did_open_text_document(DidOpenTextDocumentParams {
+        text_document: TextDocumentItem {
+            uri: draft_url.clone(),
+            language_id: "glsl".into(),
+            version: 1,
+            text: fs::read_to_string(&draft_path).unwrap(),
+        },
+    });
+    assert!(server.is_orphan_library(&draft_path));
+    let report: Value = serde_json::from_str(&fs::read_to_string(&report_path).unwrap()).unwrap();
+    assert_eq!(report[draft_path.to_str().unwrap()], serde_json::to_value(&published).unwrap());
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_orphan_library_symbols() {
+    let mut server = new_temp_server(Some(Box::new(RecordingValidator::new(None))));
+    let output = SharedOutput::default();
+    let sink = output.clone();
+    server.endpoint = LSPEndpoint::create_lsp_output_with_output_stream(move || sink.clone());
+    server.tree_sitter.borrow_mut().set_language(tree_sitter_glsl::language()).unwrap();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/07", &mut server);
+    server.build_initial_graph();
+
+    // the library isn't included anywhere, so opening it isn't linted, while opening a shader still is
+    let lib_path = tmp_path.join("shaders").join("lib").join("math.glsl");
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    for path in [&lib_path, &final_path] {
+        server.did_open_text_document(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem {
+                uri: Url::from_file_path(path).unwrap(),
+                language_id: "glsl".into(),
+                version: 1,
+                text: fs::read_to_string(path).unwrap(),
+            },
+        });
+    }
+
+    let params = DocumentSymbolParams {
+        text_document: TextDocumentIdentifier {
+            uri: Url::from_file_path(&lib_path).unwrap(),
+        },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+
+    let on_response = |resp: Option<Response>| {
+        assert!(resp.is_some());
+        let respu = resp.unwrap();
+        match respu.result_or_error {
+            ResponseResult::Result(symbols) => {
+                let names: Vec<&str> = symbols
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|symbol| symbol["name"].as_str().unwrap())
+                    .collect();
+                assert_eq!(names, vec!["PI", "square"]);
+            }
+            ResponseResult::Error(e) => {
+                panic!("expected ResponseResult::Result(..), got {:?}", e)
+            }
+        }
+    };
+
+    let completable = MethodCompletable::new(ResponseCompletable::new(Some(Id::Number(1)), Box::new(on_response)));
+    server.document_symbols(params, completable);
+    server.endpoint.request_shutdown();
+
+    let sent = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+    assert!(sent.contains(Url::from_file_path(&final_path).unwrap().as_str()));
+    assert!(!sent.contains(Url::from_file_path(&lib_path).unwrap().as_str()));
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_macro_definition_in_include() {
+    let mut server = new_temp_server(None);
+    server.tree_sitter.borrow_mut().set_language(tree_sitter_glsl::language()).unwrap();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/19", &mut server);
+    server.build_initial_graph();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let macros_path = tmp_path.join("shaders").join("lib").join("macros.glsl");
+
+    // on the `SQ` of `SQ(2.0)`
+    let params = TextDocumentPositionParams {
+        text_document: TextDocumentIdentifier {
+            uri: Url::from_file_path(&final_path).unwrap(),
+        },
+        position: Position::new(4, 11),
+    };
+
+    let on_response = |resp: Option<Response>| {
+        assert!(resp.is_some());
+        let respu = resp.unwrap();
+        match respu.result_or_error {
+            ResponseResult::Result(locations) => {
+                let locations: Vec<Location> = serde_json::from_value(locations).unwrap();
+                assert_eq!(
+                    locations,
+                    vec![Location {
+                        uri: Url::from_file_path(&macros_path).unwrap(),
+                        range: Range::new(Position::new(1, 8), Position::new(1, 10)),
+                    }]
+                );
+            }
+            ResponseResult::Error(e) => {
+                panic!("expected ResponseResult::Result(..), got {:?}", e)
+            }
+        }
+    };
+
+    let completable = MethodCompletable::new(ResponseCompletable::new(Some(Id::Number(1)), Box::new(on_response)));
+    server.goto_definition(params, completable);
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_workspace_settings() {
+    let settings = serde_json::json!({ "maxDiagnosticsPerFile": 5, "maxMergedBytes": 1048576 });
+
+    // the first pack limits its merged shaders to a few bytes
+    let validator = RecordingValidator::new(None);
+    let limited_validated = validator.validated.clone();
+    let mut limited = new_temp_server(Some(Box::new(validator)));
+    let (_limited_dir, limited_path) = copy_to_and_set_root("./testdata/01", &mut limited);
+    fs::write(limited_path.join(".mcshaderrc"), r#"{ "maxMergedBytes": 16 }"#).unwrap();
+    limited.apply_configuration(&settings);
+    limited.build_initial_graph();
+
+    let validator = RecordingValidator::new(None);
+    let unlimited_validated = validator.validated.clone();
+    let mut unlimited = new_temp_server(Some(Box::new(validator)));
+    let (_unlimited_dir, unlimited_path) = copy_to_and_set_root("./testdata/01", &mut unlimited);
+    unlimited.apply_configuration(&settings);
+    unlimited.build_initial_graph();
+
+    assert_eq!(limited.config.borrow().max_merged_bytes, 16);
+    assert_eq!(limited.config.borrow().max_diagnostics_per_file, 5);
+    assert_eq!(unlimited.config.borrow().max_merged_bytes, 1048576);
+
+    limited.lint(&limited_path.join("shaders").join("final.fsh")).unwrap();
+    unlimited.lint(&unlimited_path.join("shaders").join("final.fsh")).unwrap();
+    assert!(limited_validated.borrow().is_empty());
+    assert_eq!(unlimited_validated.borrow().len(), 1);
+
+    limited.endpoint.request_shutdown();
+    unlimited.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_internal_severity() {
+    let mut server = new_temp_server(None);
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/22", &mut server);
+    server.apply_configuration(&serde_json::json!({ "internalSeverity": { "missing-include": "warning" } }));
+    server.build_initial_graph();
+
+    let final_url = Url::from_file_path(tmp_path.join("shaders").join("final.fsh")).unwrap();
+
+    let diagnostics = server.lint(&tmp_path.join("shaders").join("final.fsh")).unwrap();
+    assert_eq!(diagnostics[&final_url].len(), 1);
+    assert_eq!(
+        diagnostics[&final_url][0].code,
+        Some(NumberOrString::String("missing-include".into()))
+    );
+    assert_eq!(diagnostics[&final_url][0].severity, Some(DiagnosticSeverity::WARNING));
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_diagnostics_regex() {
+    let validator = RecordingValidator::new(Some("0:6: error: 'gl_FragColor' : not an array".into()));
+    let mut server = new_temp_server(Some(Box::new(validator)));
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/01", &mut server);
+    server.build_initial_graph();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let final_url = Url::from_file_path(&final_path).unwrap();
+
+    // the log doesn't match the vendor's format, so it is reported whole on the first line
+    let diagnostics = server.lint(&final_path).unwrap();
+    assert_eq!(diagnostics[&final_url][0].range.start.line, 0);
+
+    server.apply_configuration(&serde_json::json!({
+        "diagnosticsRegex": r#"^(?P<filepath>\d+):(?P<linenum>\d+): (?P<severity>error|warning): (?P<output>.+)$"#
+    }));
+    let diagnostics = server.lint(&final_path).unwrap();
+    assert_eq!(diagnostics[&final_url].len(), 1);
+    assert_eq!(diagnostics[&final_url][0].range.start.line, 5);
+    assert_eq!(diagnostics[&final_url][0].message, "'gl_FragColor' : not an array");
+
+    // without an output group the regex is rejected, and the vendor's is used again
+    server.apply_configuration(&serde_json::json!({ "diagnosticsRegex": r#"^(?P<linenum>\d+):"# }));
+    assert!(server.diagnostics_regex.borrow().is_none());
+    let diagnostics = server.lint(&final_path).unwrap();
+    assert_eq!(diagnostics[&final_url][0].range.start.line, 0);
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_include_aliases() {
+    let mut server = new_temp_server(None);
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/42", &mut server);
+    server.config.borrow_mut().include_aliases.insert("@core".into(), "lib/core".into());
+    server.build_initial_graph();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let composite_path = tmp_path.join("shaders").join("composite.fsh");
+    let math_path = tmp_path.join("shaders").join("lib").join("core").join("math.glsl");
+
+    let mut graph = server.graph.borrow_mut();
+    let final_idx = graph.find_node(&final_path).unwrap();
+    let math_idx = graph.find_node(&math_path).unwrap();
+    assert_eq!(graph.child_node_indexes(final_idx).collect::<Vec<_>>(), vec![math_idx]);
+    drop(graph);
+
+    // @fx isn't configured, so its include is looked for next to composite.fsh
+    let diagnostics = server.lint(&composite_path).unwrap();
+    let composite_diagnostics = &diagnostics[&Url::from_file_path(&composite_path).unwrap()];
+    assert_eq!(composite_diagnostics.len(), 1);
+    assert_eq!(
+        composite_diagnostics[0].code,
+        Some(NumberOrString::String("unknown-include-alias".into()))
+    );
+    assert_eq!(
+        composite_diagnostics[0].range,
+        Range::new(Position::new(2, 10), Position::new(2, 24))
+    );
+    assert!(composite_diagnostics[0]
+        .message
+        .starts_with("`@fx` isn't in `mcglsl.includeAliases`"));
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_included_shader() {
+    let mut mockgl = opengl::MockShaderValidator::new();
+    mockgl.expect_supported_stages().returning(opengl::all_stages);
+    mockgl.expect_validate().returning(|_, _| None);
+    let mut server = new_temp_server(Some(Box::new(mockgl)));
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/46", &mut server);
+    server.build_initial_graph();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let diagnostics = server.lint(&final_path).unwrap();
+
+    let included: Vec<&Diagnostic> = diagnostics[&Url::from_file_path(&final_path).unwrap()]
+        .iter()
+        .filter(|d| d.code == Some(NumberOrString::String("included-shader".into())))
+        .collect();
+    assert_eq!(included.len(), 1);
+    assert_eq!(included[0].severity, Some(DiagnosticSeverity::WARNING));
+    assert_eq!(included[0].range, Range::new(Position::new(2, 10), Position::new(2, 23)));
+    assert_eq!(
+        included[0].message,
+        "`composite.fsh` is a shader program of its own, so including it brings along its `#version` and `main`"
+    );
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_created_missing_include() {
+    let mut mockgl = opengl::MockShaderValidator::new();
+    mockgl.expect_supported_stages().returning(opengl::all_stages);
+    mockgl.expect_validate().times(1).returning(|_, _| None);
+    let mut server = new_temp_server(Some(Box::new(mockgl)));
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/22", &mut server);
+    server.build_initial_graph();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let missing_path = tmp_path.join("shaders").join("lib").join("missing.glsl");
+    let final_url = Url::from_file_path(&final_path).unwrap();
+
+    let diagnostics = server.lint(&final_path).unwrap();
+    assert_eq!(diagnostics[&final_url].len(), 1);
+    assert_eq!(
+        diagnostics[&final_url][0].code,
+        Some(NumberOrString::String("missing-include".into()))
+    );
+    assert_eq!(
+        diagnostics[&final_url][0].range,
+        Range::new(Position::new(1, 10), Position::new(1, 27))
+    );
+    server.publish_diagnostic(diagnostics, None);
+
+    fs::create_dir(missing_path.parent().unwrap()).unwrap();
+    fs::write(&missing_path, "#include \"common.glsl\"\n").unwrap();
+    fs::write(missing_path.with_file_name("common.glsl"), "float common() {\n\treturn 1.0;\n}\n").unwrap();
+
+    server.did_change_watched_files(DidChangeWatchedFilesParams {
+        changes: vec![FileEvent {
+            uri: Url::from_file_path(&missing_path).unwrap(),
+            typ: FileChangeType::CREATED,
+        }],
+    });
+
+    // the final shader was linted again without errors, and the new file's own includes are known
+    assert!(server.diagnostics.borrow().get(&final_url).is_none());
+    assert!(server.unresolved_includes.borrow().is_empty());
+    let mut graph = server.graph.borrow_mut();
+    let missing = graph.find_node(&missing_path).unwrap();
+    let common = graph.find_node(&missing_path.with_file_name("common.glsl")).unwrap();
+    assert_eq!(graph.child_node_indexes(missing).collect::<Vec<_>>(), vec![common]);
+    drop(graph);
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_shader_and_include_changed_together() {
+    let validator = RecordingValidator::new(None);
+    let validated = validator.validated.clone();
+    let mut server = new_temp_server(Some(Box::new(validator)));
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/09", &mut server);
+    server.build_initial_graph();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let c_path = tmp_path.join("shaders").join("c.glsl");
+
+    // the shader now includes c.glsl directly, which changed too
+    fs::write(
+        &final_path,
+        "#version 120\n\n#include \"c.glsl\"\n\nvoid main() {\n\tgl_FragColor = vec4(c());\n}\n",
+    )
+    .unwrap();
+    fs::write(&c_path, "float c() {\n\treturn 2.0;\n}\n").unwrap();
+
+    // the include comes first in the batch
+    server.did_change_watched_files(DidChangeWatchedFilesParams {
+        changes: vec![
+            FileEvent {
+                uri: Url::from_file_path(&c_path).unwrap(),
+                typ: FileChangeType::CHANGED,
+            },
+            FileEvent {
+                uri: Url::from_file_path(&final_path).unwrap(),
+                typ: FileChangeType::CHANGED,
+            },
+        ],
+    });
+
+    // the program was linted once, with both files' new contents
+    let validated = validated.borrow();
+    assert_eq!(validated.len(), 1);
+    assert!(validated[0].1.contains("return 2.0;"));
+    assert!(!validated[0].1.contains("return 1.0;"));
+    assert_eq!(validated[0].1.matches("float c()").count(), 1);
+
+    let mut graph = server.graph.borrow_mut();
+    let final_node = graph.find_node(&final_path).unwrap();
+    let c = graph.find_node(&c_path).unwrap();
+    assert_eq!(graph.child_node_indexes(final_node).collect::<Vec<_>>(), vec![c]);
+    drop(graph);
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_saved_open_file_linted_once() {
+    let validator = RecordingValidator::new(None);
+    let validated = validator.validated.clone();
+    let mut server = new_temp_server(Some(Box::new(validator)));
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/09", &mut server);
+    server.build_initial_graph();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let c_path = tmp_path.join("shaders").join("c.glsl");
+    let final_url = Url::from_file_path(&final_path).unwrap();
+
+    server.did_open_text_document(DidOpenTextDocumentParams {
+        text_document: TextDocumentItem {
+            uri: final_url.clone(),
+            language_id: "glsl".into(),
+            version: 1,
+            text: fs::read_to_string(&final_path).unwrap(),
+        },
+    });
+    assert_eq!(validated.borrow().len(), 1);
+
+    // the client reports a save of the open file both ways
+    server.did_save_text_document(DidSaveTextDocumentParams {
+        text_document: TextDocumentIdentifier { uri: final_url.clone() },
+        text: None,
+    });
+    let changed = |path: &Path| DidChangeWatchedFilesParams {
+        changes: vec![FileEvent {
+            uri: Url::from_file_path(path).unwrap(),
+            typ: FileChangeType::CHANGED,
+        }],
+    };
+    server.did_change_watched_files(changed(&final_path));
+    assert_eq!(validated.borrow().len(), 2);
+
+    // files changed outside of the editor are still linted
+    server.did_change_watched_files(changed(&c_path));
+    assert_eq!(validated.borrow().len(), 3);
+
+    // as is the file once it is closed again
+    server.did_close_text_document(DidCloseTextDocumentParams {
+        text_document: TextDocumentIdentifier { uri: final_url },
+    });
+    server.did_change_watched_files(changed(&final_path));
+    assert_eq!(validated.borrow().len(), 4);
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_shader_options_changed() {
+    let validator = RecordingValidator::new(None);
+    let validated = validator.validated.clone();
+    let mut server = new_temp_server(Some(Box::new(validator)));
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/49", &mut server);
+    server.build_initial_graph();
+
+    let properties_path = tmp_path.join("shaders").join("shaders.properties");
+    fs::write(&properties_path, "BLOOM=false\nSHADOW_RES=2048\n").unwrap();
+
+    server.did_change_watched_files(DidChangeWatchedFilesParams {
+        changes: vec![FileEvent {
+            uri: Url::from_file_path(&properties_path).unwrap(),
+            typ: FileChangeType::CHANGED,
+        }],
+    });
+
+    // neither shader includes the properties, but both were linted again with the new options
+    let validated = validated.borrow();
+    assert_eq!(validated.len(), 2);
+    for (_, source) in validated.iter() {
+        assert!(!source.contains("#define BLOOM"));
+        assert!(source.contains("#define SHADOW_RES 2048"));
+    }
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_find_includes_trims_whitespace() {
+    let server = new_temp_server(None);
+
+    let tmp_dir = TempDir::new("mcshader").unwrap();
+    let path = tmp_dir.path().join("final.fsh");
+    fs::write(&path, "#version 120\n#include \" common.glsl \"\n#include \"lib/ü.glsl ?v=2\"\n").unwrap();
+
+    let includes = server.find_includes(&path);
+    assert_eq!(
+        includes,
+        vec![
+            (
+                tmp_dir.path().join("common.glsl"),
+                IncludePosition {
+                    line: 1,
+                    start: 11,
+                    end: 22
+                }
+            ),
+            (
+                tmp_dir.path().join("lib").join("ü.glsl"),
+                IncludePosition {
+                    line: 2,
+                    start: 10,
+                    end: 20
+                }
+            ),
+        ]
+    );
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_find_includes_skips_long_lines() {
+    let mut server = new_temp_server(None);
+
+    let tmp_dir = TempDir::new("mcshader").unwrap();
+    let shaders = tmp_dir.path().join("shaders");
+    fs::create_dir(&shaders).unwrap();
+    server.root = tmp_dir.path().into();
+
+    let long_include = format!("#include \"/skipped.glsl\" // {}", "x".repeat(2048));
+    let long_line = format!("const float weights[] = float[]({});", vec!["0.5"; 1024].join(", "));
+    let source = format!(
+        "#version 120\n{}\n{}\n#include \"/common.glsl\"\nvoid main() {{}}\n",
+        long_include, long_line
+    );
+    let path = shaders.join("final.fsh");
+    fs::write(&path, &source).unwrap();
+    fs::write(shaders.join("common.glsl"), "float common;\n").unwrap();
+    server.apply_configuration(&serde_json::json!({ "maxIncludeLineLength": 1024 }));
+
+    let includes = server.find_includes(&path);
+    assert_eq!(
+        includes,
+        vec![(
+            shaders.join("common.glsl"),
+            IncludePosition {
+                line: 3,
+                start: 10,
+                end: 22
+            }
+        )]
+    );
+
+    // long lines are still merged as they are
+    server.build_initial_graph();
+    let final_idx = server.graph.borrow_mut().find_node(&path).unwrap();
+    let nodes = server.get_dfs_for_node(final_idx).unwrap();
+    let sources = server.load_sources(&nodes).unwrap();
+    let graph = server.graph.borrow();
+    let mut source_mapper = source_mapper::SourceMapper::new(2);
+    let merged = merge_views::MergeViewBuilder::new(&nodes, &sources, &graph, &mut source_mapper).build();
+    assert!(merged.contains(&long_include));
+    assert!(merged.contains(&long_line));
+    assert!(merged.contains("float common;"));
+    drop(graph);
+
+    // without a limit, the long include is found again
+    server.apply_configuration(&serde_json::json!({ "maxIncludeLineLength": 0 }));
+    assert_eq!(server.find_includes(&path).len(), 2);
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_pragmas_among_includes() {
+    let server = new_temp_server(None);
+
+    let tmp_dir = TempDir::new("mcshader").unwrap();
+    let path = tmp_dir.path().join("final.fsh");
+    let source = "#version 120\n#pragma optimize(off)\n#include \"a.glsl\"\n  #pragma debug(on)\n#include \"b.glsl\"\n";
+    fs::write(&path, source).unwrap();
+
+    let includes = server.find_includes(&path);
+    assert_eq!(
+        includes,
+        vec![
+            (
+                tmp_dir.path().join("a.glsl"),
+                IncludePosition {
+                    line: 2,
+                    start: 10,
+                    end: 16
+                }
+            ),
+            (
+                tmp_dir.path().join("b.glsl"),
+                IncludePosition {
+                    line: 4,
+                    start: 10,
+                    end: 16
+                }
+            ),
+        ]
+    );
+
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_glsl::language()).unwrap();
+    let context = navigation::ParserContext::from_source(&mut parser, &path, source.to_string());
+    let pragmas: Vec<(String, Range)> = context
+        .list_symbols(&path)
+        .unwrap()
+        .unwrap()
+        .into_iter()
+        .filter(|symbol| symbol.kind == SymbolKind::KEY)
+        .map(|symbol| (symbol.name, symbol.selection_range))
+        .collect();
+    assert_eq!(
+        pragmas,
+        vec![
+            ("optimize(off)".to_string(), Range::new(Position::new(1, 8), Position::new(1, 21))),
+            ("debug(on)".to_string(), Range::new(Position::new(3, 10), Position::new(3, 19))),
+        ]
+    );
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_include_spellings_single_node() {
+    let mut server = new_temp_server(None);
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/16", &mut server);
+    server.build_initial_graph();
+
+    // lib/a.glsl is included as ./lib/a.glsl from final.fsh and as ../lib/./a.glsl from world/b.glsl
+    let shaders = tmp_path.join("shaders");
+    let mut graph = server.graph.borrow_mut();
+    assert_eq!(graph.graph.node_count(), 3);
+
+    let lib = graph.find_node(&shaders.join("lib").join("a.glsl")).unwrap();
+    let final_node = graph.find_node(&shaders.join("final.fsh")).unwrap();
+    let world = graph.find_node(&shaders.join("world").join("b.glsl")).unwrap();
+    assert!(graph.child_node_indexes(final_node).any(|child| child == lib));
+    assert_eq!(graph.child_node_indexes(world).collect::<Vec<_>>(), vec![lib]);
+    drop(graph);
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_diagnostics_output_report() {
+    let mut server = new_temp_server(None);
+
+    let tmp_dir = TempDir::new("mcshader").unwrap();
+    server.root = tmp_dir.path().into();
+    server.config.borrow_mut().diagnostics_output = Some("diagnostics.json".into());
+
+    let final_path = tmp_dir.path().join("shaders").join("final.fsh");
+    let common_path = tmp_dir.path().join("shaders").join("common.glsl");
+
+    let diagnostic = Diagnostic {
+        range: Range::new(Position::new(3, 0), Position::new(3, 1000)),
+        severity: Some(DiagnosticSeverity::ERROR),
+        source: Some("mcglsl".into()),
+        message: "'foo' : undeclared identifier".into(),
+        ..Default::default()
+    };
+
+    let mut diagnostics = HashMap::new();
+    diagnostics.insert(Url::from_file_path(&final_path).unwrap(), vec![diagnostic.clone()]);
+    diagnostics.insert(Url::from_file_path(&common_path).unwrap(), vec![diagnostic.clone()]);
+    server.publish_diagnostic(diagnostics, None);
+
+    let report_path = tmp_dir.path().join("diagnostics.json");
+    let report: Value = serde_json::from_str(&fs::read_to_string(&report_path).unwrap()).unwrap();
+    assert_eq!(
+        report,
+        serde_json::json!({
+            final_path.to_str().unwrap(): [diagnostic],
+            common_path.to_str().unwrap(): [diagnostic],
+        })
+    );
+
+    // clearing a file's diagnostics drops it from the report
+    let mut diagnostics = HashMap::new();
+    diagnostics.insert(Url::from_file_path(&common_path).unwrap(), vec![]);
+    server.publish_diagnostic(diagnostics, None);
+
+    let report: Value = serde_json::from_str(&fs::read_to_string(&report_path).unwrap()).unwrap();
+    assert_eq!(report, serde_json::json!({ final_path.to_str().unwrap(): [diagnostic] }));
+    assert!(!tmp_dir.path().join("diagnostics.json.tmp").exists());
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_clear_diagnostics() {
+    let mut server = new_temp_server(None);
+
+    let tmp_dir = TempDir::new("mcshader").unwrap();
+    server.root = tmp_dir.path().into();
+    server.config.borrow_mut().diagnostics_output = Some("diagnostics.json".into());
+
+    let final_url = Url::from_file_path(tmp_dir.path().join("shaders").join("final.fsh")).unwrap();
+    let common_url = Url::from_file_path(tmp_dir.path().join("shaders").join("common.glsl")).unwrap();
+    let diagnostic = Diagnostic {
+        range: Range::new(Position::new(2, 0), Position::new(2, 5)),
+        severity: Some(DiagnosticSeverity::ERROR),
+        message: "undefined variable".into(),
+        ..Default::default()
+    };
+
+    let mut diagnostics = HashMap::new();
+    diagnostics.insert(final_url.clone(), vec![diagnostic.clone()]);
+    diagnostics.insert(common_url.clone(), vec![diagnostic]);
+    server.publish_diagnostic(diagnostics, None);
+
+    let cleared = run_server_command(&server, &commands::clear_diagnostics::ClearDiagnosticsCommand, vec![]).unwrap();
+    let mut expected = vec![final_url.to_string(), common_url.to_string()];
+    expected.sort();
+    assert_eq!(cleared, serde_json::json!(expected));
+
+    // empty diagnostics were published for both files, so none are left in the report
+    assert!(server.diagnostics.borrow().is_empty());
+    let report: Value = serde_json::from_str(&fs::read_to_string(tmp_dir.path().join("diagnostics.json")).unwrap()).unwrap();
+    assert_eq!(report, serde_json::json!({}));
+
+    assert_eq!(
+        run_server_command(&server, &commands::clear_diagnostics::ClearDiagnosticsCommand, vec![]).unwrap(),
+        serde_json::json!([])
+    );
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_merge_all() {
+    let mut server = new_temp_server(None);
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/40", &mut server);
+    server.build_initial_graph();
+
+    let shaders_path = tmp_path.join("shaders");
+    let merged = commands::merge_all::MergeAllCommand.merge_all(&server);
+
+    // composite.fsh includes a cycle, so it can't be merged
+    let mut paths: Vec<&PathBuf> = merged.keys().collect();
+    paths.sort();
+    assert_eq!(paths, vec![&shaders_path.join("composite.vsh"), &shaders_path.join("final.fsh")]);
+
+    assert!(merged[&shaders_path.join("composite.vsh")].contains("float scale() {"));
+    assert!(merged[&shaders_path.join("final.fsh")].contains("gl_FragColor = vec4(1.0);"));
+
+    // and the same through the command
+    let result = Rc::new(RefCell::new(Value::Null));
+    let result_clone = result.clone();
+    let on_response = move |resp: Option<Response>| match resp.unwrap().result_or_error {
+        ResponseResult::Result(value) => *result_clone.borrow_mut() = value,
+        ResponseResult::Error(e) => panic!("expected ResponseResult::Result(..), got {:?}", e),
+    };
+    let params = ExecuteCommandParams {
+        command: "mergeAll".into(),
+        arguments: vec![],
+        work_done_progress_params: Default::default(),
+    };
+    server.execute_command(
+        params,
+        MethodCompletable::new(ResponseCompletable::new(Some(Id::Number(1)), Box::new(on_response))),
+    );
+    let final_key = shaders_path.join("final.fsh").to_str().unwrap().to_string();
+    assert_eq!(result.borrow().as_object().unwrap().len(), 2);
+    assert_eq!(
+        result.borrow()[&final_key],
+        Value::String(merged[&shaders_path.join("final.fsh")].clone())
+    );
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_validate_all_progress() {
+    let mut mockgl = opengl::MockShaderValidator::new();
+    mockgl.expect_supported_stages().returning(opengl::all_stages);
+    mockgl.expect_validate().times(4).returning(|_, _| None);
+    let mut server = new_temp_server(Some(Box::new(mockgl)));
+
+    let (_tmp_dir, _tmp_path) = copy_to_and_set_root("./testdata/24", &mut server);
+    server.build_initial_graph();
+
+    let mut progress = vec![];
+    let validated = commands::validate_all::ValidateAllCommand
+        .validate_all(&server, |value| progress.push(value))
+        .unwrap();
+    assert_eq!(validated, serde_json::json!(4));
+
+    assert_eq!(progress.len(), 6);
+    assert!(matches!(
+        progress.first(),
+        Some(WorkDoneProgress::Begin(WorkDoneProgressBegin { percentage: Some(0), .. }))
+    ));
+    assert!(matches!(progress.last(), Some(WorkDoneProgress::End(_))));
+
+    let reports: Vec<(u32, String)> = progress[1..5]
+        .iter()
+        .map(|value| match value {
+            WorkDoneProgress::Report(report) => (report.percentage.unwrap(), report.message.clone().unwrap()),
+            _ => panic!("expected a progress report, got {:?}", value),
+        })
+        .collect();
+    assert_eq!(
+        reports.iter().map(|(percentage, _)| *percentage).collect::<Vec<_>>(),
+        vec![0, 25, 50, 75]
+    );
+    assert_eq!(reports[0].1, "composite.csh (1/4)");
+    assert_eq!(reports[3].1, "composite.vsh (4/4)");
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_reload_shader_names() {
+    let validator = RecordingValidator::new(None);
+    let validated = validator.validated.clone();
+    let mut server = new_temp_server(Some(Box::new(validator)));
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/01", &mut server);
+    server.build_initial_graph();
+
+    // added after the graph was built, so only a rescan picks them up
+    let shaders = tmp_path.join("shaders");
+    let begin3 = shaders.join("begin3.fsh");
+    fs::write(&begin3, "#version 120\n\n#include \"/common.glsl\"\n\nvoid main() {}\n").unwrap();
+    fs::write(shaders.join("begin5.fsh"), "#version 120\n\nvoid main() {}\n").unwrap();
+    assert_eq!(server.graph.borrow_mut().find_node(&begin3), None);
+
+    server.apply_configuration(&serde_json::json!({ "extraShaderNames": ["begin"], "maxShaderIndex": 4 }));
+    let reloaded = run_server_command(&server, &commands::reload_shader_names::ReloadShaderNamesCommand, vec![]).unwrap();
+    assert_eq!(reloaded, serde_json::json!([begin3]));
+
+    let mut graph = server.graph.borrow_mut();
+    let begin3_idx = graph.find_node(&begin3).unwrap();
+    let common_idx = graph.find_node(&shaders.join("common.glsl")).unwrap();
+    assert_eq!(graph.child_node_indexes(begin3_idx).collect::<Vec<_>>(), vec![common_idx]);
+    drop(graph);
+
+    // only the newly top-level program is linted, final.fsh already was before
+    assert_eq!(validated.borrow().len(), 1);
+    let source = validated.borrow()[0].1.clone();
+    assert!(source.contains("float test() {"));
+    assert!(source.contains(&begin3.to_str().unwrap().replace('\\', "\\\\")));
+
+    // running it again with the same names has nothing new to lint
+    assert_eq!(
+        run_server_command(&server, &commands::reload_shader_names::ReloadShaderNamesCommand, vec![]).unwrap(),
+        serde_json::json!([])
+    );
+    assert_eq!(validated.borrow().len(), 1);
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_dimension_override_includes() {
+    let mut server = new_temp_server(None);
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/11", &mut server);
+    server.build_initial_graph();
+
+    let shaders = tmp_path.join("shaders");
+
+    let links_for = |server: &mut MinecraftShaderLanguageServer, path: &Path| -> Vec<PathBuf> {
+        let params = DocumentLinkParams {
+            text_document: TextDocumentIdentifier {
+                uri: Url::from_file_path(path).unwrap(),
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+
+        let links = Rc::new(RefCell::new(vec![]));
+        let links_clone = links.clone();
+        let on_response = move |resp: Option<Response>| match resp.unwrap().result_or_error {
+            ResponseResult::Result(value) => {
+                let mut targets: Vec<PathBuf> = value
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|link| PathBuf::from_url(Url::parse(link["target"].as_str().unwrap()).unwrap()))
+                    .collect();
+                targets.sort();
+                *links_clone.borrow_mut() = targets;
+            }
+            ResponseResult::Error(e) => panic!("expected ResponseResult::Result(..), got {:?}", e),
+        };
+
+        let completable = MethodCompletable::new(ResponseCompletable::new(Some(Id::Number(1)), Box::new(on_response)));
+        server.document_link(params, completable);
+        links.take()
+    };
+
+    assert_eq!(
+        links_for(&mut server, &shaders.join("composite.fsh")),
+        vec![shaders.join("common.glsl"), shaders.join("lib").join("shared.glsl")]
+    );
+    assert_eq!(
+        links_for(&mut server, &shaders.join("world0").join("composite.fsh")),
+        vec![shaders.join("lib").join("shared.glsl"), shaders.join("world0").join("common.glsl")]
+    );
+
+    // the shared include is a single node, reached from both the base shader and its override
+    let shared = server
+        .graph
+        .borrow_mut()
+        .find_node(&shaders.join("lib").join("shared.glsl"))
+        .unwrap();
+    assert_eq!(server.graph.borrow().collect_root_ancestors(shared).len(), 2);
+
+    // each common.glsl only belongs to its own program
+    let common = server.graph.borrow_mut().find_node(&shaders.join("common.glsl")).unwrap();
+    let roots: Vec<PathBuf> = server
+        .graph
+        .borrow()
+        .collect_root_ancestors(common)
+        .into_iter()
+        .map(|root| server.graph.borrow().get_node(root))
+        .collect();
+    assert_eq!(roots, vec![shaders.join("composite.fsh")]);
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_dimension_include_fallback() {
+    let mut mockgl = opengl::MockShaderValidator::new();
+    mockgl.expect_validate().returning(|_, _| None);
+    mockgl.expect_supported_stages().returning(opengl::all_stages);
+    let mut server = new_temp_server(Some(Box::new(mockgl)));
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/48", &mut server);
+    server.build_initial_graph();
+
+    let shaders = tmp_path.join("shaders");
+    let world_composite_path = shaders.join("world0").join("composite.fsh");
+
+    // the world folder's own common.glsl overrides the base pack's, while the includes it doesn't override
+    // fall back to the base pack. Includes that exist in neither are left relative to the including file
+    let includes: Vec<PathBuf> = server
+        .find_includes(&world_composite_path)
+        .into_iter()
+        .map(|include| include.0)
+        .collect();
+    assert_eq!(
+        includes,
+        vec![
+            shaders.join("settings.glsl"),
+            shaders.join("world0").join("common.glsl"),
+            shaders.join("lib").join("sky.glsl"),
+            shaders.join("world0").join("missing.glsl"),
+        ]
+    );
+
+    let settings = server.graph.borrow_mut().find_node(&shaders.join("settings.glsl")).unwrap();
+    let mut roots: Vec<PathBuf> = server
+        .graph
+        .borrow()
+        .collect_root_ancestors(settings)
+        .into_iter()
+        .map(|root| server.graph.borrow().get_node(root))
+        .collect();
+    roots.sort();
+    assert_eq!(roots, vec![shaders.join("composite.fsh"), world_composite_path.clone()]);
+
+    // overriding on purpose isn't ambiguous
+    let diagnostics = server.lint(&world_composite_path).unwrap();
+    assert!(diagnostics[&Url::from_file_path(&world_composite_path).unwrap()]
+        .iter()
+        .all(|diagnostic| diagnostic.code != Some(NumberOrString::String("ambiguous-include".into()))));
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_dimension_shared_include_parents() {
+    let validator = RecordingValidator::new(None);
+    let validated = validator.validated.clone();
+    let mut server = new_temp_server(Some(Box::new(validator)));
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/32", &mut server);
+    server.build_initial_graph();
+
+    let shaders = tmp_path.join("shaders");
+    let common_path = shaders.join("lib").join("common.glsl");
+    let composite_path = shaders.join("composite.fsh");
+    let world_composite_path = shaders.join("world0").join("composite.fsh");
+
+    let toplevel_ancestors = |server: &MinecraftShaderLanguageServer| -> Vec<PathBuf> {
+        let ancestors = server.get_file_toplevel_ancestors(&common_path).unwrap().unwrap();
+        let mut paths: Vec<PathBuf> = ancestors.into_iter().map(|node| server.graph.borrow().get_node(node)).collect();
+        paths.sort();
+        paths
+    };
+
+    // both the base and the dimension shader include the /-rooted include
+    assert_eq!(
+        toplevel_ancestors(&server),
+        vec![composite_path.clone(), world_composite_path.clone()]
+    );
+
+    // and keep doing so once either of them is updated
+    server.update_includes(&world_composite_path);
+    server.update_includes(&composite_path);
+    assert_eq!(
+        toplevel_ancestors(&server),
+        vec![composite_path.clone(), world_composite_path.clone()]
+    );
+
+    let diagnostics = server.lint(&common_path).unwrap();
+    assert!(diagnostics.contains_key(&Url::from_file_path(&composite_path).unwrap()));
+    assert!(diagnostics.contains_key(&Url::from_file_path(&world_composite_path).unwrap()));
+    assert_eq!(validated.borrow().len(), 2);
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_edge_include_columns() {
+    let mut server = new_temp_server(None);
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/04", &mut server);
+    server.build_initial_graph();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let utilities_path = tmp_path.join("shaders").join("utils").join("utilities.glsl");
+
+    let mut graph = server.graph.borrow_mut();
+    let final_idx = graph.find_node(&final_path).unwrap();
+    let utilities_idx = graph.find_node(&utilities_path).unwrap();
+
+    // each edge spans the include's path, without the quotes
+    assert_eq!(
+        graph.get_child_positions(final_idx, utilities_idx).collect::<Vec<_>>(),
+        vec![IncludePosition {
+            line: 2,
+            start: 10,
+            end: 31
+        }]
+    );
+    assert_eq!(
+        graph
+            .get_all_child_positions(utilities_idx)
+            .map(|(_, position)| position)
+            .collect::<Vec<_>>(),
+        vec![
+            IncludePosition {
+                line: 0,
+                start: 10,
+                end: 28
+            },
+            IncludePosition {
+                line: 1,
+                start: 10,
+                end: 28
+            },
+        ]
+    );
+    drop(graph);
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_document_link_columns() {
+    let mut server = new_temp_server(None);
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/20", &mut server);
+    server.build_initial_graph();
+
+    let params = DocumentLinkParams {
+        text_document: TextDocumentIdentifier {
+            uri: Url::from_file_path(tmp_path.join("shaders").join("final.fsh")).unwrap(),
+        },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+
+    let on_response = |resp: Option<Response>| match resp.unwrap().result_or_error {
+        ResponseResult::Result(value) => {
+            let mut ranges: Vec<Range> = value
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|link| serde_json::from_value(link["range"].clone()).unwrap())
+                .collect();
+            ranges.sort_by_key(|range| range.start.line);
+            // a tab is a single column, as is the 3 byte ideographic space in front of the second include
+            assert_eq!(
+                ranges,
+                vec![
+                    Range::new(Position::new(1, 11), Position::new(1, 17)),
+                    Range::new(Position::new(2, 11), Position::new(2, 17)),
+                ]
+            );
+        }
+        ResponseResult::Error(e) => panic!("expected ResponseResult::Result(..), got {:?}", e),
+    };
+
+    let completable = MethodCompletable::new(ResponseCompletable::new(Some(Id::Number(1)), Box::new(on_response)));
+    server.document_link(params, completable);
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_document_link_tooltip() {
+    let mut server = new_temp_server(None);
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/38", &mut server);
+    server.build_initial_graph();
+
+    let outputs_path = tmp_path.join("shaders").join("lib").join("outputs.glsl");
+    let params = DocumentLinkParams {
+        text_document: TextDocumentIdentifier {
+            uri: Url::from_file_path(tmp_path.join("shaders").join("final.fsh")).unwrap(),
+        },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+
+    let on_response = move |resp: Option<Response>| match resp.unwrap().result_or_error {
+        ResponseResult::Result(value) => {
+            let links = value.as_array().unwrap();
+            assert_eq!(links.len(), 1);
+            // the tooltip is relative to the shaders folder, while the target stays absolute
+            assert_eq!(links[0]["tooltip"], "lib/outputs.glsl");
+            assert_eq!(
+                PathBuf::from_url(Url::parse(links[0]["target"].as_str().unwrap()).unwrap()),
+                outputs_path
+            );
+        }
+        ResponseResult::Error(e) => panic!("expected ResponseResult::Result(..), got {:?}", e),
+    };
+
+    let completable = MethodCompletable::new(ResponseCompletable::new(Some(Id::Number(1)), Box::new(on_response)));
+    server.document_link(params, completable);
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_unsupported_stage_skipped() {
+    let mut mockgl = opengl::MockShaderValidator::new();
+    mockgl
+        .expect_supported_stages()
+        .returning(|| HashSet::from([gl::FRAGMENT_SHADER, gl::VERTEX_SHADER, gl::GEOMETRY_SHADER]));
+    mockgl
+        .expect_validate()
+        .withf(|tree_type, _| matches!(tree_type, TreeType::Fragment))
+        .times(1)
+        .returning(|_, _| None);
+    let mut server = new_temp_server(Some(Box::new(mockgl)));
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/12", &mut server);
+    server.build_initial_graph();
+
+    let compute_path = tmp_path.join("shaders").join("composite.csh");
+    server.lint(&compute_path).unwrap();
+    server.lint(&compute_path).unwrap();
+    assert_eq!(*server.notified_unsupported_stages.borrow(), HashSet::from([gl::COMPUTE_SHADER]));
+
+    server.lint(&tmp_path.join("shaders").join("composite.fsh")).unwrap();
+
+    server.endpoint.request_shutdown();
+}
+
+#[cfg(unix)]
+#[test]
+#[logging_macro::log_scope]
+fn test_follow_symlinked_includes() {
+    let tmp_dir = TempDir::new("mcshader").unwrap();
+    let root = tmp_dir.path().join("pack");
+    let shared = tmp_dir.path().join("shared");
+
+    fs::create_dir_all(root.join("shaders")).unwrap();
+    fs::create_dir_all(&shared).unwrap();
+    fs::write(shared.join("common.glsl"), "float common() {\n\treturn 1.0;\n}\n").unwrap();
+    fs::write(
+        root.join("shaders").join("final.fsh"),
+        "#version 120\n#include \"/lib/common.glsl\"\n",
+    )
+    .unwrap();
+    std::os::unix::fs::symlink(&shared, root.join("shaders").join("lib")).unwrap();
+
+    let link_path = root.join("shaders").join("lib").join("common.glsl");
+    let target_path = shared.join("common.glsl");
+
+    for (follow_symlinks, expected) in [(false, &link_path), (true, &target_path)] {
+        let mut server = new_temp_server(None);
+        server.root = root.clone();
+        server.config.borrow_mut().follow_symlinks = follow_symlinks;
+        server.build_initial_graph();
+
+        let includes = server.find_includes(&root.join("shaders").join("final.fsh"));
+        assert_eq!(includes.len(), 1);
+        assert_eq!(&includes[0].0, expected);
+
+        // the file is reachable from the include and from walking the workspace, both must map to the same node
+        let mut graph = server.graph.borrow_mut();
+        let final_node = graph.find_node(&root.join("shaders").join("final.fsh")).unwrap();
+        let common_node = graph.find_node(expected).unwrap();
+        assert_eq!(graph.child_node_indexes(final_node).collect::<Vec<_>>(), vec![common_node]);
+        assert_eq!(graph.graph.node_count(), 2);
+        drop(graph);
+
+        server.endpoint.request_shutdown();
+    }
+}
+
+#[cfg(unix)]
+#[test]
+#[logging_macro::log_scope]
+fn test_symlinked_shaders_directory() {
+    let tmp_dir = TempDir::new("mcshader").unwrap();
+    let root = tmp_dir.path().join("pack");
+    let checkout = tmp_dir.path().join("checkout");
+
+    fs::create_dir_all(&root).unwrap();
+    fs::create_dir_all(checkout.join("shaders").join("lib")).unwrap();
+    fs::write(
+        checkout.join("shaders").join("lib").join("common.glsl"),
+        "float common() {\n\treturn 1.0;\n}\n",
+    )
+    .unwrap();
+    fs::write(
+        checkout.join("shaders").join("final.fsh"),
+        "#version 120\n#include \"/lib/common.glsl\"\n",
+    )
+    .unwrap();
+    std::os::unix::fs::symlink(checkout.join("shaders"), root.join("shaders")).unwrap();
+
+    let final_path = root.join("shaders").join("final.fsh");
+    let common_path = root.join("shaders").join("lib").join("common.glsl");
+
+    for follow_symlinks in [false, true] {
+        let mut server = new_temp_server(None);
+        server.root = root.clone();
+        server.config.borrow_mut().follow_symlinks = follow_symlinks;
+        server.build_initial_graph();
+
+        // files are found through the link and keyed by their path under the root, like the client refers to them
+        let mut graph = server.graph.borrow_mut();
+        let final_node = graph.find_node(&final_path).unwrap();
+        let common_node = graph.find_node(&common_path).unwrap();
+        assert_eq!(graph.child_node_indexes(final_node).collect::<Vec<_>>(), vec![common_node]);
+        assert_eq!(graph.graph.node_count(), 2);
+        drop(graph);
+
+        assert!(server.in_workspace(&final_path));
+
+        server.endpoint.request_shutdown();
+    }
+}
+
+#[cfg(unix)]
+#[test]
+#[logging_macro::log_scope]
+fn test_lint_outside_root() {
+    let tmp_dir = TempDir::new("mcshader").unwrap();
+    let root = tmp_dir.path().join("pack");
+    let shared = tmp_dir.path().join("shared");
+
+    fs::create_dir_all(root.join("shaders")).unwrap();
+    fs::create_dir_all(&shared).unwrap();
+    fs::write(shared.join("common.glsl"), "float common() {\n\treturn 1.0;\n}\n").unwrap();
+    fs::write(
+        shared.join("standalone.glsl"),
+        "#version 120\n\nvoid main() {\n\tgl_FragColor = vec4(1.0);\n}\n",
+    )
+    .unwrap();
+    fs::write(
+        shared.join("wrapper.glsl"),
+        "#include \"common.glsl\"\n#include \"standalone.glsl\"\n",
+    )
+    .unwrap();
+    fs::write(
+        root.join("shaders").join("final.fsh"),
+        "#version 120\n#include \"/lib/common.glsl\"\n#include \"/lib/standalone.glsl\"\n",
+    )
+    .unwrap();
+    std::os::unix::fs::symlink(&shared, root.join("shaders").join("lib")).unwrap();
+
+    let validator = RecordingValidator::new(None);
+    let validated = validator.validated.clone();
+    let mut server = new_temp_server(Some(Box::new(validator)));
+    server.root = root.clone();
+    server.config.borrow_mut().follow_symlinks = true;
+    server.build_initial_graph();
+
+    // the symlinks are resolved, so the files are keyed by their paths outside of the root. None of them are
+    // top-level, and only final.fsh is validated
+    let wrapper_path = shared.join("wrapper.glsl");
+    let diagnostics = server.lint(&wrapper_path).unwrap();
+    assert!(diagnostics[&Url::from_file_path(&wrapper_path).unwrap()].is_empty());
+    assert!(validated.borrow().is_empty());
+
+    let common_path = shared.join("common.glsl");
+    server.lint(&common_path).unwrap();
+    assert_eq!(validated.borrow().len(), 1);
+
+    // validated standalone as the stage of final.fsh only
+    server.config.borrow_mut().validate_standalone_includes = true;
+    server.lint(&shared.join("standalone.glsl")).unwrap();
+    assert_eq!(validated.borrow().len(), 2);
+    assert_eq!(validated.borrow()[1].0, TreeType::Fragment);
+
+    server.endpoint.request_shutdown();
+}
+
+#[allow(deprecated)]
+#[test]
+#[logging_macro::log_scope]
+fn test_initialization_options() {
+    let mut server = new_temp_server(None);
+
+    let tmp_dir = TempDir::new("mcshader").unwrap();
+    let tmp_path = tmp_dir.path();
+
+    let initialize_params = InitializeParams {
+        process_id: None,
+        root_path: None,
+        root_uri: Some(Url::from_directory_path(tmp_path).unwrap()),
+        client_info: None,
+        initialization_options: Some(serde_json::json!({
+            "mcglsl": {
+                "extraShaderNames": ["begin"],
+                "maxMergedBytes": 1024,
+            }
+        })),
+        capabilities: ClientCapabilities {
+            workspace: None,
+            text_document: None,
+            experimental: None,
+            window: None,
+            general: Option::None,
+        },
+        trace: None,
+        workspace_folders: None,
+        locale: Option::None,
+    };
+
+    let on_response = |resp: Option<Response>| match resp.unwrap().result_or_error {
+        ResponseResult::Result(_) => {}
+        ResponseResult::Error(e) => panic!("expected ResponseResult::Result(..), got {:?}", e),
+    };
+
+    let completable = MethodCompletable::new(ResponseCompletable::new(Some(Id::Number(1)), Box::new(on_response)));
+    server.initialize(initialize_params, completable);
+
+    assert_eq!(server.config.borrow().extra_shader_names, vec!["begin".to_string()]);
+    assert_eq!(server.config.borrow().max_merged_bytes, 1024);
+    assert_eq!(server.config.borrow().log_level, "info");
+    assert!(server.toplevel_files.borrow().is_top_level(Path::new("shaders/begin2.fsh")));
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_graph_discovery_order() {
+    // final.fsh includes a.glsl, which includes b.glsl, which includes c.glsl, each of them twice
+    let names = ["final.fsh", "a.glsl", "b.glsl", "c.glsl"];
+    let orders: [&[usize]; 3] = [&[0, 1, 2, 3], &[3, 2, 1, 0], &[2, 0, 3, 1, 2, 0]];
+
+    for order in orders {
+        let mut server = new_temp_server(None);
+        let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/09", &mut server);
+        let path = |name: &str| tmp_path.join("shaders").join(name);
+
+        for i in order {
+            server.add_file_and_includes_to_graph(&path(names[*i]));
+        }
+
+        let mut graph = server.graph.borrow_mut();
+        assert_eq!(graph.graph.node_count(), 4, "discovery order {:?}", order);
+        assert_eq!(graph.graph.edge_count(), 6, "discovery order {:?}", order);
+
+        for (parent, child) in names.iter().zip(names.iter().skip(1)) {
+            let parent = graph.find_node(&path(parent)).unwrap();
+            let child = graph.find_node(&path(child)).unwrap();
+            let parents: Vec<NodeIndex> = graph.graph.neighbors_directed(child, petgraph::Direction::Incoming).collect();
+            assert_eq!(parents, vec![parent, parent], "discovery order {:?}", order);
+        }
+
+        let c = graph.find_node(&path("c.glsl")).unwrap();
+        let root = graph.find_node(&path("final.fsh")).unwrap();
+        let roots: HashSet<NodeIndex> = graph.collect_root_ancestors(c).into_iter().collect();
+        assert_eq!(roots, HashSet::from([root]), "discovery order {:?}", order);
+        drop(graph);
+
+        server.endpoint.request_shutdown();
+    }
+}
+
+#[allow(deprecated)]
+#[test]
+#[logging_macro::log_scope]
+fn test_dry_run_initialize() {
+    let mut server = new_temp_server(None);
+    let output = SharedOutput::default();
+    let sink = output.clone();
+    server.endpoint = LSPEndpoint::create_lsp_output_with_output_stream(move || sink.clone());
+
+    let (_tmp_dir, tmp_path) = copy_to_tmp_dir("./testdata/24");
+
+    let initialize_params = InitializeParams {
+        process_id: None,
+        root_path: None,
+        root_uri: Some(Url::from_directory_path(&tmp_path).unwrap()),
+        client_info: None,
+        initialization_options: Some(serde_json::json!({ "dryRun": true })),
+        capabilities: ClientCapabilities {
+            workspace: None,
+            text_document: None,
+            experimental: None,
+            window: None,
+            general: Option::None,
+        },
+        trace: None,
+        workspace_folders: None,
+        locale: Option::None,
+    };
+
+    let on_response = |resp: Option<Response>| match resp.unwrap().result_or_error {
+        ResponseResult::Result(_) => {}
+        ResponseResult::Error(e) => panic!("expected ResponseResult::Result(..), got {:?}", e),
+    };
+
+    let completable = MethodCompletable::new(ResponseCompletable::new(Some(Id::Number(1)), Box::new(on_response)));
+    server.initialize(initialize_params, completable);
+
+    assert_eq!(server.graph.borrow().graph.node_count(), 0);
+    assert!(server.diagnostics.borrow().is_empty());
+
+    server.endpoint.request_shutdown();
+
+    let report = format!(
+        "Dry run: found shaders directory {} with 5 shader file(s), 4 of them top-level",
+        tmp_path.join("shaders").to_str().unwrap()
+    );
+    let sent = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+    assert!(sent.contains(ShowMessage::METHOD));
+    assert!(sent.contains(&serde_json::to_string(&report).unwrap()));
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_misplaced_shaders_hint() {
+    let mut server = new_temp_server(None);
+
+    // the programs were put directly into the pack, next to their includes
+    let tmp_dir = TempDir::new("mcshader").unwrap();
+    let tmp_path = tmp_dir.path().to_path_buf();
+    for name in ["final.fsh", "composite.vsh", "common.glsl"] {
+        fs::write(tmp_path.join(name), "#version 120\n").unwrap();
+    }
+    server.root = tmp_path.clone();
+
+    assert_eq!(
+        server.misplaced_shaders_hint(),
+        Some(format!(
+            "Found shader programs directly in {} (composite.vsh, final.fsh), but shaders are only loaded from its shaders directory. Move them into {}",
+            tmp_path.to_str().unwrap(),
+            tmp_path.join("shaders").to_str().unwrap()
+        ))
+    );
+
+    fs::create_dir(tmp_path.join("shaders")).unwrap();
+    assert_eq!(server.misplaced_shaders_hint(), None);
+
+    server.endpoint.request_shutdown();
+}
+
+#[allow(deprecated)]
+#[test]
+#[logging_macro::log_scope]
+fn test_quiet_initialize() {
+    let mut server = new_temp_server(None);
+    let output = SharedOutput::default();
+    let sink = output.clone();
+    server.endpoint = LSPEndpoint::create_lsp_output_with_output_stream(move || sink.clone());
+
+    let (_tmp_dir, tmp_path) = copy_to_tmp_dir("./testdata/01");
+
+    let initialize_params = InitializeParams {
+        process_id: None,
+        root_path: None,
+        root_uri: Some(Url::from_directory_path(&tmp_path).unwrap()),
+        client_info: None,
+        initialization_options: Some(serde_json::json!({ "mcglsl": { "quiet": true } })),
+        capabilities: ClientCapabilities {
+            workspace: None,
+            text_document: None,
+            experimental: None,
+            window: None,
+            general: Option::None,
+        },
+        trace: None,
+        workspace_folders: None,
+        locale: Option::None,
+    };
+
+    // responses are written to the same output as notifications, like the endpoint does when running
+    let respond = |sink: SharedOutput| {
+        move |resp: Option<Response>| {
+            let resp = resp.unwrap();
+            if let ResponseResult::Error(e) = &resp.result_or_error {
+                panic!("expected ResponseResult::Result(..), got {:?}", e);
+            }
+            serde_json::to_writer(sink, &resp).unwrap();
+        }
+    };
+
+    let completable = MethodCompletable::new(ResponseCompletable::new(Some(Id::Number(1)), Box::new(respond(output.clone()))));
+    server.initialize(initialize_params, completable);
+    assert!(server.config.borrow().quiet);
+
+    let params = ExecuteCommandParams {
+        command: "clearDiagnostics".into(),
+        arguments: vec![],
+        work_done_progress_params: Default::default(),
+    };
+    let completable = MethodCompletable::new(ResponseCompletable::new(Some(Id::Number(2)), Box::new(respond(output.clone()))));
+    server.execute_command(params, completable);
+
+    server.endpoint.request_shutdown();
+
+    let sent = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+    assert!(!sent.contains(lsp_ext::Status::METHOD));
+    assert!(!sent.contains("executed successfully"));
+    // quiet only leaves out notifications, requests are still answered
+    assert!(sent.contains(r#""id":1"#));
+    assert!(sent.contains(r#""id":2"#));
+}
+
+// a validator reporting an NVIDIA style error on every line using an undefined variable, at the position
+// the #line directives of the merged source map it to
+fn undefined_variable_validator() -> opengl::MockShaderValidator {
+    checked_undefined_variable_validator(|_| {})
+}
+
+// like `undefined_variable_validator`, also running `check` on each source it validates
+fn checked_undefined_variable_validator(check: impl Fn(&str) + Send + 'static) -> opengl::MockShaderValidator {
+    let mut mockgl = opengl::MockShaderValidator::new();
+    mockgl.expect_supported_stages().returning(opengl::all_stages);
+    mockgl.expect_vendor().returning(|| "NVIDIA Corporation".into());
+    mockgl.expect_validate().returning(move |_, source| {
+        check(source);
+        let origins = source_mapper::merged_line_origins(source);
+        let errors: Vec<String> = source
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| line.contains("undefined_"))
+            .map(|(i, _)| {
+                let (source_num, line) = origins[i].unwrap();
+                format!("{}({}) : error C1008: undefined variable", source_num, line + 1)
+            })
+            .collect();
+        Some(errors.join("\n"))
+    });
+    mockgl
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_compact_merge_diagnostics() {
+    let validator = checked_undefined_variable_validator(|source| assert!(!source.contains("\n\n\n")));
+    let mut server = new_temp_server(Some(Box::new(validator)));
+    server.config.borrow_mut().compact_merge = true;
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/15", &mut server);
+    server.build_initial_graph();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let diagnostics = server.lint(&final_path).unwrap();
+
+    let lines = |path: &Path| -> Vec<u32> {
+        diagnostics[&Url::from_file_path(path).unwrap()]
+            .iter()
+            .map(|d| d.range.start.line)
+            .collect()
+    };
+    assert_eq!(lines(&final_path), vec![9]);
+    assert_eq!(lines(&tmp_path.join("shaders").join("lib").join("a.glsl")), vec![3]);
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_max_diagnostics_per_file() {
+    let mut mockgl = opengl::MockShaderValidator::new();
+    mockgl.expect_supported_stages().returning(opengl::all_stages);
+    mockgl.expect_vendor().returning(|| "NVIDIA Corporation".into());
+    mockgl.expect_validate().returning(|_, _| {
+        let errors: Vec<String> = (1..=200).map(|i| format!("0({}) : error C0000: error {}", i % 11 + 1, i)).collect();
+        Some(errors.join("\n"))
+    });
+    let mut server = new_temp_server(Some(Box::new(mockgl)));
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/15", &mut server);
+    server.build_initial_graph();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let diagnostics = server.lint(&final_path).unwrap();
+
+    let final_diagnostics = &diagnostics[&Url::from_file_path(&final_path).unwrap()];
+    assert_eq!(final_diagnostics.len(), 101);
+    assert_eq!(final_diagnostics[0].message, "error 1");
+    assert_eq!(final_diagnostics[99].message, "error 100");
+    assert_eq!(
+        final_diagnostics[100].code,
+        Some(NumberOrString::String("diagnostics-truncated".into()))
+    );
+    assert!(final_diagnostics[100].message.starts_with("100 more diagnostic(s) hidden"));
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_diagnostic_target() {
+    for target in [configuration::DiagnosticTarget::Origin, configuration::DiagnosticTarget::Root] {
+        let mut server = new_temp_server(Some(Box::new(undefined_variable_validator())));
+        server.config.borrow_mut().diagnostic_target = target;
+
+        let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/15", &mut server);
+        server.build_initial_graph();
+
+        let final_path = tmp_path.join("shaders").join("final.fsh");
+        let lib_path = tmp_path.join("shaders").join("lib").join("a.glsl");
+        let diagnostics = server.lint(&final_path).unwrap();
+
+        let final_diagnostics = &diagnostics[&Url::from_file_path(&final_path).unwrap()];
+        let lib_diagnostics = &diagnostics[&Url::from_file_path(&lib_path).unwrap()];
+        match target {
+            configuration::DiagnosticTarget::Origin => {
+                assert_eq!(final_diagnostics.len(), 1);
+                assert_eq!(lib_diagnostics.len(), 1);
+                assert_eq!(lib_diagnostics[0].range.start.line, 3);
+            }
+            configuration::DiagnosticTarget::Root => {
+                assert_eq!(final_diagnostics.len(), 2);
+                assert!(lib_diagnostics.is_empty());
+
+                let remapped = final_diagnostics.iter().find(|d| d.range.start.line == 4).unwrap();
+                assert_eq!(remapped.range, Range::new(Position::new(4, 10), Position::new(4, 21)));
+                let origin = Path::new("shaders").join("lib").join("a.glsl");
+                assert!(remapped.message.ends_with(&format!("({}:4)", origin.to_str().unwrap())));
+            }
+        }
+
+        server.endpoint.request_shutdown();
+    }
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_lint_scope_file() {
+    let mut mockgl = opengl::MockShaderValidator::new();
+    mockgl.expect_supported_stages().returning(opengl::all_stages);
+    mockgl.expect_validate().times(0);
+    let mut server = new_temp_server(Some(Box::new(mockgl)));
+    server.config.borrow_mut().lint_scope = configuration::LintScope::File;
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/09", &mut server);
+    server.build_initial_graph();
+
+    // a.glsl is included by final.fsh, which is not validated
+    let include_path = tmp_path.join("shaders").join("a.glsl");
+    let diagnostics = server.lint(&include_path).unwrap();
+
+    assert_eq!(
+        diagnostics.keys().collect::<Vec<_>>(),
+        vec![&Url::from_file_path(&include_path).unwrap()]
+    );
+    assert!(diagnostics[&Url::from_file_path(&include_path).unwrap()].is_empty());
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_validate_standalone_include() {
+    let mut mockgl = opengl::MockShaderValidator::new();
+    mockgl.expect_supported_stages().returning(opengl::all_stages);
+    // program/final.glsl is validated on its own, as the fragment shader final.fsh includes it in,
+    // whereas lib/util.glsl isn't self-contained and is still validated through final.fsh
+    mockgl
+        .expect_validate()
+        .withf(|tree_type, source| *tree_type == TreeType::Fragment && source.starts_with("#version 120\n"))
+        .times(1)
+        .returning(|_, _| None);
+    mockgl
+        .expect_validate()
+        .withf(|tree_type, source| *tree_type == TreeType::Fragment && !source.starts_with("#version"))
+        .times(1)
+        .returning(|_, _| None);
+    let mut server = new_temp_server(Some(Box::new(mockgl)));
+    server.tree_sitter.borrow_mut().set_language(tree_sitter_glsl::language()).unwrap();
+    server.config.borrow_mut().validate_standalone_includes = true;
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/28", &mut server);
+    server.build_initial_graph();
+
+    let program_path = tmp_path.join("shaders").join("program").join("final.glsl");
+    let util_path = tmp_path.join("shaders").join("lib").join("util.glsl");
+
+    let diagnostics = server.lint(&program_path).unwrap();
+    let mut files: Vec<_> = diagnostics.keys().cloned().collect();
+    files.sort();
+    assert_eq!(
+        files,
+        vec![
+            Url::from_file_path(&util_path).unwrap(),
+            Url::from_file_path(&program_path).unwrap()
+        ]
+    );
+    assert!(diagnostics.values().all(|file_diagnostics| file_diagnostics.is_empty()));
+
+    let diagnostics = server.lint(&util_path).unwrap();
+    assert!(diagnostics.contains_key(&Url::from_file_path(tmp_path.join("shaders").join("final.fsh")).unwrap()));
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_navigation_outside_workspace() {
+    let mut server = new_temp_server(None);
+    server.tree_sitter.borrow_mut().set_language(tree_sitter_glsl::language()).unwrap();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/09", &mut server);
+    server.build_initial_graph();
+
+    let other_dir = TempDir::new("mcshader").unwrap();
+    let other_path = other_dir.path().join("final.fsh");
+    fs::copy(tmp_path.join("shaders").join("final.fsh"), &other_path).unwrap();
+
+    assert!(server.in_workspace(&tmp_path.join("shaders").join("final.fsh")));
+    assert!(!server.in_workspace(&other_path));
+
+    // requests for files of other workspaces are answered, just without any results
+    let on_response = |resp: Option<Response>| match resp.unwrap().result_or_error {
+        ResponseResult::Result(symbols) => assert_eq!(symbols, serde_json::json!([])),
+        ResponseResult::Error(e) => panic!("expected ResponseResult::Result(..), got {:?}", e),
+    };
+
+    let params = DocumentSymbolParams {
+        text_document: TextDocumentIdentifier {
+            uri: Url::from_file_path(&other_path).unwrap(),
+        },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let completable = MethodCompletable::new(ResponseCompletable::new(Some(Id::Number(1)), Box::new(on_response)));
+    server.document_symbols(params, completable);
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_configured_encoding() {
+    let mut server = new_temp_server(None);
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/33", &mut server);
+    server.config.borrow_mut().encoding = "windows-1252".into();
+    server.build_initial_graph();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let tone_path = tmp_path.join("shaders").join("lib").join("tone.glsl");
+
+    let final_idx = server.graph.borrow_mut().find_node(&final_path).unwrap();
+    let tone_idx = server.graph.borrow_mut().find_node(&tone_path).unwrap();
+    assert_eq!(
+        server.graph.borrow().get_child_positions(final_idx, tone_idx).collect::<Vec<_>>(),
+        vec![IncludePosition {
+            line: 3,
+            start: 10,
+            end: 24
+        }]
+    );
+
+    let nodes = server.get_dfs_for_node(final_idx).unwrap();
+    let sources = server.load_sources(&nodes).unwrap();
+    assert!(sources.get(&final_path).unwrap().contains("// café au lait — a warm tone"));
+    assert!(sources.get(&tone_path).unwrap().starts_with("// © 2022"));
+
+    // read as UTF-8, the files aren't valid
+    server.config.borrow_mut().encoding = "utf-8".into();
+    assert!(server.load_sources(&nodes).is_err());
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_sources_read_once() {
+    let mut server = new_temp_server(Some(Box::new(RecordingValidator::new(None))));
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/09", &mut server);
+    server.build_initial_graph();
+
+    // scanning for includes reads each of final.fsh, a.glsl, b.glsl and c.glsl
+    assert_eq!(server.file_cache.borrow().reads(), 4);
+
+    // merging reuses what the scan read
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    server.lint(&final_path).unwrap();
+    assert_eq!(server.file_cache.borrow().reads(), 4);
+
+    // until a file changes
+    let c_path = tmp_path.join("shaders").join("c.glsl");
+    fs::write(&c_path, "float c() {\n\treturn 2.0;\n}\n\n").unwrap();
+    server.lint(&final_path).unwrap();
+    assert_eq!(server.file_cache.borrow().reads(), 5);
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_equivalent_paths() {
+    let validator = RecordingValidator::new(None);
+    let validated = validator.validated.clone();
+    let mut server = new_temp_server(Some(Box::new(validator)));
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/09", &mut server);
+    server.build_initial_graph();
+    let nodes = server.graph.borrow().graph.node_count();
+
+    // the same files as the scan found, spelled differently
+    let final_path = tmp_path.join("shaders").join(".").join("final.fsh");
+    let c_path = tmp_path.join("shaders").join("lib").join("..").join("c.glsl");
+
+    assert!(server.graph.borrow_mut().find_node(&final_path).is_some());
+
+    // c.glsl is read again and its program linted, rather than it being added to the graph as a new file
+    run_server_command(&server, &commands::invalidate::InvalidateCommand, vec![serde_json::json!(c_path)]).unwrap();
+    assert_eq!(server.file_cache.borrow().reads(), 5);
+    assert_eq!(server.graph.borrow().graph.node_count(), nodes);
+    assert_eq!(validated.borrow().len(), 1);
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_invalidate_file() {
+    let validator = RecordingValidator::new(None);
+    let validated = validator.validated.clone();
+    let mut server = new_temp_server(Some(Box::new(validator)));
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/09", &mut server);
+    server.build_initial_graph();
+    assert_eq!(server.file_cache.borrow().reads(), 4);
+
+    // only the invalidated file is read again, though it is unchanged, and the shader including it is linted
+    let c_path = tmp_path.join("shaders").join("c.glsl");
+    assert_eq!(
+        run_server_command(&server, &commands::invalidate::InvalidateCommand, vec![serde_json::json!(c_path)]).unwrap(),
+        Value::Null
+    );
+    assert_eq!(server.file_cache.borrow().reads(), 5);
+    assert_eq!(validated.borrow().len(), 1);
+
+    run_server_command(&server, &commands::invalidate::InvalidateCommand, vec![serde_json::json!(c_path)]).unwrap();
+    assert_eq!(server.file_cache.borrow().reads(), 6);
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+#[logging_macro::log_scope]
+fn test_redetect_vendor_clears_cache() {
+    let mut server = new_temp_server(None);
+    let mut fs = vendor_cache::MockCacheFs::new();
+    fs.expect_remove_file()
+        .withf(|path| path.ends_with("mcshader-lsp/vendor"))
+        .times(1)
+        .returning(|_| Ok(()));
+    server.vendor_cache = vendor_cache::VendorCache::new(Some(PathBuf::from("/cache/mcshader-lsp/vendor")), Rc::new(fs));
+
+    // the cache is kept unless asked otherwise
+    server.apply_configuration(&serde_json::json!({}));
+    server.apply_configuration(&serde_json::json!({ "redetectVendor": true }));
+
+    server.endpoint.request_shutdown();
+}
@@ -0,0 +1,51 @@
+use std::io;
+
+use encoding_rs::{Encoding, UTF_8};
+use slog_scope::warn;
+
+// the encoding with the given label, e.g. `windows-1252`, falling back to UTF-8 for labels that aren't recognized
+fn encoding_for_label(label: &str) -> &'static Encoding {
+    match Encoding::for_label(label.as_bytes()) {
+        Some(encoding) => encoding,
+        None => {
+            warn!("unknown file encoding configured, falling back to UTF-8"; "encoding" => label);
+            UTF_8
+        }
+    }
+}
+
+/// Decodes the contents of a file saved in the encoding with the given label. UTF-8 files must be valid,
+/// like with `fs::read_to_string`, while other encodings are decoded lossily. A byte order mark takes
+/// precedence over the label.
+pub fn decode(bytes: &[u8], label: &str) -> io::Result<String> {
+    // the byte order mark isn't part of the source, even for UTF-8
+    let (encoding, bytes) = match Encoding::for_bom(bytes) {
+        Some((encoding, bom_length)) => (encoding, &bytes[bom_length..]),
+        None => (encoding_for_label(label), bytes),
+    };
+    if encoding == UTF_8 {
+        return String::from_utf8(bytes.to_vec()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+    }
+    Ok(encoding.decode_without_bom_handling(bytes).0.into_owned())
+}
+
+/// Decodes the contents of a file saved in the encoding with the given label, replacing anything that
+/// can't be decoded.
+pub fn decode_lossy(bytes: &[u8], label: &str) -> String {
+    encoding_for_label(label).decode(bytes).0.into_owned()
+}
+
+#[cfg(test)]
+mod text_encoding_test {
+    use crate::text_encoding::decode;
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_decode_strips_bom() {
+        assert_eq!(decode(b"\xEF\xBB\xBF#version 120\n", "utf-8").unwrap(), "#version 120\n");
+        assert_eq!(decode(b"\xEF\xBB\xBF#version 120\n", "windows-1252").unwrap(), "#version 120\n");
+        assert_eq!(decode(b"\xFF\xFE#\0v\0", "utf-8").unwrap(), "#v");
+        assert_eq!(decode(b"caf\xE9", "windows-1252").unwrap(), "caf\u{e9}");
+        assert!(decode(b"caf\xE9", "utf-8").is_err());
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+use path_slash::PathExt;
+use regex::Regex;
+
+lazy_static! {
+    static ref RE_WORLD_FOLDER: Regex = Regex::new(r#"^shaders(/world-?\d+)?"#).unwrap();
+    static ref RE_WORLD_FOLDER_NAME: Regex = Regex::new(r#"^world-?\d+$"#).unwrap();
+}
+
+pub const DEFAULT_MAX_SHADER_INDEX: usize = 99;
+
+// program names that may be suffixed with an index, e.g. composite1.fsh
+const INDEXED_PROGRAMS: &[&str] = &["composite", "deferred", "prepare", "shadowcomp"];
+
+const PROGRAMS: &[&str] = &[
+    "composite_pre",
+    "deferred_pre",
+    "final",
+    "gbuffers_armor_glint",
+    "gbuffers_basic",
+    "gbuffers_beaconbeam",
+    "gbuffers_block",
+    "gbuffers_clouds",
+    "gbuffers_damagedblock",
+    "gbuffers_entities",
+    "gbuffers_entities_glowing",
+    "gbuffers_hand",
+    "gbuffers_hand_water",
+    "gbuffers_item",
+    "gbuffers_line",
+    "gbuffers_skybasic",
+    "gbuffers_skytextured",
+    "gbuffers_spidereyes",
+    "gbuffers_terrain",
+    "gbuffers_terrain_cutout",
+    "gbuffers_terrain_cutout_mip",
+    "gbuffers_terrain_solid",
+    "gbuffers_textured",
+    "gbuffers_textured_lit",
+    "gbuffers_water",
+    "gbuffers_weather",
+    "shadow",
+    "shadow_cutout",
+    "shadow_solid",
+];
+
+/// The set of file names recognized as top-level shader programs. Built from the
+/// Optifine/Iris program names plus any user configured program names, so that new
+/// programs can be opted into at runtime without rebuilding the server.
+pub struct TopLevelFiles {
+    names: HashSet<String>,
+}
+
+impl TopLevelFiles {
+    /// Builds the set of top-level file names. `extra_programs` are treated like
+    /// `composite`, i.e. they are also recognized with an index suffix up to `max_index`.
+    pub fn new(extra_programs: &[String], max_index: usize) -> TopLevelFiles {
+        let indexed: Vec<&str> = INDEXED_PROGRAMS
+            .iter()
+            .copied()
+            .chain(extra_programs.iter().map(|s| s.as_str()))
+            .collect();
+
+        let mut names = HashSet::with_capacity((indexed.len() * (max_index + 1) * 27) + (PROGRAMS.len() * 4));
+        for ext in ["fsh", "vsh", "gsh", "csh"] {
+            for program in &indexed {
+                names.insert(format!("{}.{}", program, ext));
+                for i in 1..=max_index {
+                    names.insert(format!("{}{}.{}", program, i, ext));
+                }
+            }
+            for program in PROGRAMS {
+                names.insert(format!("{}.{}", program, ext));
+            }
+        }
+        let base_char_num = b'a';
+        for suffix_num in 0u8..=25u8 {
+            let suffix_char = (base_char_num + suffix_num) as char;
+            for program in &indexed {
+                names.insert(format!("{}_{}.csh", program, suffix_char));
+                for i in 1..=max_index {
+                    names.insert(format!("{}{}_{}.csh", program, i, suffix_char));
+                }
+            }
+        }
+
+        TopLevelFiles { names }
+    }
+
+    /// Returns whether `path`, relative to the project root, is a top-level shader
+    /// file in either the shaders folder or one of its world folders.
+    pub fn is_top_level(&self, path: &Path) -> bool {
+        let path = path.to_slash().unwrap();
+        if !RE_WORLD_FOLDER.is_match(&path) {
+            return false;
+        }
+        let parts: Vec<&str> = path.split('/').collect();
+        let len = parts.len();
+        (len == 3 || len == 2) && self.names.contains(parts[len - 1])
+    }
+}
+
+/// Returns the base pack's counterpart of `path` if it is in one of the world folders of `shaders_root`, e.g.
+/// `shaders/lib/common.glsl` for `shaders/world0/lib/common.glsl`. World folders override the base pack's files
+/// they contain, and fall back to it for the rest.
+pub fn base_pack_path(path: &Path, shaders_root: &Path) -> Option<PathBuf> {
+    let mut components = path.strip_prefix(shaders_root).ok()?.components();
+    let world_folder = components.next()?.as_os_str().to_str()?;
+    if !RE_WORLD_FOLDER_NAME.is_match(world_folder) || components.as_path().as_os_str().is_empty() {
+        return None;
+    }
+    Some(shaders_root.join(components.as_path()))
+}
+
+impl Default for TopLevelFiles {
+    fn default() -> Self {
+        TopLevelFiles::new(&[], DEFAULT_MAX_SHADER_INDEX)
+    }
+}
+
+#[cfg(test)]
+mod toplevel_test {
+    use std::path::PathBuf;
+
+    use crate::toplevel::{base_pack_path, TopLevelFiles};
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_default_toplevel_files() {
+        let files = TopLevelFiles::default();
+
+        assert!(files.is_top_level(&PathBuf::from("shaders/final.fsh")));
+        assert!(files.is_top_level(&PathBuf::from("shaders/world-1/composite99.vsh")));
+        assert!(files.is_top_level(&PathBuf::from("shaders/deferred12_c.csh")));
+        assert!(!files.is_top_level(&PathBuf::from("shaders/composite100.fsh")));
+        assert!(!files.is_top_level(&PathBuf::from("shaders/common.glsl")));
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_base_pack_path() {
+        let root = PathBuf::from("/pack/shaders");
+
+        assert_eq!(
+            base_pack_path(&root.join("world0/common.glsl"), &root),
+            Some(root.join("common.glsl"))
+        );
+        assert_eq!(
+            base_pack_path(&root.join("world-1/lib/sky.glsl"), &root),
+            Some(root.join("lib/sky.glsl"))
+        );
+        assert_eq!(base_pack_path(&root.join("lib/common.glsl"), &root), None);
+        assert_eq!(base_pack_path(&root.join("world0"), &root), None);
+        assert_eq!(base_pack_path(&PathBuf::from("/elsewhere/world0/common.glsl"), &root), None);
+    }
+}
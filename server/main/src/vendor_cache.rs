@@ -0,0 +1,204 @@
+use std::env;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use slog_scope::{error, info};
+
+#[cfg(test)]
+use mockall::automock;
+
+const CACHE_DIR_NAME: &str = "mcshader-lsp";
+const VENDOR_FILE_NAME: &str = "vendor";
+
+/// The file operations the vendor cache is kept with.
+#[cfg_attr(test, automock)]
+pub trait CacheFs {
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+    /// Writes `contents` to `path`, creating its directory if needed.
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()>;
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+}
+
+/// The `CacheFs` of the actual filesystem.
+pub struct DiskFs;
+
+impl CacheFs for DiskFs {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, contents)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+}
+
+/// Keeps the OpenGL vendor detected on a previous run, so the compile log format can be picked on startup
+/// without creating a context just to ask the driver.
+#[derive(Clone)]
+pub struct VendorCache {
+    // where the vendor is kept. Nothing is read or written when unset
+    path: Option<PathBuf>,
+    fs: Rc<dyn CacheFs>,
+}
+
+impl Default for VendorCache {
+    fn default() -> Self {
+        VendorCache::new(None, Rc::new(DiskFs))
+    }
+}
+
+impl VendorCache {
+    pub fn new(path: Option<PathBuf>, fs: Rc<dyn CacheFs>) -> VendorCache {
+        VendorCache { path, fs }
+    }
+
+    /// A cache kept in the platform's cache directory, or a disabled one if that can't be found.
+    pub fn in_platform_cache_dir() -> VendorCache {
+        VendorCache::new(
+            platform_cache_dir().map(|dir| dir.join(CACHE_DIR_NAME).join(VENDOR_FILE_NAME)),
+            Rc::new(DiskFs),
+        )
+    }
+
+    /// The cached vendor, if one was written and can be read.
+    pub fn read(&self) -> Option<String> {
+        let path = self.path.as_ref()?;
+        match self.fs.read_to_string(path) {
+            Ok(vendor) if !vendor.trim().is_empty() => Some(vendor.trim().to_string()),
+            Ok(_) => None,
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => {
+                error!("failed to read cached OpenGL vendor"; "path" => path.to_str().unwrap(), "error" => e.to_string());
+                None
+            }
+        }
+    }
+
+    pub fn write(&self, vendor: &str) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+        match self.fs.write(path, vendor) {
+            Ok(_) => info!("cached OpenGL vendor"; "path" => path.to_str().unwrap(), "vendor" => vendor),
+            Err(e) => error!("failed to cache OpenGL vendor"; "path" => path.to_str().unwrap(), "error" => e.to_string()),
+        }
+    }
+
+    /// Forgets the cached vendor, so it is detected again the next time it is needed.
+    pub fn clear(&self) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+        match self.fs.remove_file(path) {
+            Ok(_) => info!("cleared cached OpenGL vendor"; "path" => path.to_str().unwrap()),
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => error!("failed to clear cached OpenGL vendor"; "path" => path.to_str().unwrap(), "error" => e.to_string()),
+        }
+    }
+}
+
+// %LOCALAPPDATA% on Windows, ~/Library/Caches on macOS and $XDG_CACHE_HOME or ~/.cache elsewhere
+fn platform_cache_dir() -> Option<PathBuf> {
+    let home = || env::var_os("HOME").map(PathBuf::from);
+    if cfg!(windows) {
+        env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library").join("Caches"))
+    } else {
+        env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            // relative paths are to be ignored, per the XDG base directory spec
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| home().map(|home| home.join(".cache")))
+    }
+}
+
+#[cfg(test)]
+mod vendor_cache_test {
+    use std::cell::RefCell;
+    use std::io::{self, ErrorKind};
+    use std::path::PathBuf;
+    use std::rc::Rc;
+
+    use crate::vendor_cache::{MockCacheFs, VendorCache};
+
+    // a filesystem holding at most the one cache file, in memory
+    fn stub_fs(file: Rc<RefCell<Option<String>>>) -> MockCacheFs {
+        let mut fs = MockCacheFs::new();
+        let read = file.clone();
+        fs.expect_read_to_string()
+            .withf(|path| path.ends_with("mcshader-lsp/vendor"))
+            .returning_st(move |_| read.borrow().clone().ok_or_else(|| io::Error::from(ErrorKind::NotFound)));
+        let written = file.clone();
+        fs.expect_write()
+            .withf(|path, _| path.ends_with("mcshader-lsp/vendor"))
+            .returning_st(move |_, contents| {
+                written.replace(Some(contents.to_string()));
+                Ok(())
+            });
+        fs.expect_remove_file().returning_st(move |_| match file.take() {
+            Some(_) => Ok(()),
+            None => Err(ErrorKind::NotFound.into()),
+        });
+        fs
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_vendor_cache_round_trip() {
+        let file = Rc::new(RefCell::new(None));
+        let cache = VendorCache::new(Some(PathBuf::from("/cache/mcshader-lsp/vendor")), Rc::new(stub_fs(file.clone())));
+
+        assert_eq!(cache.read(), None);
+
+        cache.write("NVIDIA Corporation");
+        assert_eq!(*file.borrow(), Some("NVIDIA Corporation".into()));
+        assert_eq!(cache.read(), Some("NVIDIA Corporation".into()));
+
+        cache.write("Intel");
+        assert_eq!(cache.read(), Some("Intel".into()));
+
+        cache.clear();
+        assert_eq!(*file.borrow(), None);
+        assert_eq!(cache.read(), None);
+        // clearing twice is fine
+        cache.clear();
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_vendor_cache_ignores_blank_and_unreadable() {
+        let file = Rc::new(RefCell::new(Some("  \n".to_string())));
+        let cache = VendorCache::new(Some(PathBuf::from("/cache/mcshader-lsp/vendor")), Rc::new(stub_fs(file.clone())));
+        assert_eq!(cache.read(), None);
+
+        file.replace(Some("ATI Technologies\n".into()));
+        assert_eq!(cache.read(), Some("ATI Technologies".into()));
+
+        let mut fs = MockCacheFs::new();
+        fs.expect_read_to_string().returning(|_| Err(ErrorKind::PermissionDenied.into()));
+        let unreadable = VendorCache::new(Some(PathBuf::from("/cache/mcshader-lsp/vendor")), Rc::new(fs));
+        assert_eq!(unreadable.read(), None);
+    }
+
+    #[test]
+    #[logging_macro::log_scope]
+    fn test_disabled_vendor_cache() {
+        // the mock has no expectations, so any file operation fails the test
+        let disabled = VendorCache::new(None, Rc::new(MockCacheFs::new()));
+        disabled.write("NVIDIA Corporation");
+        assert_eq!(disabled.read(), None);
+        disabled.clear();
+    }
+}
@@ -0,0 +1,5 @@
+#define PI 3.14159
+
+float square(float x) {
+	return x * x;
+}
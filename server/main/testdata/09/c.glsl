@@ -0,0 +1,5 @@
+float c() {
+	// padding padding padding padding padding padding padding padding padding padding
+	// padding padding padding padding padding padding padding padding padding padding
+	return 1.0;
+}
@@ -0,0 +1,3 @@
+float shared() {
+	return 0.5;
+}
@@ -0,0 +1,3 @@
+float common() {
+	return 1.0;
+}
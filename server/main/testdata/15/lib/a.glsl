@@ -0,0 +1,5 @@
+float a() {
+
+
+	return undefined_a;
+}
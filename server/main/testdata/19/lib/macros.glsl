@@ -0,0 +1,2 @@
+// common macros
+#define SQ(x) ((x) * (x))
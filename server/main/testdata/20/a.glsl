@@ -0,0 +1,3 @@
+float a() {
+	return 1.0;
+}
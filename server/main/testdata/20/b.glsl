@@ -0,0 +1,3 @@
+float b() {
+	return 1.0;
+}
@@ -0,0 +1,9 @@
+void clip(float alpha) {
+	if (alpha < 0.1) discard;
+}
+
+void clipFragment(float alpha) {
+#ifdef FRAGMENT_SHADER
+	if (alpha < 0.5) discard;
+#endif
+}
@@ -0,0 +1,3 @@
+float luma(vec3 c) {
+	return dot(c, vec3(0.3, 0.6, 0.1));
+}
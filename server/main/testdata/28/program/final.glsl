@@ -0,0 +1,7 @@
+#version 120
+
+#include "/lib/util.glsl"
+
+void main() {
+	gl_FragColor = vec4(luma(vec3(0.5)));
+}
@@ -0,0 +1,5 @@
+vec3 color = vec3(1.0);
+if (color.r > 0.5) {
+    #include "/lib/inner.glsl"
+}
+gl_FragColor = vec4(color, 1.0);
@@ -0,0 +1 @@
+color *= 0.5;
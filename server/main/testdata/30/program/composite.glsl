@@ -0,0 +1,11 @@
+#ifdef FRAGMENT_SHADER
+void main() {
+	gl_FragColor = vec4(1.0);
+}
+#endif
+
+#ifdef VERTEX_SHADER
+void main() {
+	gl_Position = ftransform();
+}
+#endif
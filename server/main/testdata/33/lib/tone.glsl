@@ -0,0 +1,4 @@
+//  2022
+vec3 tone() {
+	return vec3(1.0, 0.9, 0.8);
+}
@@ -0,0 +1,4 @@
+float draft(float x) {
+	return x * 2.0;
+
+/* TODO: finish
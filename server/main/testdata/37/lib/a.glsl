@@ -0,0 +1,4 @@
+#include "/lib/b.glsl"
+float a() {
+	return b();
+}
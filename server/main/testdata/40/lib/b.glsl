@@ -0,0 +1,5 @@
+#include "/lib/a.glsl"
+
+float b() {
+	return 1.0;
+}
@@ -0,0 +1,3 @@
+float scale() {
+	return 1.0;
+}
@@ -0,0 +1,3 @@
+float square(float x) {
+	return x * x;
+}
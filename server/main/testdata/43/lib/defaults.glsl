@@ -0,0 +1,7 @@
+#ifndef SHADOW_RES
+#define SHADOW_RES 1024
+#endif
+
+#if !defined(BLOOM_STRENGTH)
+#define BLOOM_STRENGTH 1.0
+#endif
@@ -0,0 +1,2 @@
+#define SHADOW_RES 1024
+#define BLOOM_STRENGTH  0.5 // default
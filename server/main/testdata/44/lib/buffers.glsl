@@ -0,0 +1,4 @@
+layout(std140, binding = 0) uniform CameraData {
+	mat4 view;
+	mat4 projection;
+};
@@ -0,0 +1,13 @@
+#define INTENSITY 0.8
+#define SHADOWS
+
+uniform float frameTimeCounter;
+
+struct Light {
+	vec3 color;
+	float intensity;
+};
+
+vec4 shade(Light light) {
+	return vec4(light.color * light.intensity, 1.0);
+}
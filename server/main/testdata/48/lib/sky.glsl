@@ -0,0 +1,3 @@
+vec3 sky() {
+	return vec3(0.5, 0.7, 1.0);
+}
@@ -0,0 +1,7 @@
+vec4 bloom(vec4 color) {
+#ifdef BLOOM
+	return color * 1.5;
+#else
+	return color;
+#endif
+}
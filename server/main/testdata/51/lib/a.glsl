@@ -0,0 +1,3 @@
+float a() {
+	return undefined_a;
+}